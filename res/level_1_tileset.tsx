@@ -194,11 +194,11 @@
  </tile>
  <tile id="48">
   <properties>
-   <property name="arena_height" value="10"/>
-   <property name="arena_width" value="17"/>
+   <property name="arena_height" type="float" value="10"/>
+   <property name="arena_width" type="float" value="17"/>
    <property name="entity_class" value="SpawnPoint"/>
    <property name="spawned_entity_class" value="BossFish"/>
-   <property name="water_height" value="4"/>
+   <property name="water_height" type="float" value="4"/>
   </properties>
  </tile>
  <tile id="50">
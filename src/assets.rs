@@ -0,0 +1,346 @@
+use std::{
+    collections::HashMap,
+    fs,
+    io::{Cursor, Read, Write},
+    path::{Component, Path, PathBuf},
+    sync::OnceLock,
+};
+
+use anyhow::{Context, Result};
+
+use crate::Options;
+
+// ---------------------------------------------------------------------------------------------------------------------
+
+/// Name of the archive loaded from the asset root when no archive is specified
+pub const DEFAULT_ARCHIVE_NAME: &str = "assets.pak";
+
+/// The game's assets live in this directory of the asset root
+const ASSETS_DIR: &str = "res";
+
+const ARCHIVE_MAGIC: &[u8; 8] = b"PLATPAK1";
+
+static LOCATOR: OnceLock<AssetLocator> = OnceLock::new();
+
+/// Makes `locator` the AssetLocator used to read assets for the remainder of the process.
+/// Must be called before any asset is read; fails if a locator is already in use.
+pub fn install(locator: AssetLocator) -> Result<()> {
+    LOCATOR
+        .set(locator)
+        .map_err(|_| anyhow::anyhow!("An AssetLocator is already installed"))
+}
+
+/// Returns the installed AssetLocator, or if none was installed, one reading from the working directory.
+pub fn locator() -> &'static AssetLocator {
+    LOCATOR.get_or_init(|| AssetLocator::new(PathBuf::from("."), None))
+}
+
+/// Reads the asset at `path` with the installed AssetLocator.
+pub fn read<P: AsRef<Path>>(path: P) -> Result<Vec<u8>> {
+    locator().read(path.as_ref())
+}
+
+/// Opens the asset at `path` with the installed AssetLocator, for buffered and seekable reading.
+pub fn open<P: AsRef<Path>>(path: P) -> Result<Cursor<Vec<u8>>> {
+    read(path).map(Cursor::new)
+}
+
+/// Lexically normalizes `path`, removing "." components and resolving ".." components where
+/// possible, such that different relative paths to the same asset are equal.
+pub fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if !normalized.pop() {
+                    normalized.push("..");
+                }
+            }
+            c => normalized.push(c),
+        }
+    }
+    normalized
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+
+/// Resolves asset paths, e.g., "res/level_1.tmx", which are relative to an asset root directory.
+/// An asset is read from the file system if present, and otherwise from the optional archive.
+/// Absolute paths are read from the file system as-is.
+pub struct AssetLocator {
+    root: PathBuf,
+    archive: Option<Archive>,
+}
+
+impl AssetLocator {
+    pub fn new(root: PathBuf, archive: Option<Archive>) -> Self {
+        Self { root, archive }
+    }
+
+    /// Creates an AssetLocator per `options`. Without --asset-root, the root is the executable's
+    /// directory if it holds the game's assets, and otherwise the working directory. Without
+    /// --asset-archive, the root's assets.pak is used if present.
+    pub fn from_options(options: &Options) -> Result<Self> {
+        let root = match &options.asset_root {
+            Some(root) => root.clone(),
+            None => std::env::current_exe()
+                .ok()
+                .and_then(|exe| exe.parent().map(Path::to_path_buf))
+                .filter(|dir| {
+                    dir.join(ASSETS_DIR).is_dir() || dir.join(DEFAULT_ARCHIVE_NAME).is_file()
+                })
+                .unwrap_or_else(|| PathBuf::from(".")),
+        };
+
+        let archive_path = match &options.asset_archive {
+            Some(archive) => Some(archive.clone()),
+            None => Some(root.join(DEFAULT_ARCHIVE_NAME)).filter(|archive| archive.is_file()),
+        };
+        let archive = archive_path.map(|path| Archive::load(&path)).transpose()?;
+
+        log::info!(
+            "Loading assets from {:?}{}",
+            root,
+            if archive.is_some() {
+                " and archive"
+            } else {
+                ""
+            }
+        );
+        Ok(Self::new(root, archive))
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// The directory on the file system holding the game's assets
+    pub fn assets_dir(&self) -> PathBuf {
+        self.root.join(ASSETS_DIR)
+    }
+
+    /// Reads the asset at `path`
+    pub fn read(&self, path: &Path) -> Result<Vec<u8>> {
+        if path.is_absolute() {
+            return fs::read(path).with_context(|| format!("Unable to read {:?}", path));
+        }
+
+        let file_path = self.root.join(path);
+        if file_path.is_file() {
+            return fs::read(&file_path).with_context(|| format!("Unable to read {:?}", file_path));
+        }
+
+        match self.archive.as_ref().and_then(|archive| archive.get(path)) {
+            Some(bytes) => Ok(bytes.to_vec()),
+            None => anyhow::bail!("Unable to find asset {:?} in {:?}", path, self.root),
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+
+/// A set of assets packed in a single file. The file is the magic bytes "PLATPAK1", followed by
+/// a little-endian u32 count of entries, each of which is a u32 path length, the utf8 path relative
+/// to the asset root with '/' separators, a u64 data length and the data.
+#[derive(Default)]
+pub struct Archive {
+    files: HashMap<PathBuf, Vec<u8>>,
+}
+
+impl Archive {
+    pub fn load(path: &Path) -> Result<Self> {
+        let bytes = fs::read(path).with_context(|| format!("Unable to read archive {:?}", path))?;
+        Self::parse(&bytes).with_context(|| format!("Invalid archive {:?}", path))
+    }
+
+    /// Packs each file under `root`'s asset directory, returning the archive.
+    pub fn pack(root: &Path) -> Result<Self> {
+        let mut archive = Archive::default();
+        let mut dirs = vec![root.join(ASSETS_DIR)];
+        while let Some(dir) = dirs.pop() {
+            for entry in fs::read_dir(&dir).with_context(|| format!("Unable to read {:?}", dir))? {
+                let path = entry?.path();
+                if path.is_dir() {
+                    dirs.push(path);
+                } else {
+                    let bytes =
+                        fs::read(&path).with_context(|| format!("Unable to read {:?}", path))?;
+                    let asset_path = path.strip_prefix(root)?.to_path_buf();
+                    archive.insert(&asset_path, bytes);
+                }
+            }
+        }
+        Ok(archive)
+    }
+
+    pub fn insert(&mut self, path: &Path, bytes: Vec<u8>) {
+        self.files.insert(normalize(path), bytes);
+    }
+
+    pub fn get(&self, path: &Path) -> Option<&[u8]> {
+        self.files.get(&normalize(path)).map(Vec::as_slice)
+    }
+
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let mut file = fs::File::create(path)
+            .with_context(|| format!("Unable to create archive {:?}", path))?;
+        file.write_all(&self.to_bytes()?)?;
+        Ok(())
+    }
+
+    fn to_bytes(&self) -> Result<Vec<u8>> {
+        // sorted, so packing the same assets produces the same archive
+        let mut paths = self.files.keys().collect::<Vec<_>>();
+        paths.sort();
+
+        let mut bytes = ARCHIVE_MAGIC.to_vec();
+        bytes.extend((paths.len() as u32).to_le_bytes());
+        for path in paths {
+            let name = path
+                .components()
+                .map(|c| c.as_os_str().to_str())
+                .collect::<Option<Vec<_>>>()
+                .with_context(|| format!("Asset path {:?} is not utf8", path))?
+                .join("/");
+            let data = &self.files[path];
+            bytes.extend((name.len() as u32).to_le_bytes());
+            bytes.extend(name.as_bytes());
+            bytes.extend((data.len() as u64).to_le_bytes());
+            bytes.extend(data);
+        }
+        Ok(bytes)
+    }
+
+    fn parse(bytes: &[u8]) -> Result<Self> {
+        let mut reader = Cursor::new(bytes);
+        let mut magic = [0u8; 8];
+        reader.read_exact(&mut magic)?;
+        anyhow::ensure!(&magic == ARCHIVE_MAGIC, "Not an asset archive");
+
+        fn read_u32(reader: &mut Cursor<&[u8]>) -> Result<u32> {
+            let mut buf = [0u8; 4];
+            reader.read_exact(&mut buf)?;
+            Ok(u32::from_le_bytes(buf))
+        }
+
+        fn read_u64(reader: &mut Cursor<&[u8]>) -> Result<u64> {
+            let mut buf = [0u8; 8];
+            reader.read_exact(&mut buf)?;
+            Ok(u64::from_le_bytes(buf))
+        }
+
+        // checks `len` against the bytes remaining before allocating, so a corrupt length
+        // can't exhaust memory
+        fn read_bytes(reader: &mut Cursor<&[u8]>, len: u64) -> Result<Vec<u8>> {
+            let remaining = reader.get_ref().len() as u64 - reader.position();
+            anyhow::ensure!(
+                len <= remaining,
+                "Expected {} bytes, found {}",
+                len,
+                remaining
+            );
+            let mut buf = vec![0u8; len as usize];
+            reader.read_exact(&mut buf)?;
+            Ok(buf)
+        }
+
+        let mut archive = Archive::default();
+        let count = read_u32(&mut reader)?;
+        for _ in 0..count {
+            let name_len = read_u32(&mut reader)?;
+            let name = read_bytes(&mut reader, name_len as u64).context("Truncated asset path")?;
+            let name = String::from_utf8(name).context("Expected utf8 asset path")?;
+            let data_len = read_u64(&mut reader)?;
+            let data = read_bytes(&mut reader, data_len)
+                .with_context(|| format!("Truncated asset {:?}", name))?;
+            archive.insert(Path::new(&name), data);
+        }
+        Ok(archive)
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod assets_tests {
+    use super::*;
+
+    #[test]
+    fn paths_are_normalized() {
+        assert_eq!(
+            normalize(Path::new("./res/entities.tsx")),
+            Path::new("res/entities.tsx")
+        );
+        assert_eq!(
+            normalize(Path::new(
+                "test_fixtures/maps/../../res/level_1_tileset.tsx"
+            )),
+            Path::new("res/level_1_tileset.tsx")
+        );
+        assert_eq!(
+            normalize(Path::new("../res/a.png")),
+            Path::new("../res/a.png")
+        );
+    }
+
+    #[test]
+    fn archive_round_trips() {
+        let mut archive = Archive::default();
+        archive.insert(Path::new("res/level_1.tmx"), b"map".to_vec());
+        archive.insert(Path::new("res/audio/bump.wav"), vec![0, 1, 2, 255]);
+
+        let archive = Archive::parse(&archive.to_bytes().unwrap()).unwrap();
+        assert_eq!(archive.len(), 2);
+        assert_eq!(
+            archive.get(Path::new("./res/level_1.tmx")),
+            Some(&b"map"[..])
+        );
+        assert_eq!(
+            archive.get(Path::new("res/audio/bump.wav")),
+            Some(&[0, 1, 2, 255][..])
+        );
+        assert!(Archive::parse(b"PLATPAK0").is_err());
+
+        // truncated archives, and lengths longer than the archive, are errors
+        let bytes = archive.to_bytes().unwrap();
+        for len in 0..bytes.len() {
+            assert!(Archive::parse(&bytes[..len]).is_err());
+        }
+        let mut huge_path = bytes[..12].to_vec();
+        huge_path.extend(u32::MAX.to_le_bytes());
+        assert!(Archive::parse(&huge_path).is_err());
+        let mut huge_data = bytes[..12].to_vec();
+        huge_data.extend(1u32.to_le_bytes());
+        huge_data.push(b'a');
+        huge_data.extend(u64::MAX.to_le_bytes());
+        assert!(Archive::parse(&huge_data).is_err());
+    }
+
+    #[test]
+    fn locator_falls_back_to_archive() {
+        let mut archive = Archive::default();
+        archive.insert(Path::new("res/packed_only.txt"), b"packed".to_vec());
+        archive.insert(Path::new("res/campaign.manifest"), b"stale".to_vec());
+        let locator = AssetLocator::new(PathBuf::from("."), Some(archive));
+
+        assert_eq!(
+            locator.read(Path::new("res/packed_only.txt")).unwrap(),
+            b"packed"
+        );
+        // loose files take precedence over the archive
+        assert_ne!(
+            locator.read(Path::new("res/campaign.manifest")).unwrap(),
+            b"stale"
+        );
+        assert!(locator.read(Path::new("res/missing.txt")).is_err());
+    }
+}
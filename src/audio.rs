@@ -1,8 +1,15 @@
-use std::{borrow::BorrowMut, fs::File};
+use std::{
+    borrow::BorrowMut,
+    collections::{HashMap, HashSet},
+    io::Cursor,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Result;
 
 use rodio::{self, Source};
 
-use crate::Options;
+use crate::{assets, campaign, Options};
 
 #[derive(Debug, Clone, Copy)]
 pub enum Channel {
@@ -29,9 +36,9 @@ pub enum Sounds {
 }
 
 impl Sounds {
-    fn file(&self) -> File {
+    fn path(&self) -> &'static Path {
         use Sounds::*;
-        std::fs::File::open(match self {
+        Path::new(match self {
             BossInjured => "res/audio/boss_injury.wav",
             BossDied => "res/audio/boss_death.wav",
             Bump => "res/audio/bump.wav",
@@ -44,11 +51,10 @@ impl Sounds {
             FirebrandDeath => "res/audio/fb_death.wav",
             PowerUp => "res/audio/powerup.wav",
         })
-        .unwrap()
     }
 
-    fn buffer(&self) -> std::io::BufReader<File> {
-        std::io::BufReader::new(self.file())
+    fn decoder(&self) -> Result<rodio::Decoder<Cursor<Vec<u8>>>> {
+        Ok(rodio::Decoder::new(assets::open(self.path())?)?)
     }
 
     fn should_pause_current_track(&self) -> bool {
@@ -72,7 +78,7 @@ impl Sounds {
 
 // ---------------------------------------------------------------------------------------------------------------------
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Tracks {
     MainTheme,
     BossFight,
@@ -81,19 +87,14 @@ pub enum Tracks {
 }
 
 impl Tracks {
-    fn file(&self) -> File {
+    fn default_path(&self) -> PathBuf {
         use Tracks::*;
-        std::fs::File::open(match self {
+        PathBuf::from(match self {
             MainTheme => "res/audio/main_theme.ogg",
             BossFight => "res/audio/boss_fight.ogg",
             AreaClear => "res/audio/area_clear.ogg",
             GameOver => "res/audio/game_over.ogg",
         })
-        .unwrap()
-    }
-
-    fn buffer(&self) -> std::io::BufReader<File> {
-        std::io::BufReader::new(self.file())
     }
 
     fn volume(&self) -> f32 {
@@ -129,24 +130,103 @@ impl SinkHolder {
 
 pub struct Audio {
     no_music: bool,
-    stream: rodio::OutputStream,
-    stream_handle: rodio::OutputStreamHandle,
+    // user volume settings, from 0 to 1, scaling tracks' and sounds' own volumes
+    music_volume: f32,
+    sfx_volume: f32,
+    // None when running silently, e.g., in headless simulation
+    output: Option<(rodio::OutputStream, rodio::OutputStreamHandle)>,
     current_track: Option<rodio::Sink>,
+    // the current track's own volume, before scaling by music_volume
+    current_track_volume: f32,
     current_track_explicitly_paused: bool,
     interrupting_sinks: Vec<SinkHolder>,
+    // per-level replacements for tracks' default files
+    track_files: HashMap<Tracks, PathBuf>,
+    // files which failed to play, so each failure is reported once
+    failed_files: HashSet<PathBuf>,
 }
 
 impl Audio {
+    /// Creates an Audio playing to the default output device; if it can't be opened, the
+    /// game plays silently.
     pub fn new(options: &Options) -> Self {
-        let (stream, stream_handle) =
-            rodio::OutputStream::try_default().expect("Expect to open rodio audio output");
+        let output = rodio::OutputStream::try_default()
+            .map_err(|e| log::error!("Unable to open audio output, playing silently: {}", e))
+            .ok();
         Audio {
             no_music: options.no_music,
-            stream,
-            stream_handle,
+            music_volume: 1.0,
+            sfx_volume: 1.0,
+            output,
+            current_track: None,
+            current_track_volume: 1.0,
+            current_track_explicitly_paused: false,
+            interrupting_sinks: Vec::new(),
+            track_files: HashMap::new(),
+            failed_files: HashSet::new(),
+        }
+    }
+
+    /// Creates an Audio which opens no output device and plays nothing.
+    pub fn silent() -> Self {
+        Audio {
+            no_music: true,
+            music_volume: 1.0,
+            sfx_volume: 1.0,
+            output: None,
             current_track: None,
+            current_track_volume: 1.0,
             current_track_explicitly_paused: false,
             interrupting_sinks: Vec::new(),
+            track_files: HashMap::new(),
+            failed_files: HashSet::new(),
+        }
+    }
+
+    /// Sets the file to play for `track`, e.g., for a level with its own music. Passing
+    /// None restores the track's default file.
+    pub fn set_track_file(&mut self, track: Tracks, file: Option<PathBuf>) {
+        match file {
+            Some(file) => self.track_files.insert(track, file),
+            None => self.track_files.remove(&track),
+        };
+    }
+
+    /// Points the main theme and boss fight tracks at the level's music, if it specifies any.
+    pub fn set_level_tracks(&mut self, level: &campaign::Level) {
+        self.set_track_file(Tracks::MainTheme, level.music.clone());
+        self.set_track_file(Tracks::BossFight, level.boss_music.clone());
+    }
+
+    pub fn music_volume(&self) -> f32 {
+        self.music_volume
+    }
+
+    /// Sets the volume, from 0 to 1, music plays at, including the current track
+    pub fn set_music_volume(&mut self, volume: f32) {
+        self.music_volume = volume.clamp(0.0, 1.0);
+        if let Some(sink) = &self.current_track {
+            sink.set_volume(self.current_track_volume * self.music_volume);
+        }
+    }
+
+    pub fn sfx_volume(&self) -> f32 {
+        self.sfx_volume
+    }
+
+    /// Sets the volume, from 0 to 1, sound effects play at
+    pub fn set_sfx_volume(&mut self, volume: f32) {
+        self.sfx_volume = volume.clamp(0.0, 1.0);
+    }
+
+    fn stream_handle(&self) -> Option<&rodio::OutputStreamHandle> {
+        self.output.as_ref().map(|(_, handle)| handle)
+    }
+
+    /// Logs that `path` couldn't be played; a missing or corrupt file is silent rather than fatal.
+    fn report_failure(&mut self, path: &Path, error: anyhow::Error) {
+        if self.failed_files.insert(path.to_path_buf()) {
+            log::error!("Unable to play {:?}: {:#}", path, error);
         }
     }
 
@@ -172,10 +252,27 @@ impl Audio {
         }
 
         self.stop_current_track();
-        let sink = rodio::Sink::try_new(&self.stream_handle).unwrap();
-        sink.set_volume(track.volume());
+        let Some(stream_handle) = self.stream_handle() else {
+            return;
+        };
+        let path = self
+            .track_files
+            .get(&track)
+            .cloned()
+            .unwrap_or_else(|| track.default_path());
+        let sink_and_source = rodio::Sink::try_new(stream_handle)
+            .map_err(anyhow::Error::from)
+            .and_then(|sink| Ok((sink, rodio::Decoder::new(assets::open(&path)?)?)));
+        let (sink, source) = match sink_and_source {
+            Ok(sink_and_source) => sink_and_source,
+            Err(e) => {
+                self.report_failure(&path, e);
+                return;
+            }
+        };
 
-        let source = rodio::Decoder::new(track.buffer()).unwrap();
+        self.current_track_volume = track.volume();
+        sink.set_volume(track.volume() * self.music_volume);
         if track.loops() {
             sink.append(source.repeat_infinite());
         } else {
@@ -222,8 +319,21 @@ impl Audio {
     }
 
     pub fn play_sound(&mut self, sound: Sounds) {
-        let sink = self.stream_handle.play_once(sound.buffer()).unwrap();
-        sink.set_volume(sound.volume());
+        let Some(stream_handle) = self.stream_handle() else {
+            return;
+        };
+        let sink = match sound.decoder().and_then(|decoder| {
+            let sink = rodio::Sink::try_new(stream_handle)?;
+            sink.append(decoder);
+            Ok(sink)
+        }) {
+            Ok(sink) => sink,
+            Err(e) => {
+                self.report_failure(sound.path(), e);
+                return;
+            }
+        };
+        sink.set_volume(sound.volume() * self.sfx_volume);
         if sound.should_pause_current_track() {
             self.interrupting_sinks.push(SinkHolder::Sink(sink));
         } else {
@@ -232,6 +342,10 @@ impl Audio {
     }
 
     pub fn play_stereo_sound(&mut self, sound: Sounds, channel: Channel) {
+        let Some(stream_handle) = self.stream_handle() else {
+            return;
+        };
+
         let x = match channel {
             Channel::Center => 0.0,
             Channel::Left => -1.0,
@@ -240,16 +354,23 @@ impl Audio {
 
         // note: rodio's spatial sink seems to be inverted from what I'd expect;
         // So, inverting x seems to produce expected results.
-        let sink = rodio::SpatialSink::try_new(
-            &self.stream_handle,
-            [-x, 0.0, 0.0],
-            [-1.0, 0.0, 0.0],
-            [1.0, 0.0, 0.0],
-        )
-        .unwrap();
-
-        sink.append(rodio::Decoder::new(sound.buffer()).unwrap());
-        sink.set_volume(sound.volume());
+        let sink = match sound.decoder().and_then(|decoder| {
+            let sink = rodio::SpatialSink::try_new(
+                stream_handle,
+                [-x, 0.0, 0.0],
+                [-1.0, 0.0, 0.0],
+                [1.0, 0.0, 0.0],
+            )?;
+            sink.append(decoder);
+            Ok(sink)
+        }) {
+            Ok(sink) => sink,
+            Err(e) => {
+                self.report_failure(sound.path(), e);
+                return;
+            }
+        };
+        sink.set_volume(sound.volume() * self.sfx_volume);
 
         if sound.should_pause_current_track() {
             self.interrupting_sinks.push(SinkHolder::SpatialSink(sink));
@@ -5,7 +5,7 @@ use winit::{dpi::PhysicalPosition, keyboard::KeyCode};
 
 use crate::{
     state::constants::{MAX_CAMERA_SCALE, MIN_CAMERA_SCALE},
-    util::{Bounds, UniformWrapper},
+    util::Bounds,
 };
 
 // ---------------------------------------------------------------------------------------------------------------------
@@ -21,7 +21,7 @@ pub const OPENGL_TO_WGPU_MATRIX: Matrix4<f32> = Matrix4::new(
     0.0, 0.0, 0.5, 1.0,
 );
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Camera {
     position: Point3<f32>,
     pub look_dir: Vector3<f32>,
@@ -170,23 +170,16 @@ pub type Uniforms = crate::util::UniformWrapper<UniformData>;
 
 // ---------------------------------------------------------------------------------------------------------------------
 
+/// CameraController moves the camera to track a target within some bounds. It owns no gpu
+/// resources; callers which render write camera state into a `camera::Uniforms` themselves.
 pub struct CameraController {
     pub camera: Camera,
     pub projection: Projection,
-    pub uniforms: UniformWrapper<UniformData>,
 }
 
 impl CameraController {
-    pub fn new(
-        camera: Camera,
-        projection: Projection,
-        uniforms: UniformWrapper<UniformData>,
-    ) -> Self {
-        Self {
-            camera,
-            projection,
-            uniforms,
-        }
+    pub fn new(camera: Camera, projection: Projection) -> Self {
+        Self { camera, projection }
     }
 
     pub fn process_keyboard(&mut self, _key: KeyCode, _state: ElementState) -> bool {
@@ -235,10 +228,6 @@ impl CameraController {
             self.camera.position.x += offset.x;
             self.camera.position.y += offset.y;
         }
-
-        self.uniforms
-            .data
-            .update_view_proj(&self.camera, &self.projection);
     }
 
     /// Return the bounds of the camera viewport expressed as (bottom_left,extent)
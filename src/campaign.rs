@@ -0,0 +1,203 @@
+use std::{
+    io::BufRead,
+    path::{Path, PathBuf},
+};
+
+use anyhow::*;
+
+use crate::assets;
+
+// ---------------------------------------------------------------------------------------------------------------------
+
+/// Describes a single level of a campaign
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Level {
+    pub name: String,
+    /// The level's TMX map
+    pub map: PathBuf,
+    /// The TSX tileset entities (firebrand, enemies, etc) are drawn from
+    pub entity_tileset: PathBuf,
+    /// Music played during the level; if None the default main theme plays
+    pub music: Option<PathBuf>,
+    /// Music played during the boss fight; if None the default boss fight music plays
+    pub boss_music: Option<PathBuf>,
+    /// Hit points of the level's boss; if None the boss uses its default
+    pub boss_hit_points: Option<u32>,
+}
+
+impl Level {
+    fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            map: PathBuf::new(),
+            entity_tileset: PathBuf::new(),
+            music: None,
+            boss_music: None,
+            boss_hit_points: None,
+        }
+    }
+}
+
+/// A Campaign is the ordered sequence of levels which make up the game, loaded from a
+/// manifest file. The manifest is line oriented; each `level <name>` line begins a new
+/// level, and the `<key> <value>` lines following it configure that level:
+///
+/// ```text
+/// # comments and blank lines are ignored
+/// level Ghoul Realm
+/// map level_1.tmx
+/// entity_tileset entities.tsx
+/// music audio/main_theme.ogg
+/// boss_music audio/boss_fight.ogg
+/// boss_hit_points 12
+/// ```
+///
+/// `map` and `entity_tileset` are required. Paths are relative to the manifest file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Campaign {
+    pub levels: Vec<Level>,
+}
+
+impl Campaign {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let file =
+            assets::open(path).with_context(|| format!("Unable to open campaign {:?}", path))?;
+        let base_dir = path.parent().unwrap_or_else(|| Path::new(""));
+        Self::read(file, base_dir).with_context(|| format!("Unable to read campaign {:?}", path))
+    }
+
+    /// Returns the level at `index`, or an error if the campaign has no such level.
+    pub fn level(&self, index: u32) -> Result<&Level> {
+        self.levels.get(index as usize).with_context(|| {
+            format!(
+                "Campaign has {} levels, level index {} is out of range",
+                self.levels.len(),
+                index
+            )
+        })
+    }
+
+    pub fn num_levels(&self) -> u32 {
+        self.levels.len() as u32
+    }
+
+    fn read<R: BufRead>(reader: R, base_dir: &Path) -> Result<Self> {
+        let mut levels: Vec<Level> = Vec::new();
+
+        for (idx, line) in reader.lines().enumerate() {
+            let line = line?;
+            let line_number = idx + 1;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+            let value = value.trim();
+            if value.is_empty() {
+                bail!("Expected a value for \"{}\" on line {}", key, line_number);
+            }
+
+            if key == "level" {
+                levels.push(Level::new(value));
+                continue;
+            }
+
+            let level = levels.last_mut().with_context(|| {
+                format!(
+                    "\"{}\" on line {} must follow a \"level\" entry",
+                    key, line_number
+                )
+            })?;
+
+            match key {
+                "map" => level.map = base_dir.join(value),
+                "entity_tileset" => level.entity_tileset = base_dir.join(value),
+                "music" => level.music = Some(base_dir.join(value)),
+                "boss_music" => level.boss_music = Some(base_dir.join(value)),
+                "boss_hit_points" => {
+                    level.boss_hit_points = Some(value.parse().with_context(|| {
+                        format!(
+                            "Expected boss_hit_points on line {} to be a number",
+                            line_number
+                        )
+                    })?)
+                }
+                _ => bail!(
+                    "Unrecognized campaign entry \"{}\" on line {}",
+                    key,
+                    line_number
+                ),
+            }
+        }
+
+        if levels.is_empty() {
+            bail!("Campaign has no levels");
+        }
+
+        for level in &levels {
+            if level.map.as_os_str().is_empty() {
+                bail!("Level \"{}\" has no map", level.name);
+            }
+            if level.entity_tileset.as_os_str().is_empty() {
+                bail!("Level \"{}\" has no entity_tileset", level.name);
+            }
+        }
+
+        Ok(Self { levels })
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod campaign_tests {
+    use super::*;
+
+    #[test]
+    fn campaign_manifest_parses_levels_in_order() {
+        let data = "# test campaign\n\
+                    level One\n\
+                    map level_1.tmx\n\
+                    entity_tileset entities.tsx\n\
+                    boss_hit_points 12\n\
+                    \n\
+                    level Two\n\
+                    map level_2.tmx\n\
+                    entity_tileset entities.tsx\n\
+                    music audio/level_2.ogg\n";
+        let campaign = Campaign::read(data.as_bytes(), Path::new("res")).unwrap();
+
+        assert_eq!(campaign.num_levels(), 2);
+        assert_eq!(campaign.levels[0].name, "One");
+        assert_eq!(campaign.levels[0].map, Path::new("res/level_1.tmx"));
+        assert_eq!(campaign.levels[0].boss_hit_points, Some(12));
+        assert_eq!(campaign.levels[0].music, None);
+        assert_eq!(campaign.levels[1].name, "Two");
+        assert_eq!(
+            campaign.levels[1].music,
+            Some(PathBuf::from("res/audio/level_2.ogg"))
+        );
+        assert!(campaign.level(2).is_err());
+    }
+
+    #[test]
+    fn campaign_entries_must_follow_a_level() {
+        let data = "map level_1.tmx\n";
+        assert!(Campaign::read(data.as_bytes(), Path::new("res")).is_err());
+    }
+
+    #[test]
+    fn campaign_level_without_map_is_rejected() {
+        let data = "level One\nentity_tileset entities.tsx\n";
+        assert!(Campaign::read(data.as_bytes(), Path::new("res")).is_err());
+    }
+
+    #[test]
+    fn default_campaign_loads() {
+        let campaign = Campaign::load("res/campaign.manifest").unwrap();
+        assert!(campaign.level(0).unwrap().map.exists());
+        assert!(campaign.level(0).unwrap().entity_tileset.exists());
+    }
+}
@@ -1096,7 +1096,7 @@ impl Space {
         max_steps: i32,
         mask: u32,
         test: F,
-    ) -> ProbeResult
+    ) -> ProbeResult<'_>
     where
         F: Fn(f32, &Collider) -> bool,
     {
@@ -1254,6 +1254,7 @@ mod space_tests {
             .is_some());
     }
 
+    #[allow(clippy::type_complexity)]
     fn test_points(
         collider: &Collider,
     ) -> (
@@ -3,9 +3,9 @@ use std::time::Duration;
 
 use crate::{
     audio, collision,
-    entity::{Entity, GameStatePeek},
+    entity::{Entity, GameRng, GameStatePeek},
     event_dispatch::*,
-    map,
+    map, property,
     sprite::{self, rendering},
     state::constants::{layers, sprite_masks, ORIGINAL_VIEWPORT_TILES_WIDE},
     tileset,
@@ -56,16 +56,17 @@ impl Entity for Bat {
         entity_id: u32,
         sprite: &sprite::Sprite,
         _tile: &tileset::Tile,
+        _properties: &property::Properties,
         map: &map::Map,
         collision_space: &mut collision::Space,
-    ) {
+    ) -> anyhow::Result<()> {
         self.entity_id = entity_id;
         self.spawn_point_id = sprite
             .entity_id
             .expect("Spawned entities expect to find a spawn point id from the sprite");
 
         self.position = point3(sprite.origin.x, sprite.origin.y, layers::stage::ENTITIES);
-        self.pixels_per_unit = map.tileset.get_sprite_size().cast().unwrap();
+        self.pixels_per_unit = map.tile_size().cast().unwrap();
 
         // Create collider
         let collider = collision::Collider::new_dynamic(
@@ -75,6 +76,7 @@ impl Entity for Bat {
             sprite_masks::ENTITY | sprite_masks::SHOOTABLE | sprite_masks::CONTACT_DAMAGE,
         );
         self.collider_id = Some(collision_space.add_collider(collider));
+        Ok(())
     }
 
     fn update(
@@ -85,6 +87,7 @@ impl Entity for Bat {
         audio: &mut audio::Audio,
         message_dispatcher: &mut Dispatcher,
         game_state_peek: &GameStatePeek,
+        _rng: &mut GameRng,
     ) {
         //
         // Update life state
@@ -168,7 +171,7 @@ impl Entity for Bat {
 
     fn sprite_cycle(&self) -> &str {
         if self.chase_dir.is_some() {
-            if self.animation_cycle_tick % 2 == 0 {
+            if self.animation_cycle_tick.is_multiple_of(2) {
                 "fly_0"
             } else {
                 "fly_1"
@@ -4,9 +4,9 @@ use cgmath::*;
 
 use crate::{
     audio, collision,
-    entity::{Entity, GameStatePeek},
+    entity::{Entity, GameRng, GameStatePeek},
     event_dispatch::*,
-    map, sprite,
+    map, property, sprite,
     state::{constants::layers, events::Event},
     tileset,
 };
@@ -35,11 +35,13 @@ impl Entity for BossFightTrigger {
         entity_id: u32,
         sprite: &sprite::Sprite,
         _tile: &tileset::Tile,
+        _properties: &property::Properties,
         _map: &map::Map,
         _collision_space: &mut collision::Space,
-    ) {
+    ) -> anyhow::Result<()> {
         self.entity_id = entity_id;
         self.position = point3(sprite.origin.x, sprite.origin.y, layers::stage::ENTITIES);
+        Ok(())
     }
 
     fn update(
@@ -50,6 +52,7 @@ impl Entity for BossFightTrigger {
         _audio: &mut audio::Audio,
         message_dispatcher: &mut Dispatcher,
         game_state_peek: &GameStatePeek,
+        _rng: &mut GameRng,
     ) {
         if game_state_peek.player_position.x > (self.position.x + 1.0)
             && !self.did_send_fight_started_message
@@ -1,12 +1,13 @@
+use anyhow::{Context, Result};
 use cgmath::*;
-use rand::{prelude::*, Rng};
+use rand::Rng;
 use std::time::Duration;
 
 use crate::{
     audio, collision,
-    entity::{Entity, GameStatePeek},
+    entity::{Entity, GameRng, GameStatePeek},
     event_dispatch::*,
-    map,
+    map, property,
     sprite::{self, rendering},
     state::{
         constants::{layers, sprite_masks, ORIGINAL_VIEWPORT_TILES_WIDE},
@@ -42,6 +43,25 @@ enum AttackPhase {
     Submerging,
 }
 
+/// The size of the arena a BossFish fights in, and the height of its water, read from its tile.
+pub struct Arena {
+    extent: Vector2<f32>,
+    water_height: f32,
+}
+
+impl Arena {
+    pub fn from_tile(tile: &tileset::Tile) -> Result<Self> {
+        let float_property = |name: &str| tile.properties().float(name).context("BossFish arena");
+        Ok(Self {
+            extent: vec2(
+                float_property("arena_width")?,
+                float_property("arena_height")?,
+            ),
+            water_height: float_property("water_height")?,
+        })
+    }
+}
+
 // --------------------------------------------------------------------------------------------------------------------
 
 pub struct BossFish {
@@ -53,7 +73,6 @@ pub struct BossFish {
     animation_cycle_tick_countdown: f32,
     animation_cycle_tick: u32,
     time: f32,
-    rng: ThreadRng,
     attack_phase: AttackPhase,
     hit_points: i32,
     sent_defeated_message: bool,
@@ -68,6 +87,8 @@ pub struct BossFish {
     post_shoot_countdown: Option<f32>,
     injury_flash_countdown: Option<f32>,
     sound_to_play: Option<audio::Sounds>,
+    // set when hit in handle_message, to broadcast on the next update
+    did_receive_injury: bool,
 }
 
 impl Default for BossFish {
@@ -81,7 +102,6 @@ impl Default for BossFish {
             animation_cycle_tick_countdown: ANIMATION_CYCLE_DURATION,
             animation_cycle_tick: 0,
             time: 0.0,
-            rng: thread_rng(),
             attack_phase: AttackPhase::Submerged { time_started: 0.0 },
             hit_points: HIT_POINTS,
             sent_defeated_message: false,
@@ -96,6 +116,7 @@ impl Default for BossFish {
             post_shoot_countdown: None,
             injury_flash_countdown: None,
             sound_to_play: None,
+            did_receive_injury: false,
         }
     }
 }
@@ -106,21 +127,20 @@ impl Entity for BossFish {
         entity_id: u32,
         sprite: &sprite::Sprite,
         tile: &tileset::Tile,
+        _properties: &property::Properties,
         _map: &map::Map,
         collision_space: &mut collision::Space,
-    ) {
+    ) -> Result<()> {
         self.entity_id = entity_id;
         self.spawn_point_id = sprite
             .entity_id
-            .expect("Spawned entities expect to find a spawn point id from the sprite");
+            .context("Spawned entities expect to find a spawn point id from the sprite")?;
 
         self.position = point3(sprite.origin.x, sprite.origin.y, layers::stage::ENTITIES);
-        self.arena_extent = vec2(
-            tile.float_property("arena_width"),
-            tile.float_property("arena_height"),
-        );
+        let arena = Arena::from_tile(tile)?;
+        self.arena_extent = arena.extent;
         self.arena_origin = sprite.origin.xy() - self.arena_extent / 2.0;
-        self.water_height = tile.float_property("water_height");
+        self.water_height = arena.water_height;
 
         // Create collider
         let collider = collision::Collider::new_dynamic(
@@ -130,6 +150,7 @@ impl Entity for BossFish {
             sprite_masks::ENTITY | sprite_masks::SHOOTABLE | sprite_masks::CONTACT_DAMAGE,
         );
         self.collider_id = Some(collision_space.add_collider(collider));
+        Ok(())
     }
 
     fn process_keyboard(
@@ -158,6 +179,7 @@ impl Entity for BossFish {
         audio: &mut audio::Audio,
         message_dispatcher: &mut Dispatcher,
         game_state_peek: &GameStatePeek,
+        rng: &mut GameRng,
     ) {
         if !self.active {
             message_dispatcher.entity_to_global(self.entity_id, Event::QueryBossFightMayStart);
@@ -172,7 +194,7 @@ impl Entity for BossFish {
             //  Update position and sprite
             //
 
-            self.update_phase(dt, game_state_peek, message_dispatcher);
+            self.update_phase(dt, game_state_peek, message_dispatcher, rng);
 
             if let Some(id) = self.collider_id {
                 collision_space.update_collider_position(id, self.collider_bounds().origin);
@@ -221,6 +243,11 @@ impl Entity for BossFish {
             audio.play_sound(sound);
             self.sound_to_play = None;
         }
+
+        if self.did_receive_injury {
+            message_dispatcher.broadcast(Event::BossInjured);
+            self.did_receive_injury = false;
+        }
     }
 
     fn update_uniforms(&self, uniforms: &mut rendering::Uniforms) {
@@ -305,7 +332,7 @@ impl Entity for BossFish {
             0
         };
 
-        if self.animation_cycle_tick % 2 == 0 {
+        if self.animation_cycle_tick.is_multiple_of(2) {
             match shoot_cycle {
                 1 => "a_1",
                 2 => "a_2",
@@ -329,12 +356,16 @@ impl Entity for BossFish {
                 self.hit_points = (self.hit_points - (damage as i32)).max(0);
                 self.injury_flash_countdown = Some(INJURY_FLASH_DURATION);
                 self.sound_to_play = Some(audio::Sounds::BossInjured);
+                self.did_receive_injury = true;
             }
-            Event::BossFightMayStart => {
+            Event::BossFightMayStart { hit_points } => {
                 log::info!(
                     "BossFish[{}]::handle_message - BossFightMayStart",
                     self.entity_id()
                 );
+                if let Some(hit_points) = hit_points {
+                    self.hit_points = hit_points as i32;
+                }
                 self.active = true;
             }
             _ => {}
@@ -348,6 +379,7 @@ impl BossFish {
         dt: f32,
         game_state_peek: &GameStatePeek,
         message_dispatcher: &mut Dispatcher,
+        rng: &mut GameRng,
     ) {
         let distance_to_player = (game_state_peek.player_position.x - self.position.x).abs();
 
@@ -359,7 +391,7 @@ impl BossFish {
                     // move from Submerged to Raising. Pick an emergence point that is half the original
                     // viewport width away, with a lowish probability of being right under player.
                     let max_dist = ORIGINAL_VIEWPORT_TILES_WIDE as f32 / 2.0;
-                    let dist = match self.rng.gen_range(0..10) {
+                    let dist = match rng.gen_range(0..10) {
                         0..=3 => max_dist,
                         4..=7 => -max_dist,
                         _ => 0.0,
@@ -383,7 +415,7 @@ impl BossFish {
                 self.position.y += MOVEMENT_SPEED * dt;
                 if self.position.y >= game_state_peek.player_position.y {
                     self.position.y = game_state_peek.player_position.y;
-                    self.should_launch_firesprites = self.rng.gen_bool(0.75);
+                    self.should_launch_firesprites = rng.gen_bool(0.75);
                     self.set_attack_phase(AttackPhase::Attacking {
                         target_x: game_state_peek.player_position.x,
                     });
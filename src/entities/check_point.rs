@@ -4,9 +4,9 @@ use cgmath::*;
 
 use crate::{
     audio, collision,
-    entity::{Entity, GameStatePeek},
+    entity::{Entity, GameRng, GameStatePeek},
     event_dispatch::*,
-    map, sprite,
+    map, property, sprite,
     state::{constants::layers, events::Event},
     tileset,
 };
@@ -33,11 +33,13 @@ impl Entity for CheckPoint {
         entity_id: u32,
         sprite: &sprite::Sprite,
         _tile: &tileset::Tile,
+        _properties: &property::Properties,
         _map: &map::Map,
         _collision_space: &mut collision::Space,
-    ) {
+    ) -> anyhow::Result<()> {
         self.entity_id = entity_id;
         self.position = point3(sprite.origin.x, sprite.origin.y, layers::stage::ENTITIES);
+        Ok(())
     }
 
     fn update(
@@ -48,6 +50,7 @@ impl Entity for CheckPoint {
         _audio: &mut audio::Audio,
         message_dispatcher: &mut Dispatcher,
         game_state_peek: &GameStatePeek,
+        _rng: &mut GameRng,
     ) {
         if !self.did_send_pass_message && game_state_peek.player_position.x > self.position.x {
             message_dispatcher.entity_to_global(self.entity_id(), Event::FirebrandPassedCheckpoint);
@@ -4,7 +4,7 @@ use cgmath::*;
 
 use crate::{
     audio, collision,
-    entity::{Entity, GameStatePeek},
+    entity::{Entity, GameRng, GameStatePeek},
     event_dispatch::*,
     map,
     sprite::rendering,
@@ -98,6 +98,7 @@ impl Entity for DeathAnimation {
         _audio: &mut audio::Audio,
         _message_dispatcher: &mut Dispatcher,
         _game_state_peek: &GameStatePeek,
+        _rng: &mut GameRng,
     ) {
         let dt = dt.as_secs_f32();
         self.time += dt;
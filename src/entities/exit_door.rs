@@ -4,7 +4,7 @@ use cgmath::*;
 
 use crate::{
     audio, collision,
-    entity::{Entity, GameStatePeek},
+    entity::{Entity, GameRng, GameStatePeek},
     event_dispatch::*,
     map,
     sprite::{self, find_bounds, rendering},
@@ -67,6 +67,7 @@ impl Entity for ExitDoor {
         _audio: &mut audio::Audio,
         message_dispatcher: &mut Dispatcher,
         game_state_peek: &GameStatePeek,
+        _rng: &mut GameRng,
     ) {
         match self.mode {
             Mode::Closed => {
@@ -4,9 +4,9 @@ use cgmath::*;
 
 use crate::{
     audio, collision,
-    entity::{Entity, GameStatePeek},
+    entity::{Entity, GameRng, GameStatePeek},
     event_dispatch::*,
-    map,
+    map, property,
     sprite::{self, rendering},
     state::{
         constants::{self, layers, sprite_masks},
@@ -49,12 +49,13 @@ impl Entity for FallingBridge {
         entity_id: u32,
         sprite: &sprite::Sprite,
         _tile: &tileset::Tile,
+        _properties: &property::Properties,
         map: &map::Map,
         collision_space: &mut collision::Space,
-    ) {
+    ) -> anyhow::Result<()> {
         self.entity_id = entity_id;
         self.position = point3(sprite.origin.x, sprite.origin.y, layers::stage::LEVEL);
-        self.pixels_per_unit = map.tileset.get_sprite_size().cast().unwrap();
+        self.pixels_per_unit = map.tile_size().cast().unwrap();
 
         // we need to use a dynamic collider so we can assign an entity id
         self.collider_id = Some(
@@ -65,6 +66,7 @@ impl Entity for FallingBridge {
                 sprite_masks::ENTITY | sprite_masks::GROUND | sprite_masks::RATCHET,
             )),
         );
+        Ok(())
     }
 
     fn update(
@@ -75,6 +77,7 @@ impl Entity for FallingBridge {
         _audio: &mut audio::Audio,
         _message_dispatcher: &mut Dispatcher,
         _game_state_peek: &GameStatePeek,
+        _rng: &mut GameRng,
     ) {
         let dt = dt.as_secs_f32();
         let collider_id = self.collider_id.unwrap();
@@ -134,11 +137,9 @@ impl Entity for FallingBridge {
 
     fn handle_message(&mut self, message: &Message) {
         match message.event {
-            Event::FirebrandContact => {
-                if self.time_remaining.is_none() {
-                    self.offset.y -= 2.0 / self.pixels_per_unit.y;
-                    self.time_remaining = Some(FALLING_BRIDGE_CONTACT_DELAY);
-                }
+            Event::FirebrandContact if self.time_remaining.is_none() => {
+                self.offset.y -= 2.0 / self.pixels_per_unit.y;
+                self.time_remaining = Some(FALLING_BRIDGE_CONTACT_DELAY);
             }
             Event::ResetState => {
                 self.time_remaining = None;
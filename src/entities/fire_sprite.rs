@@ -4,9 +4,9 @@ use std::time::Duration;
 use crate::{
     audio, collision,
     entities::util::{HitPointState, HorizontalDir, MarchState},
-    entity::{Entity, GameStatePeek},
+    entity::{Entity, GameRng, GameStatePeek},
     event_dispatch::*,
-    map,
+    map, property,
     sprite::{self, rendering},
     state::constants::{layers, sprite_masks},
     tileset,
@@ -52,9 +52,10 @@ impl Entity for FireSprite {
         entity_id: u32,
         sprite: &sprite::Sprite,
         _tile: &tileset::Tile,
-        map: &map::Map,
+        properties: &property::Properties,
+        _map: &map::Map,
         collision_space: &mut collision::Space,
-    ) {
+    ) -> anyhow::Result<()> {
         self.entity_id = entity_id;
         self.spawn_point_id = sprite
             .entity_id
@@ -72,17 +73,11 @@ impl Entity for FireSprite {
             )),
         );
 
-        let fixed_position = {
-            if let Some(properties) = map.object_group_properties_for_sprite(sprite, "Metadata") {
-                properties.property("fixed_position") == Some("true")
-            } else {
-                false
-            }
-        };
-
-        if !fixed_position {
+        // properties are forwarded from the spawn point which spawned this FireSprite
+        if !properties.flag("fixed_position") {
             self.march = Some(MarchState::new(HorizontalDir::East, MOVEMENT_SPEED));
         }
+        Ok(())
     }
 
     fn update(
@@ -93,6 +88,7 @@ impl Entity for FireSprite {
         audio: &mut audio::Audio,
         message_dispatcher: &mut Dispatcher,
         game_state_peek: &GameStatePeek,
+        _rng: &mut GameRng,
     ) {
         let entity_id = self.entity_id;
         let spawn_point_id = self.spawn_point_id;
@@ -139,7 +135,7 @@ impl Entity for FireSprite {
     }
 
     fn update_uniforms(&self, uniforms: &mut rendering::Uniforms) {
-        let (xscale, xoffset) = if self.animation_cycle_tick / 2 % 2 == 0 {
+        let (xscale, xoffset) = if (self.animation_cycle_tick / 2).is_multiple_of(2) {
             (1.0, 0.0)
         } else {
             (-1.0, 1.0)
@@ -178,7 +174,7 @@ impl Entity for FireSprite {
     }
 
     fn sprite_cycle(&self) -> &str {
-        if self.animation_cycle_tick % 2 == 0 {
+        if self.animation_cycle_tick.is_multiple_of(2) {
             "default"
         } else {
             "alt"
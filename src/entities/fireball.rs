@@ -4,7 +4,7 @@ use cgmath::*;
 
 use crate::{
     audio, collision,
-    entity::{Entity, GameStatePeek},
+    entity::{Entity, GameRng, GameStatePeek},
     event_dispatch::*,
     map,
     sprite::rendering,
@@ -109,6 +109,7 @@ impl Entity for Fireball {
         audio: &mut audio::Audio,
         message_dispatcher: &mut Dispatcher,
         game_state_peek: &GameStatePeek,
+        _rng: &mut GameRng,
     ) {
         if self.should_play_launch_sound {
             audio.play_sound(audio::Sounds::FireballShoot);
@@ -210,7 +211,7 @@ impl Entity for Fireball {
         match self.mode {
             Mode::Fireball => "default",
             Mode::Firesprite => {
-                if self.animation_cycle_tick % 2 == 0 {
+                if self.animation_cycle_tick.is_multiple_of(2) {
                     "default"
                 } else {
                     "alt"
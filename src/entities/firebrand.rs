@@ -10,7 +10,7 @@ use winit::{event::ElementState, keyboard::KeyCode};
 
 use crate::{
     audio, collision,
-    entity::{Entity, GameStatePeek},
+    entity::{Entity, GameRng, GameStatePeek},
     event_dispatch::*,
     input::*,
     map,
@@ -131,12 +131,40 @@ impl Display for Stance {
 
 // ---------------------------------------------------------------------------------------------------------------------
 
-#[derive(Debug, Copy, Clone, Hash)]
-enum Action {
-    MoveLeft,
-    MoveRight,
-    Jump,
-    Shoot,
+/// Snapshot of the button states FirebrandInputState provides for a single frame.
+/// Recorded to, and played back from, replay files.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct FirebrandInput {
+    pub move_left: ButtonState,
+    pub move_right: ButtonState,
+    pub jump: ButtonState,
+    pub fire: ButtonState,
+}
+
+impl FirebrandInput {
+    /// Creates a FirebrandInput where each button is either held down, or up.
+    pub fn held(left: bool, right: bool, jump: bool, fire: bool) -> Self {
+        let state = |down: bool| {
+            if down {
+                ButtonState::Down
+            } else {
+                ButtonState::Up
+            }
+        };
+        Self {
+            move_left: state(left),
+            move_right: state(right),
+            jump: state(jump),
+            fire: state(fire),
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.move_left.is_active()
+            || self.move_right.is_active()
+            || self.jump.is_active()
+            || self.fire.is_active()
+    }
 }
 
 struct FirebrandInputState {
@@ -146,112 +174,60 @@ struct FirebrandInputState {
 impl Default for FirebrandInputState {
     fn default() -> Self {
         Self {
-            input_state: InputState::for_keys(&[
-                winit::keyboard::KeyCode::KeyW,
-                winit::keyboard::KeyCode::KeyA,
-                winit::keyboard::KeyCode::KeyD,
-                winit::keyboard::KeyCode::Space,
+            input_state: InputState::for_actions(&[
+                Action::MoveLeft,
+                Action::MoveRight,
+                Action::Jump,
+                Action::Shoot,
             ]),
         }
     }
 }
 
 impl FirebrandInputState {
-    fn process_keyboard(&mut self, key: KeyCode, state: ElementState) -> bool {
-        self.input_state.process_keyboard(key, state)
-    }
-
-    /// Maps gamepad input into keyboard input. Total hack, but, it works.
-    pub fn gamepad_input(&mut self, event: gilrs::Event) {
-        let input = match event.event {
-            gilrs::EventType::ButtonPressed(button, ..) => match button {
-                gilrs::Button::South | gilrs::Button::North => {
-                    Some((KeyCode::Space, ElementState::Pressed))
-                }
-                gilrs::Button::East | gilrs::Button::West => {
-                    Some((KeyCode::KeyW, ElementState::Pressed))
-                }
-                gilrs::Button::DPadUp => Some((KeyCode::KeyW, ElementState::Pressed)),
-                gilrs::Button::DPadLeft => Some((KeyCode::KeyA, ElementState::Pressed)),
-                gilrs::Button::DPadRight => Some((KeyCode::KeyD, ElementState::Pressed)),
-                _ => None,
-            },
-            gilrs::EventType::ButtonReleased(button, ..) => match button {
-                gilrs::Button::South | gilrs::Button::North => {
-                    Some((KeyCode::Space, ElementState::Released))
-                }
-                gilrs::Button::East | gilrs::Button::West => {
-                    Some((KeyCode::KeyW, ElementState::Released))
-                }
-                gilrs::Button::DPadUp => Some((KeyCode::KeyW, ElementState::Released)),
-                gilrs::Button::DPadLeft => Some((KeyCode::KeyA, ElementState::Released)),
-                gilrs::Button::DPadRight => Some((KeyCode::KeyD, ElementState::Released)),
-                _ => None,
-            },
-            _ => None,
-        };
-
-        if let Some((key, state)) = input {
-            self.process_keyboard(key, state);
-        }
+    fn process_action(&mut self, action: Action, state: ElementState) -> bool {
+        self.input_state.process_action(action, state)
     }
 
     fn update(&mut self) {
         self.input_state.update();
     }
 
-    fn override_user_input(&mut self, left: bool, right: bool, jump: bool, fire: bool) -> bool {
+    fn override_user_input(&mut self, input: FirebrandInput) -> bool {
         let mut state = HashMap::new();
-        state.insert(
-            KeyCode::KeyW,
-            if jump {
-                ButtonState::Down
-            } else {
-                ButtonState::Up
-            },
-        );
-        state.insert(
-            KeyCode::KeyA,
-            if left {
-                ButtonState::Down
-            } else {
-                ButtonState::Up
-            },
-        );
-        state.insert(
-            KeyCode::KeyD,
-            if right {
-                ButtonState::Down
-            } else {
-                ButtonState::Up
-            },
-        );
-        state.insert(
-            KeyCode::Space,
-            if fire {
-                ButtonState::Down
-            } else {
-                ButtonState::Up
-            },
-        );
+        state.insert(Action::Jump, input.jump);
+        state.insert(Action::MoveLeft, input.move_left);
+        state.insert(Action::MoveRight, input.move_right);
+        state.insert(Action::Shoot, input.fire);
         self.input_state.set(state);
-        left || right || jump || fire
+        input.is_active()
+    }
+
+    fn current(&self) -> FirebrandInput {
+        FirebrandInput {
+            move_left: *self.move_left(),
+            move_right: *self.move_right(),
+            jump: *self.jump(),
+            fire: *self.fire(),
+        }
     }
 
     fn jump(&self) -> &ButtonState {
-        self.input_state.get_button_state(KeyCode::KeyW).unwrap()
+        self.input_state.get_button_state(Action::Jump).unwrap()
     }
 
     fn move_left(&self) -> &ButtonState {
-        self.input_state.get_button_state(KeyCode::KeyA).unwrap()
+        self.input_state.get_button_state(Action::MoveLeft).unwrap()
     }
 
     fn move_right(&self) -> &ButtonState {
-        self.input_state.get_button_state(KeyCode::KeyD).unwrap()
+        self.input_state
+            .get_button_state(Action::MoveRight)
+            .unwrap()
     }
 
     fn fire(&self) -> &ButtonState {
-        self.input_state.get_button_state(KeyCode::Space).unwrap()
+        self.input_state.get_button_state(Action::Shoot).unwrap()
     }
 }
 
@@ -298,7 +274,7 @@ pub struct CharacterState {
 }
 
 impl CharacterState {
-    fn new(position: Point2<f32>, num_lives_remaining: u32) -> Self {
+    fn new(position: Point2<f32>, num_lives_remaining: u32, num_vials: u32) -> Self {
         CharacterState {
             position,
             position_offset: Zero::zero(),
@@ -309,7 +285,7 @@ impl CharacterState {
             hit_points_max: HIT_POINTS,
             flight_time_remaining: FLIGHT_DURATION,
             flight_time_max: FLIGHT_DURATION,
-            num_vials: 0,
+            num_vials,
             num_lives: num_lives_remaining,
             alive: true,
         }
@@ -352,10 +328,12 @@ pub struct Firebrand {
     did_pass_through_exit_door: bool,
     walk_on_distance_remaining: Option<f32>,
     sound_to_play: Option<audio::Sounds>,
+    // set by receive_injury, which may run in handle_message, to broadcast on the next update
+    did_receive_injury: bool,
 }
 
 impl Firebrand {
-    pub fn new(position: Point2<f32>, num_lives_remaining: u32) -> Firebrand {
+    pub fn new(position: Point2<f32>, num_lives_remaining: u32, num_vials: u32) -> Firebrand {
         Self {
             entity_id: 0,
             collider_id: None,
@@ -363,7 +341,7 @@ impl Firebrand {
             time: 0.0,
             step: 0,
             input_state: FirebrandInputState::default(),
-            character_state: CharacterState::new(position.xy(), num_lives_remaining),
+            character_state: CharacterState::new(position.xy(), num_lives_remaining, num_vials),
             overlapping_colliders: HashSet::new(),
             overlapping_sprites: HashSet::new(),
             contacting_colliders: HashSet::new(),
@@ -384,6 +362,7 @@ impl Firebrand {
             did_pass_through_exit_door: false,
             walk_on_distance_remaining: None,
             sound_to_play: None,
+            did_receive_injury: false,
         }
     }
 }
@@ -391,7 +370,7 @@ impl Firebrand {
 impl Entity for Firebrand {
     fn init(&mut self, entity_id: u32, map: &map::Map, collision_space: &mut collision::Space) {
         self.entity_id = entity_id;
-        self.pixels_per_unit = map.tileset.get_sprite_size().cast().unwrap();
+        self.pixels_per_unit = map.tile_size().cast().unwrap();
 
         self.collider_id = Some(
             collision_space.add_collider(collision::Collider::new_dynamic(
@@ -404,29 +383,33 @@ impl Entity for Firebrand {
     }
 
     fn process_keyboard(&mut self, key: KeyCode, state: ElementState) -> bool {
+        match (key, state) {
+            (KeyCode::Delete, ElementState::Pressed) if !self.did_pass_through_exit_door => {
+                self.receive_injury(self.character_state.hit_points);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn process_action(&mut self, action: Action, state: ElementState) -> bool {
         if self.did_pass_through_exit_door {
             // after walking through the exit door, Firebrand keeps walking to right... forever
             self.input_state
-                .override_user_input(false, true, false, false);
-            // don't consume input, since we want to allow Esc, etc to quite game.
+                .override_user_input(FirebrandInput::held(false, true, false, false));
+            // don't consume input, since we want to allow Pause, etc to reach the game ui.
             false
-        } else if self.input_state.process_keyboard(key, state) {
-            true
         } else {
-            match (key, state) {
-                (KeyCode::Delete, ElementState::Pressed) => {
-                    self.receive_injury(self.character_state.hit_points);
-                    true
-                }
-                _ => false,
-            }
+            self.input_state.process_action(action, state)
         }
     }
 
-    fn process_gamepad(&mut self, event: gilrs::Event) {
-        if !self.did_pass_through_exit_door {
-            self.input_state.gamepad_input(event);
-        }
+    fn user_input(&self) -> Option<FirebrandInput> {
+        Some(self.input_state.current())
+    }
+
+    fn override_user_input(&mut self, input: FirebrandInput) {
+        self.input_state.override_user_input(input);
     }
 
     fn update(
@@ -437,6 +420,7 @@ impl Entity for Firebrand {
         audio: &mut audio::Audio,
         message_dispatcher: &mut Dispatcher,
         game_state_peek: &GameStatePeek,
+        _rng: &mut GameRng,
     ) {
         if dt == Duration::from_secs(0) {
             return;
@@ -791,6 +775,11 @@ impl Entity for Firebrand {
             self.sound_to_play = None;
         }
 
+        if self.did_receive_injury {
+            message_dispatcher.broadcast(Event::FirebrandInjured);
+            self.did_receive_injury = false;
+        }
+
         //
         // Update overlapping/contacting sprites for debug rendering
         //
@@ -1096,17 +1085,17 @@ impl Firebrand {
             if let Some(c) = collision_space.get_collider_at(*test_point, GROUND) {
                 if can_collide_width(&position, c) {
                     match c.shape {
-                        collision::Shape::Square => {
+                        collision::Shape::Square
                             if c.intersects_unit_rect(
                                 &position,
                                 inset_for_collider(c),
                                 contacts_are_collision,
-                            ) {
-                                self.process_potential_collision_with(c);
-                                tracking = Some(c);
-                                if may_apply_correction {
-                                    position.y = c.top();
-                                }
+                            ) =>
+                        {
+                            self.process_potential_collision_with(c);
+                            tracking = Some(c);
+                            if may_apply_correction {
+                                position.y = c.top();
                             }
                         }
                         collision::Shape::NorthEast | collision::Shape::NorthWest => {
@@ -1549,6 +1538,7 @@ impl Firebrand {
             } else {
                 self.set_stance(Stance::Injury);
                 self.sound_to_play = Some(audio::Sounds::FirebrandInjury);
+                self.did_receive_injury = true;
             }
         }
     }
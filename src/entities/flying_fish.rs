@@ -1,13 +1,13 @@
 use cgmath::*;
-use rand::{prelude::*, Rng};
+use rand::Rng;
 use std::{f32::consts::PI, time::Duration};
 
 use crate::{
     audio, collision,
     entities::util::HitPointState,
-    entity::{Entity, GameStatePeek},
+    entity::{Entity, GameRng, GameStatePeek},
     event_dispatch::*,
-    map,
+    map, property,
     sprite::{self, rendering},
     state::constants::{layers, sprite_masks},
     tileset,
@@ -34,7 +34,6 @@ pub struct FlyingFish {
     pixels_per_unit: Vector2<f32>,
     jump_phase: i32,
     jump_height: f32,
-    rng: ThreadRng,
     life: HitPointState,
 }
 
@@ -51,7 +50,6 @@ impl Default for FlyingFish {
             pixels_per_unit: vec2(0.0, 0.0),
             jump_phase: 0,
             jump_height: PARABOLA_HALF_HEIGHT_SHORT,
-            rng: thread_rng(),
             life: HitPointState::new(HIT_POINTS),
         }
     }
@@ -63,9 +61,10 @@ impl Entity for FlyingFish {
         entity_id: u32,
         sprite: &sprite::Sprite,
         _tile: &tileset::Tile,
+        _properties: &property::Properties,
         map: &map::Map,
         collision_space: &mut collision::Space,
-    ) {
+    ) -> anyhow::Result<()> {
         self.entity_id = entity_id;
         self.spawn_point_id = sprite
             .entity_id
@@ -73,7 +72,7 @@ impl Entity for FlyingFish {
 
         self.position = point3(sprite.origin.x, sprite.origin.y, layers::stage::ENTITIES);
         self.centroid = sprite.origin.xy();
-        self.pixels_per_unit = map.tileset.get_sprite_size().cast().unwrap();
+        self.pixels_per_unit = map.tile_size().cast().unwrap();
 
         // offset phase such that neighbor fish don't jump in same dir
         self.phase = self.position.x as i32 % 2;
@@ -87,6 +86,7 @@ impl Entity for FlyingFish {
                 sprite_masks::ENTITY | sprite_masks::SHOOTABLE | sprite_masks::CONTACT_DAMAGE,
             )),
         );
+        Ok(())
     }
 
     fn update(
@@ -97,6 +97,7 @@ impl Entity for FlyingFish {
         audio: &mut audio::Audio,
         message_dispatcher: &mut Dispatcher,
         game_state_peek: &GameStatePeek,
+        rng: &mut GameRng,
     ) {
         let dt = dt.as_secs_f32();
 
@@ -134,7 +135,7 @@ impl Entity for FlyingFish {
             self.time_in_phase -= PARABOLA_MOTION_DURATION;
             self.phase += 1;
 
-            if self.rng.gen::<f32>() < 0.25 {
+            if rng.gen::<f32>() < 0.25 {
                 self.jump_phase += 1;
                 self.jump_height = match self.jump_phase % 2 {
                     0 => PARABOLA_HALF_HEIGHT_SHORT,
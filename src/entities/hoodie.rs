@@ -3,9 +3,9 @@ use std::time::Duration;
 
 use crate::{
     audio, collision,
-    entity::{Entity, GameStatePeek},
+    entity::{Entity, GameRng, GameStatePeek},
     event_dispatch::*,
-    map,
+    map, property,
     sprite::{self, rendering},
     state::constants::{layers, sprite_masks},
     tileset,
@@ -56,16 +56,17 @@ impl Entity for Hoodie {
         entity_id: u32,
         sprite: &sprite::Sprite,
         _tile: &tileset::Tile,
+        _properties: &property::Properties,
         map: &map::Map,
         collision_space: &mut collision::Space,
-    ) {
+    ) -> anyhow::Result<()> {
         self.entity_id = entity_id;
         self.spawn_point_id = sprite
             .entity_id
             .expect("Spawned entities expect to find a spawn point id from the sprite");
 
         self.position = point3(sprite.origin.x, sprite.origin.y, layers::stage::ENTITIES);
-        self.pixels_per_unit = map.tileset.get_sprite_size().cast().unwrap();
+        self.pixels_per_unit = map.tile_size().cast().unwrap();
 
         // Make collider
         self.collider_id = Some(
@@ -76,6 +77,7 @@ impl Entity for Hoodie {
                 sprite_masks::ENTITY | sprite_masks::SHOOTABLE | sprite_masks::CONTACT_DAMAGE,
             )),
         );
+        Ok(())
     }
 
     fn update(
@@ -86,6 +88,7 @@ impl Entity for Hoodie {
         audio: &mut audio::Audio,
         message_dispatcher: &mut Dispatcher,
         game_state_peek: &GameStatePeek,
+        _rng: &mut GameRng,
     ) {
         //
         // Update life state
@@ -23,6 +23,7 @@ use anyhow::Result;
 use crate::collision;
 use crate::entity;
 use crate::map;
+use crate::property;
 use crate::sprite;
 use crate::tileset;
 
@@ -123,22 +124,57 @@ pub fn instantiate_entity_by_class_name(classname: &str) -> Option<Box<dyn entit
     }
 }
 
+/// Instantiates the entity which owns the sprites of a map layer with the "entity" role,
+/// as named by the layer's "entity" property.
+pub fn instantiate_layer_entity(
+    layer: &map::Layer,
+    sprites: Vec<sprite::Sprite>,
+) -> Result<Box<dyn entity::Entity>> {
+    match layer.entity_class_name()? {
+        "RisingFloor" => Ok(Box::new(rising_floor::RisingFloor::new(sprites))),
+        "ExitDoor" => {
+            let open_dir = match layer.property("open_dir") {
+                Some("east") => util::HorizontalDir::East,
+                Some("west") => util::HorizontalDir::West,
+                _ => anyhow::bail!(
+                    "ExitDoor layer \"{}\" expects an \"open_dir\" property of \"east\" or \"west\"",
+                    layer.name
+                ),
+            };
+            Ok(Box::new(exit_door::ExitDoor::new(sprites, open_dir)))
+        }
+        classname => anyhow::bail!(
+            "Unable to instantiate entity class \"{}\" for layer \"{}\"",
+            classname,
+            layer.name
+        ),
+    }
+}
+
 pub fn instantiate_map_sprite(
     classname: &str,
     sprite: &sprite::Sprite,
     tile: &tileset::Tile,
+    properties: &property::Properties,
     map: &map::Map,
     collision_space: &mut collision::Space,
     entity_id_vendor: Option<&mut entity::IdVendor>,
 ) -> Result<Box<dyn entity::Entity>> {
     if let Some(mut e) = instantiate_entity_by_class_name(classname) {
         if let Some(id_vendor) = entity_id_vendor {
-            e.init_from_map_sprite(id_vendor.next_id(), sprite, tile, map, collision_space);
+            e.init_from_map_sprite(
+                id_vendor.next_id(),
+                sprite,
+                tile,
+                properties,
+                map,
+                collision_space,
+            )?;
         } else {
             let id = sprite
                 .entity_id
                 .expect("Expect entity_id on Sprite when loading from map");
-            e.init_from_map_sprite(id, sprite, tile, map, collision_space);
+            e.init_from_map_sprite(id, sprite, tile, properties, map, collision_space)?;
         }
         Ok(e)
     } else {
@@ -1,12 +1,13 @@
 use std::time::Duration;
 
+use anyhow::{bail, Result};
 use cgmath::*;
 
 use crate::{
     audio, collision,
-    entity::{Entity, GameStatePeek},
+    entity::{Entity, GameRng, GameStatePeek},
     event_dispatch::*,
-    map,
+    map, property,
     sprite::{self, rendering},
     state::{
         constants::{layers, sprite_masks},
@@ -24,7 +25,7 @@ pub enum Type {
 }
 
 impl Type {
-    fn from_str(t: &str) -> Option<Type> {
+    pub fn from_str(t: &str) -> Option<Type> {
         match t {
             "vial" => Some(Type::Vial),
             "heart" => Some(Type::Heart),
@@ -32,6 +33,20 @@ impl Type {
         }
     }
 
+    /// Reads the type of PowerUp a tile places from its "powerup_type" property.
+    pub fn from_tile(tile: &tileset::Tile) -> Result<Type> {
+        match tile.get_property("powerup_type") {
+            None => bail!("PowerUp has no \"powerup_type\" property"),
+            Some(type_name) => match Type::from_str(type_name) {
+                Some(t) => Ok(t),
+                None => bail!(
+                    "Unknown powerup_type \"{}\"; expected \"vial\" or \"heart\"",
+                    type_name
+                ),
+            },
+        }
+    }
+
     fn sprite_name(&self) -> &'static str {
         match self {
             Type::Vial => "vial",
@@ -74,9 +89,11 @@ impl Entity for PowerUp {
         entity_id: u32,
         sprite: &sprite::Sprite,
         tile: &tileset::Tile,
+        _properties: &property::Properties,
         _map: &map::Map,
         collision_space: &mut collision::Space,
-    ) {
+    ) -> Result<()> {
+        self.powerup_type = Some(Type::from_tile(tile)?);
         self.is_collider_active = true;
         self.entity_id = entity_id;
         self.position = point3(sprite.origin.x, sprite.origin.y, layers::stage::ENTITIES);
@@ -91,12 +108,7 @@ impl Entity for PowerUp {
                 sprite_masks::ENTITY,
             )),
         );
-
-        let type_name = tile
-            .get_property("powerup_type")
-            .expect("PowerUp tile must specify 'powerup_type'");
-
-        self.powerup_type = Some(Type::from_str(type_name).expect("Expect supported powerup type"));
+        Ok(())
     }
 
     fn update(
@@ -107,6 +119,7 @@ impl Entity for PowerUp {
         audio: &mut audio::Audio,
         message_dispatcher: &mut Dispatcher,
         _game_state_peek: &GameStatePeek,
+        _rng: &mut GameRng,
     ) {
         let dt = dt.as_secs_f32();
         self.time += dt;
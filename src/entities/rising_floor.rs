@@ -5,7 +5,7 @@ use sprite::find_bounds;
 
 use crate::{
     audio, collision,
-    entity::{Entity, GameStatePeek},
+    entity::{Entity, GameRng, GameStatePeek},
     event_dispatch::*,
     map,
     sprite::{self, rendering},
@@ -59,7 +59,7 @@ impl Entity for RisingFloor {
             )),
         );
 
-        self.pixels_per_unit = map.tileset.get_sprite_size().x as f32;
+        self.pixels_per_unit = map.tile_size().x as f32;
     }
 
     fn update(
@@ -70,6 +70,7 @@ impl Entity for RisingFloor {
         _audio: &mut audio::Audio,
         message_dispatcher: &mut Dispatcher,
         _game_state_peek: &GameStatePeek,
+        _rng: &mut GameRng,
     ) {
         if self.rising {
             if !self.sent_started_rising_message {
@@ -159,7 +160,7 @@ impl RisingFloor {
             (vec2(4.0 * p, 0.0), d),
             (vec2(2.0 * p, 0.0), d),
             (vec2(0.0, 0.0), d),
-            (vec2(-1.0 * p, 0.0), d),
+            (vec2(-p, 0.0), d),
             (vec2(0.0, 0.0), d),
             (vec2(0.0, 0.0), d),
         ]
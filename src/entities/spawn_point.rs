@@ -1,21 +1,48 @@
 use std::time::Duration;
 
+use anyhow::{bail, Result};
 use cgmath::*;
 
 use crate::{
     audio, collision,
-    entity::{Entity, GameStatePeek},
+    entity::{Entity, GameRng, GameStatePeek},
     event_dispatch::*,
-    map, sprite,
+    map, property, sprite,
     state::{constants::layers, events::Event},
     tileset,
 };
 
+use super::boss_fish;
+
+/// Returns the class of entity a SpawnPoint spawns, which a map object's properties may override
+/// for its tile, or an error if there is none or the spawned entity couldn't be initialized.
+pub fn spawned_entity_class<'a>(
+    tile: &'a tileset::Tile,
+    properties: &'a property::Properties,
+) -> Result<&'a str> {
+    let class_name = match properties
+        .string("spawned_entity_class")
+        .or_else(|| tile.get_property("spawned_entity_class"))
+    {
+        Some(class_name) => class_name,
+        None => bail!("SpawnPoint has no \"spawned_entity_class\" property"),
+    };
+    if super::instantiate_entity_by_class_name(class_name).is_none() {
+        bail!("SpawnPoint spawns unknown entity class \"{}\"", class_name);
+    }
+    if class_name == "BossFish" {
+        boss_fish::Arena::from_tile(tile)?;
+    }
+    Ok(class_name)
+}
+
 pub struct SpawnPoint {
     entity_id: u32,
     position: Point3<f32>,
     sprite: Option<sprite::Sprite>,
     tile: Option<tileset::Tile>,
+    properties: property::Properties,
+    spawned_entity_class: String,
     spawned_entity_id: Option<u32>,
     did_become_visible: bool,
 }
@@ -27,6 +54,8 @@ impl Default for SpawnPoint {
             position: point3(0.0, 0.0, 0.0),
             sprite: None,
             tile: None,
+            properties: property::Properties::default(),
+            spawned_entity_class: String::new(),
             spawned_entity_id: None,
             did_become_visible: false,
         }
@@ -39,13 +68,17 @@ impl Entity for SpawnPoint {
         entity_id: u32,
         sprite: &sprite::Sprite,
         tile: &tileset::Tile,
+        properties: &property::Properties,
         _map: &map::Map,
         _collision_space: &mut collision::Space,
-    ) {
+    ) -> Result<()> {
         self.entity_id = entity_id;
         self.position = point3(sprite.origin.x, sprite.origin.y, layers::stage::ENTITIES);
         self.sprite = Some(*sprite);
         self.tile = Some(tile.clone());
+        self.properties = properties.clone();
+        self.spawned_entity_class = spawned_entity_class(tile, properties)?.to_string();
+        Ok(())
     }
 
     fn update(
@@ -56,6 +89,7 @@ impl Entity for SpawnPoint {
         _audio: &mut audio::Audio,
         message_dispatcher: &mut Dispatcher,
         _game_state_peek: &GameStatePeek,
+        _rng: &mut GameRng,
     ) {
         if self.did_become_visible && self.spawned_entity_id.is_none() {
             let sprite = self
@@ -66,17 +100,13 @@ impl Entity for SpawnPoint {
                 .as_ref()
                 .expect("SpawnPoint must be initialized from a map sprite")
                 .clone();
-            let class_name = tile
-                .get_property("spawned_entity_class")
-                .expect("Spawn point must have \"spawned_entity_class\" property on tile")
-                .to_string();
-
             message_dispatcher.entity_to_global(
                 self.entity_id(),
                 Event::SpawnEntity {
-                    class_name,
+                    class_name: self.spawned_entity_class.clone(),
                     spawn_point_sprite: sprite,
                     spawn_point_tile: tile,
+                    spawn_point_properties: self.properties.clone(),
                 },
             );
         }
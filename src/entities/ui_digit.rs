@@ -1,12 +1,12 @@
+use anyhow::{bail, ensure, Result};
 use cgmath::*;
-use core::panic;
 use std::time::Duration;
 
 use crate::{
     audio, collision,
-    entity::{Entity, GameStatePeek},
+    entity::{Entity, GameRng, GameStatePeek},
     event_dispatch::*,
-    map,
+    map, property,
     sprite::{self, rendering},
     state::constants::layers,
     tileset,
@@ -18,6 +18,47 @@ use crate::{
 enum Tracking {
     Vials,
     Lives,
+    // volumes are shown from 0 to 10
+    MusicVolume,
+    SfxVolume,
+    ContinueCountdown,
+    Continues,
+}
+
+/// Reads which value a UiDigit tracks, and which decimal digit of it the UiDigit displays.
+fn parse_properties(properties: &property::Properties) -> Result<(Tracking, u32)> {
+    let tracking = match properties.string("tracking") {
+        Some("vials") => Tracking::Vials,
+        Some("lives") => Tracking::Lives,
+        Some("music_volume") => Tracking::MusicVolume,
+        Some("sfx_volume") => Tracking::SfxVolume,
+        Some("continue_countdown") => Tracking::ContinueCountdown,
+        Some("continues") => Tracking::Continues,
+        Some(tracking) => bail!(
+            "Unknown UiDigit tracking \"{}\"; expected \"vials\", \"lives\", \"music_volume\", \"sfx_volume\", \"continue_countdown\" or \"continues\"",
+            tracking
+        ),
+        None => bail!("UiDigit has no \"tracking\" property"),
+    };
+
+    let mut digit = 0;
+    if properties.contains("digit") {
+        let value = properties.int("digit")?;
+        // i32 values have at most 10 decimal digits
+        ensure!(
+            (0..10).contains(&value),
+            "UiDigit digit must be from 0 to 9, found {}",
+            value
+        );
+        digit = value as u32;
+    }
+
+    Ok((tracking, digit))
+}
+
+/// Returns an error describing why `properties` don't configure a UiDigit, if they don't.
+pub fn validate_properties(properties: &property::Properties) -> Result<()> {
+    parse_properties(properties).map(|_| ())
 }
 
 // ---------------------------------------------------------------------------------------------------------------------
@@ -48,35 +89,17 @@ impl Entity for UiDigit {
         entity_id: u32,
         sprite: &sprite::Sprite,
         _tile: &tileset::Tile,
-        map: &map::Map,
+        properties: &property::Properties,
+        _map: &map::Map,
         _collision_space: &mut collision::Space,
-    ) {
+    ) -> Result<()> {
         self.entity_id = entity_id;
         self.position = point3(sprite.origin.x, sprite.origin.y, layers::ui::FOREGROUND);
 
-        if let Some(obj) = map.object_group_properties_for_sprite(sprite, "Metadata") {
-            for property in obj.properties.iter() {
-                match property.name.as_str() {
-                    "tracking" => match property.value.as_str() {
-                        "vials" => self.tracking = Some(Tracking::Vials),
-                        "lives" => self.tracking = Some(Tracking::Lives),
-                        _ => panic!("Only 'vials' and 'lives' supported for UiDigit"),
-                    },
-                    "digit" => {
-                        self.digit = property
-                            .value
-                            .parse()
-                            .expect("Expected to parse 'digit' property to i32")
-                    }
-                    _ => {}
-                }
-            }
-
-            self.tracking
-                .expect("Expect UiDigit to have loaded 'tracking' target from metadata");
-        } else {
-            log::error!("Could not find metadata for sprite");
-        }
+        let (tracking, digit) = parse_properties(properties)?;
+        self.tracking = Some(tracking);
+        self.digit = digit;
+        Ok(())
     }
 
     fn update(
@@ -84,15 +107,20 @@ impl Entity for UiDigit {
         _dt: Duration,
         _map: &map::Map,
         _collision_space: &mut collision::Space,
-        _audio: &mut audio::Audio,
+        audio: &mut audio::Audio,
         _message_dispatcher: &mut Dispatcher,
         game_state_peek: &GameStatePeek,
+        _rng: &mut GameRng,
     ) {
         let value = match self.tracking {
-            Some(Tracking::Vials) => game_state_peek.player_vials,
-            Some(Tracking::Lives) => game_state_peek.player_lives,
+            Some(Tracking::Vials) => game_state_peek.player_vials as i32,
+            Some(Tracking::Lives) => game_state_peek.player_lives as i32,
+            Some(Tracking::MusicVolume) => (audio.music_volume() * 10.0).round() as i32,
+            Some(Tracking::SfxVolume) => (audio.sfx_volume() * 10.0).round() as i32,
+            Some(Tracking::ContinueCountdown) => game_state_peek.continue_countdown as i32,
+            Some(Tracking::Continues) => game_state_peek.continues_remaining as i32,
             _ => 0,
-        } as i32;
+        };
 
         let value = value / (10_i32).pow(self.digit);
         self.cycle = (value % 10) as u32;
@@ -3,9 +3,9 @@ use std::time::Duration;
 
 use crate::{
     audio, collision,
-    entity::{Entity, GameStatePeek},
+    entity::{Entity, GameRng, GameStatePeek},
     event_dispatch::*,
-    map,
+    map, property,
     sprite::{self, rendering},
     state::constants::layers,
     tileset,
@@ -41,11 +41,13 @@ impl Entity for UiFlightBar {
         entity_id: u32,
         sprite: &sprite::Sprite,
         _tile: &tileset::Tile,
+        _properties: &property::Properties,
         _map: &map::Map,
         _collision_space: &mut collision::Space,
-    ) {
+    ) -> anyhow::Result<()> {
         self.entity_id = entity_id;
         self.position = point3(sprite.origin.x, sprite.origin.y, layers::ui::FOREGROUND);
+        Ok(())
     }
 
     fn update(
@@ -56,6 +58,7 @@ impl Entity for UiFlightBar {
         _audio: &mut audio::Audio,
         _message_dispatcher: &mut Dispatcher,
         game_state_peek: &GameStatePeek,
+        _rng: &mut GameRng,
     ) {
         self.width_scale_max = game_state_peek.player_flight.1 * FLIGHT_BAR_SCALE;
         self.width_scale_current =
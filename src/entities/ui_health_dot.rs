@@ -4,9 +4,9 @@ use cgmath::*;
 
 use crate::{
     audio, collision,
-    entity::{Entity, GameStatePeek},
+    entity::{Entity, GameRng, GameStatePeek},
     event_dispatch::*,
-    map,
+    map, property,
     sprite::{self, rendering},
     state::constants::{layers, sprite_masks},
     tileset,
@@ -40,9 +40,10 @@ impl Entity for UiHealthDot {
         entity_id: u32,
         sprite: &sprite::Sprite,
         _tile: &tileset::Tile,
+        _properties: &property::Properties,
         _map: &map::Map,
         collision_space: &mut collision::Space,
-    ) {
+    ) -> anyhow::Result<()> {
         self.entity_id = entity_id;
         self.position = point3(sprite.origin.x, sprite.origin.y, layers::ui::FOREGROUND);
 
@@ -50,6 +51,7 @@ impl Entity for UiHealthDot {
         collider.mask = sprite_masks::ui::HEALTH_DOT;
 
         self.collider_id = Some(collision_space.add_collider(collider));
+        Ok(())
     }
 
     fn update(
@@ -60,6 +62,7 @@ impl Entity for UiHealthDot {
         _audio: &mut audio::Audio,
         _message_dispatcher: &mut Dispatcher,
         game_state_peek: &GameStatePeek,
+        _rng: &mut GameRng,
     ) {
         if self.index.is_none() {
             self.index = Some(self.determine_index(collision_space));
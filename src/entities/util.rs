@@ -168,6 +168,7 @@ impl From<CompassDir> for Vector2<f32> {
 }
 
 #[cfg(test)]
+#[allow(clippy::items_after_test_module)]
 mod chase_dir_tests {
     use super::*;
 
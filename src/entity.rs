@@ -1,12 +1,15 @@
 use std::{collections::HashSet, fmt::Debug, time::Duration};
 
 use cgmath::*;
+use rand::SeedableRng;
 use winit::{event::ElementState, keyboard::KeyCode};
 
 use crate::{
     audio, collision,
+    entities::firebrand::FirebrandInput,
     event_dispatch::*,
-    map,
+    input::Action,
+    map, property,
     sprite::{self, rendering},
     tileset,
     util::Bounds,
@@ -37,6 +40,56 @@ impl IdVendor {
 
 // ---------------------------------------------------------------------------------------------------------------------
 
+/// GameRng is the single source of randomness for the simulation. It's owned by AppState, and
+/// is reseeded on Event::ResetState so that gameplay is reproducible from a given seed.
+pub struct GameRng {
+    seed: u64,
+    rng: rand::rngs::StdRng,
+}
+
+impl GameRng {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            rng: rand::rngs::StdRng::seed_from_u64(seed),
+        }
+    }
+
+    /// Creates a GameRng with a seed drawn from system entropy
+    pub fn from_entropy() -> Self {
+        Self::new(rand::random())
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Restarts the random sequence from the original seed
+    pub fn reset(&mut self) {
+        self.rng = rand::rngs::StdRng::seed_from_u64(self.seed);
+    }
+}
+
+impl rand::RngCore for GameRng {
+    fn next_u32(&mut self) -> u32 {
+        self.rng.next_u32()
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.rng.next_u64()
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.rng.fill_bytes(dest)
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.rng.try_fill_bytes(dest)
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+
 /// Entities don't have direct access to game state; but a read-only peek is useful so that
 /// entities may "chase" the player, etc. GameStatePeek is a holder for this information. An proper engine
 /// might store a snapshot of state for each entity in the level, but all we need right now is to know
@@ -50,6 +103,9 @@ pub struct GameStatePeek {
     pub player_lives: u32,         // number of lives remaining
     pub current_map_bounds: Bounds,
     pub camera_position: Point2<f32>,
+    // the continue prompt's state, which GameUi fills in for its entities
+    pub continues_remaining: u32, // number of continues the player may still use
+    pub continue_countdown: u32,  // whole seconds left to accept a continue
 }
 
 impl Default for GameStatePeek {
@@ -62,6 +118,8 @@ impl Default for GameStatePeek {
             player_lives: 0,
             current_map_bounds: Bounds::default(),
             camera_position: point2(0.0, 0.0),
+            continues_remaining: 0,
+            continue_countdown: 0,
         }
     }
 }
@@ -73,17 +131,21 @@ pub trait Entity {
     /// # Arguments
     /// * `sprite` The sprite created from the tile from the level map which instantiated this Entity instance
     /// * `tile` the Tile from the level map which instantiated this Entity instance
+    /// * `properties` the properties of the map object which placed this Entity; empty for entities placed by tile layers
     /// * `map` the map from which the Tile was loaded.
     /// * `collision_space` the shared collision space
     ///
+    /// Returns an error if the tile or properties don't describe a valid instance of this Entity.
     fn init_from_map_sprite(
         &mut self,
         _entity_id: u32,
         _sprite: &sprite::Sprite,
         _tile: &tileset::Tile,
+        _properties: &property::Properties,
         _map: &map::Map,
         _collision_space: &mut collision::Space,
-    ) {
+    ) -> anyhow::Result<()> {
+        Ok(())
     }
 
     /// Initializes an entity whcih is not loaded from the level map. This is generally for dynamic
@@ -95,15 +157,28 @@ pub trait Entity {
         false
     }
 
-    /// Handle gamepad input events. This is invoked on each entity when the
-    /// gamepad input state changes.
-    fn process_gamepad(&mut self, _event: gilrs::Event) {}
+    /// Handle an action from keyboard or gamepad input, as bound by the input map,
+    /// returning true iff said action was consumed.
+    fn process_action(&mut self, _action: Action, _state: ElementState) -> bool {
+        false
+    }
+
+    /// Entities driven by user input return the input state they'll act on this frame.
+    /// This is recorded when capturing a replay.
+    fn user_input(&self) -> Option<FirebrandInput> {
+        None
+    }
+
+    /// Replace the user input state an entity will act on this frame; used for replay playback.
+    fn override_user_input(&mut self, _input: FirebrandInput) {}
 
     /// Update internal state of entity.
     /// # Arguments
     /// * `dt` delta time since last update
     /// * `collision_space` the shared collision space for collision lookup. A moving sprite should update its position in the collision space
     /// * `message_dispatcher` the dispatcher for queing messages to be processed by entities at end of update loop.
+    /// * `rng` the game-wide random number generator; entities must not use any other source of randomness.
+    #[allow(clippy::too_many_arguments)]
     fn update(
         &mut self,
         _dt: Duration,
@@ -112,6 +187,7 @@ pub trait Entity {
         _audio: &mut audio::Audio,
         _message_dispatcher: &mut Dispatcher,
         _game_state_peek: &GameStatePeek,
+        _rng: &mut GameRng,
     ) {
     }
 
@@ -251,3 +327,26 @@ impl Debug for EntityComponents {
         )
     }
 }
+
+// ---------------------------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod game_rng_tests {
+    use super::*;
+    use rand::Rng;
+
+    #[test]
+    fn reset_restarts_sequence() {
+        let mut rng = GameRng::new(42);
+        let first = (0..8).map(|_| rng.gen_range(0..100)).collect::<Vec<i32>>();
+        rng.reset();
+        let second = (0..8).map(|_| rng.gen_range(0..100)).collect::<Vec<i32>>();
+        assert_eq!(first, second);
+
+        let mut other = GameRng::new(42);
+        let third = (0..8)
+            .map(|_| other.gen_range(0..100))
+            .collect::<Vec<i32>>();
+        assert_eq!(first, third);
+    }
+}
@@ -0,0 +1,118 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+// ---------------------------------------------------------------------------------------------------------------------
+
+/// Polls a directory tree for changes to files having any of a set of extensions. Polling
+/// compares modification times, so it needs no platform file notification support.
+pub struct FileWatcher {
+    root: PathBuf,
+    extensions: Vec<String>,
+    modification_times: HashMap<PathBuf, SystemTime>,
+}
+
+impl FileWatcher {
+    /// Creates a FileWatcher for files under `root` with one of `extensions` (without the leading '.');
+    /// files present now are not reported as changed by the first poll.
+    pub fn new(root: &Path, extensions: &[&str]) -> Self {
+        let mut watcher = Self {
+            root: root.to_path_buf(),
+            extensions: extensions.iter().map(|e| e.to_string()).collect(),
+            modification_times: HashMap::new(),
+        };
+        watcher.modification_times = watcher.scan();
+        watcher
+    }
+
+    /// Returns the watched files which were added, modified or removed since the previous poll.
+    pub fn poll(&mut self) -> Vec<PathBuf> {
+        let modification_times = self.scan();
+        let mut changed = modification_times
+            .iter()
+            .filter(|(path, time)| self.modification_times.get(*path) != Some(time))
+            .map(|(path, _)| path.clone())
+            .chain(
+                self.modification_times
+                    .keys()
+                    .filter(|path| !modification_times.contains_key(*path))
+                    .cloned(),
+            )
+            .collect::<Vec<_>>();
+        changed.sort();
+
+        self.modification_times = modification_times;
+        changed
+    }
+
+    fn scan(&self) -> HashMap<PathBuf, SystemTime> {
+        let mut modification_times = HashMap::new();
+        let mut dirs = vec![self.root.clone()];
+        while let Some(dir) = dirs.pop() {
+            let entries = match fs::read_dir(&dir) {
+                Ok(entries) => entries,
+                Err(e) => {
+                    log::warn!("Unable to read directory {:?}: {}", dir, e);
+                    continue;
+                }
+            };
+            for entry in entries.flatten() {
+                let path = entry.path();
+                if path.is_dir() {
+                    dirs.push(path);
+                } else if self.is_watched(&path) {
+                    if let Ok(time) = entry.metadata().and_then(|m| m.modified()) {
+                        modification_times.insert(path, time);
+                    }
+                }
+            }
+        }
+        modification_times
+    }
+
+    fn is_watched(&self, path: &Path) -> bool {
+        path.extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(|e| self.extensions.iter().any(|watched| watched == e))
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod file_watcher_tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn changes_to_watched_files_are_reported() {
+        let root = std::env::temp_dir().join(format!(
+            "platformer-test-file-watcher-{}",
+            std::process::id()
+        ));
+        fs::create_dir_all(root.join("maps")).unwrap();
+        let map = root.join("maps").join("level.tmx");
+        fs::write(&map, "a").unwrap();
+        fs::write(root.join("notes.txt"), "a").unwrap();
+
+        let mut watcher = FileWatcher::new(&root, &["tmx", "png"]);
+        assert!(watcher.poll().is_empty());
+
+        // unwatched extensions are ignored
+        fs::write(root.join("notes.txt"), "b").unwrap();
+        let texture = root.join("sprites.png");
+        fs::write(&texture, "a").unwrap();
+        assert_eq!(watcher.poll(), vec![texture.clone()]);
+
+        let file = fs::File::options().write(true).open(&map).unwrap();
+        file.set_modified(SystemTime::now() + Duration::from_secs(1))
+            .unwrap();
+        fs::remove_file(&texture).unwrap();
+        assert_eq!(watcher.poll(), vec![map, texture]);
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,294 @@
+use std::time::Duration;
+
+use gilrs::{
+    ff::{BaseEffect, BaseEffectType, EffectBuilder, Repeat, Replay, Ticks},
+    GamepadId, Gilrs,
+};
+
+// ---------------------------------------------------------------------------------------------------------------------
+
+/// A force feedback effect played on the active gamepad
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Rumble {
+    /// Strength, from 0 to 1, of the gamepad's low frequency motor
+    pub strong: f32,
+    /// Strength, from 0 to 1, of the gamepad's high frequency motor
+    pub weak: f32,
+    /// How long to rumble; if None, rumbles until `Gamepads::stop_rumble` is called
+    pub duration: Option<Duration>,
+}
+
+/// Input from Gamepads, for the app to act on
+#[derive(Clone, Copy, Debug)]
+pub enum GamepadEvent {
+    /// An event from the active gamepad
+    Input(gilrs::EventType),
+    /// A different gamepad became the active one
+    ActiveChanged,
+    /// The active gamepad was disconnected; no gamepad is active until one is picked
+    ActiveDisconnected,
+}
+
+/// The part of a gamepad event which matters in picking the active gamepad
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Change {
+    Connected,
+    Disconnected,
+    ButtonPressed(gilrs::Button),
+    Other,
+}
+
+impl From<&gilrs::EventType> for Change {
+    fn from(event: &gilrs::EventType) -> Self {
+        match event {
+            gilrs::EventType::Connected => Change::Connected,
+            gilrs::EventType::Disconnected => Change::Disconnected,
+            gilrs::EventType::ButtonPressed(button, ..) => Change::ButtonPressed(*button),
+            _ => Change::Other,
+        }
+    }
+}
+
+/// Returns the active gamepad after `change` on gamepad `id`, and whether the event should reach
+/// the game. The first gamepad connected becomes active, a gamepad becomes active when its Start
+/// button is pressed, or any button when no gamepad is active, and disconnecting the active
+/// gamepad leaves none active.
+fn select_active<Id: Copy + PartialEq>(
+    active: Option<Id>,
+    id: Id,
+    change: Change,
+) -> (Option<Id>, bool) {
+    match change {
+        Change::Connected => (active.or(Some(id)), false),
+        Change::Disconnected if active == Some(id) => (None, false),
+        Change::Disconnected => (active, false),
+        Change::ButtonPressed(button)
+            if active != Some(id) && (active.is_none() || button == gilrs::Button::Start) =>
+        {
+            (Some(id), true)
+        }
+        _ => (active, active == Some(id)),
+    }
+}
+
+/// Returns the gamepad a rumble should play on: the active gamepad, if it supports force
+/// feedback per `is_ff_supported`.
+fn rumble_target<Id: Copy>(active: Option<Id>, is_ff_supported: impl Fn(Id) -> bool) -> Option<Id> {
+    active.filter(|&id| is_ff_supported(id))
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+
+/// Tracks connected gamepads, and which of them is the active one controlling the game; input
+/// from other gamepads is ignored. The first gamepad connected becomes active, and the player
+/// picks another by pressing its Start button, or any button when no gamepad is active.
+pub struct Gamepads {
+    gilrs: Option<Gilrs>,
+    active: Option<GamepadId>,
+    rumble: Option<gilrs::ff::Effect>,
+}
+
+impl Gamepads {
+    pub fn new() -> Self {
+        let gilrs = match Gilrs::new() {
+            Ok(gilrs) => Some(gilrs),
+            Err(e) => {
+                log::warn!("Gamepad support is unavailable: {}", e);
+                None
+            }
+        };
+
+        let mut gamepads = Self {
+            gilrs,
+            active: None,
+            rumble: None,
+        };
+
+        if let Some(gilrs) = &gamepads.gilrs {
+            for (id, gamepad) in gilrs.gamepads() {
+                log::info!("{} is {:?}", gamepad.name(), gamepad.power_info());
+                gamepads.active.get_or_insert(id);
+            }
+        }
+        if let Some(id) = gamepads.active {
+            gamepads.log_active(id);
+        }
+        gamepads
+    }
+
+    /// Returns the input received since the previous poll; events from gamepads other than
+    /// the active one are dropped.
+    pub fn poll(&mut self) -> Vec<GamepadEvent> {
+        let mut events = vec![];
+        while let Some(event) = self.gilrs.as_mut().and_then(Gilrs::next_event) {
+            match event.event {
+                gilrs::EventType::Connected => {
+                    if let Some(gamepad) = self.gamepad_name(event.id) {
+                        log::info!("{} connected", gamepad);
+                    }
+                }
+                gilrs::EventType::Disconnected => {
+                    log::info!("Gamepad {} disconnected", event.id);
+                }
+                _ => {}
+            }
+
+            let (active, forward) = select_active(self.active, event.id, (&event.event).into());
+            if active != self.active {
+                match active {
+                    Some(id) => {
+                        self.set_active(id);
+                        events.push(GamepadEvent::ActiveChanged);
+                    }
+                    None => {
+                        self.active = None;
+                        self.rumble = None;
+                        events.push(GamepadEvent::ActiveDisconnected);
+                    }
+                }
+            }
+            if forward {
+                events.push(GamepadEvent::Input(event.event));
+            }
+        }
+        events
+    }
+
+    /// Plays `rumble` on the active gamepad, if it supports force feedback, replacing any
+    /// rumble already playing.
+    pub fn rumble(&mut self, rumble: Rumble) {
+        let Some(gilrs) = self.gilrs.as_mut() else {
+            return;
+        };
+        let Some(id) = rumble_target(self.active, |id| {
+            gilrs
+                .connected_gamepad(id)
+                .is_some_and(|gamepad| gamepad.is_ff_supported())
+        }) else {
+            return;
+        };
+
+        let magnitude = |strength: f32| (strength.clamp(0.0, 1.0) * u16::MAX as f32) as u16;
+        let (play_for, repeat) = match rumble.duration {
+            Some(duration) => {
+                let ticks = Ticks::from_ms(duration.as_millis() as u32);
+                (ticks, Repeat::For(ticks))
+            }
+            None => (Ticks::from_ms(1000), Repeat::Infinitely),
+        };
+        let scheduling = Replay {
+            play_for,
+            ..Default::default()
+        };
+
+        let effect = EffectBuilder::new()
+            .add_effect(BaseEffect {
+                kind: BaseEffectType::Strong {
+                    magnitude: magnitude(rumble.strong),
+                },
+                scheduling,
+                ..Default::default()
+            })
+            .add_effect(BaseEffect {
+                kind: BaseEffectType::Weak {
+                    magnitude: magnitude(rumble.weak),
+                },
+                scheduling,
+                ..Default::default()
+            })
+            .repeat(repeat)
+            .gamepads(&[id])
+            .finish(gilrs);
+
+        // the effect plays until dropped, so it's kept until replaced or stopped
+        match effect.and_then(|effect| effect.play().map(|_| effect)) {
+            Ok(effect) => self.rumble = Some(effect),
+            Err(e) => log::warn!("Unable to rumble gamepad: {}", e),
+        }
+    }
+
+    /// Stops the rumble playing, if any; only gamepads supporting force feedback play one.
+    pub fn stop_rumble(&mut self) {
+        self.rumble = None;
+    }
+
+    fn set_active(&mut self, id: GamepadId) {
+        self.active = Some(id);
+        self.rumble = None;
+        self.log_active(id);
+    }
+
+    fn log_active(&self, id: GamepadId) {
+        if let Some(gamepad) = self.gamepad_name(id) {
+            log::info!("Using {} as the active gamepad", gamepad);
+        }
+    }
+
+    fn gamepad_name(&self, id: GamepadId) -> Option<String> {
+        self.gilrs
+            .as_ref()
+            .and_then(|gilrs| gilrs.connected_gamepad(id))
+            .map(|gamepad| gamepad.name().to_string())
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod gamepad_tests {
+    use super::*;
+    use gilrs::Button;
+
+    #[test]
+    fn first_connected_gamepad_becomes_active() {
+        assert_eq!(select_active(None, 1, Change::Connected), (Some(1), false));
+        assert_eq!(
+            select_active(Some(1), 2, Change::Connected),
+            (Some(1), false)
+        );
+    }
+
+    #[test]
+    fn start_on_another_gamepad_makes_it_active() {
+        assert_eq!(
+            select_active(Some(1), 2, Change::ButtonPressed(Button::Start)),
+            (Some(2), true)
+        );
+        // other buttons on an inactive gamepad are ignored
+        assert_eq!(
+            select_active(Some(1), 2, Change::ButtonPressed(Button::South)),
+            (Some(1), false)
+        );
+        assert_eq!(select_active(Some(1), 2, Change::Other), (Some(1), false));
+        // with no gamepad active, any button picks one
+        assert_eq!(
+            select_active(None, 2, Change::ButtonPressed(Button::South)),
+            (Some(2), true)
+        );
+        // the active gamepad's input reaches the game
+        assert_eq!(
+            select_active(Some(1), 1, Change::ButtonPressed(Button::Start)),
+            (Some(1), true)
+        );
+        assert_eq!(select_active(Some(1), 1, Change::Other), (Some(1), true));
+    }
+
+    #[test]
+    fn disconnecting_the_active_gamepad_leaves_none_active() {
+        assert_eq!(
+            select_active(Some(1), 1, Change::Disconnected),
+            (None, false)
+        );
+        assert_eq!(
+            select_active(Some(1), 2, Change::Disconnected),
+            (Some(1), false)
+        );
+    }
+
+    #[test]
+    fn rumble_needs_force_feedback() {
+        assert_eq!(rumble_target(Some(1), |_| true), Some(1));
+        assert_eq!(rumble_target(Some(1), |_| false), None);
+        assert_eq!(rumble_target(None::<u32>, |_| true), None);
+    }
+}
@@ -35,34 +35,82 @@ impl ButtonState {
     }
 }
 
+/// The game's abstract inputs. Keys and gamepad buttons/sticks are bound to actions by an
+/// `input_map::InputMap`, and entities respond to actions rather than physical input.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Action {
+    MoveLeft,
+    MoveRight,
+    Jump,
+    Shoot,
+    MenuUp,
+    MenuDown,
+    Pause,
+    ToggleFullscreen,
+    Quit,
+}
+
+impl Action {
+    pub const ALL: [Action; 9] = [
+        Action::MoveLeft,
+        Action::MoveRight,
+        Action::Jump,
+        Action::Shoot,
+        Action::MenuUp,
+        Action::MenuDown,
+        Action::Pause,
+        Action::ToggleFullscreen,
+        Action::Quit,
+    ];
+
+    /// The action's name in input bindings files
+    pub fn name(&self) -> &'static str {
+        match self {
+            Action::MoveLeft => "move_left",
+            Action::MoveRight => "move_right",
+            Action::Jump => "jump",
+            Action::Shoot => "shoot",
+            Action::MenuUp => "menu_up",
+            Action::MenuDown => "menu_down",
+            Action::Pause => "pause",
+            Action::ToggleFullscreen => "toggle_fullscreen",
+            Action::Quit => "quit",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Action> {
+        Self::ALL.into_iter().find(|action| action.name() == name)
+    }
+}
+
 #[derive(Default, Debug)]
 pub struct InputState {
-    buttons: HashMap<winit::keyboard::KeyCode, ButtonState>,
+    buttons: HashMap<Action, ButtonState>,
 }
 
 impl InputState {
-    pub fn for_keys(keys: &[winit::keyboard::KeyCode]) -> Self {
+    pub fn for_actions(actions: &[Action]) -> Self {
         let mut buttons = HashMap::new();
-        for key in keys {
-            buttons.insert(*key, ButtonState::default());
+        for action in actions {
+            buttons.insert(*action, ButtonState::default());
         }
 
         Self { buttons }
     }
 
-    pub fn register(&mut self, key: winit::keyboard::KeyCode) {
-        self.buttons.insert(key, ButtonState::default());
+    pub fn register(&mut self, action: Action) {
+        self.buttons.insert(action, ButtonState::default());
     }
 
-    pub fn get_button_state(&self, key: winit::keyboard::KeyCode) -> Option<&ButtonState> {
-        self.buttons.get(&key)
+    pub fn get_button_state(&self, action: Action) -> Option<&ButtonState> {
+        self.buttons.get(&action)
     }
 
-    pub fn process_keyboard(&mut self, key: winit::keyboard::KeyCode, state: ElementState) -> bool {
+    pub fn process_action(&mut self, action: Action, state: ElementState) -> bool {
         let pressed = state == ElementState::Pressed;
-        if let Some(button_state) = self.buttons.get(&key) {
+        if let Some(button_state) = self.buttons.get(&action) {
             let new_state = button_state.transition(pressed);
-            self.buttons.insert(key, new_state);
+            self.buttons.insert(action, new_state);
             true
         } else {
             false
@@ -71,15 +119,15 @@ impl InputState {
 
     pub fn update(&mut self) {
         let previous_button_state = std::mem::take(&mut self.buttons);
-        for (key, button_state) in previous_button_state {
+        for (action, button_state) in previous_button_state {
             self.buttons
-                .insert(key, button_state.transition(button_state.is_active()));
+                .insert(action, button_state.transition(button_state.is_active()));
         }
     }
 
     /// Reassign button state to the provided one - this is intended for
     /// situations where overriding user input is necessary.
-    pub fn set(&mut self, buttons: HashMap<winit::keyboard::KeyCode, ButtonState>) {
+    pub fn set(&mut self, buttons: HashMap<Action, ButtonState>) {
         self.buttons = buttons;
     }
 }
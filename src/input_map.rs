@@ -0,0 +1,632 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    io::BufRead,
+    path::Path,
+};
+
+use anyhow::*;
+use winit::{event::ElementState, keyboard::KeyCode};
+
+use crate::{assets, input::Action};
+
+// ---------------------------------------------------------------------------------------------------------------------
+
+/// Keys which may be bound to actions, named in bindings files as winit names them
+const KEYS: &[KeyCode] = &[
+    KeyCode::KeyA,
+    KeyCode::KeyB,
+    KeyCode::KeyC,
+    KeyCode::KeyD,
+    KeyCode::KeyE,
+    KeyCode::KeyF,
+    KeyCode::KeyG,
+    KeyCode::KeyH,
+    KeyCode::KeyI,
+    KeyCode::KeyJ,
+    KeyCode::KeyK,
+    KeyCode::KeyL,
+    KeyCode::KeyM,
+    KeyCode::KeyN,
+    KeyCode::KeyO,
+    KeyCode::KeyP,
+    KeyCode::KeyQ,
+    KeyCode::KeyR,
+    KeyCode::KeyS,
+    KeyCode::KeyT,
+    KeyCode::KeyU,
+    KeyCode::KeyV,
+    KeyCode::KeyW,
+    KeyCode::KeyX,
+    KeyCode::KeyY,
+    KeyCode::KeyZ,
+    KeyCode::Digit0,
+    KeyCode::Digit1,
+    KeyCode::Digit2,
+    KeyCode::Digit3,
+    KeyCode::Digit4,
+    KeyCode::Digit5,
+    KeyCode::Digit6,
+    KeyCode::Digit7,
+    KeyCode::Digit8,
+    KeyCode::Digit9,
+    KeyCode::Numpad0,
+    KeyCode::Numpad1,
+    KeyCode::Numpad2,
+    KeyCode::Numpad3,
+    KeyCode::Numpad4,
+    KeyCode::Numpad5,
+    KeyCode::Numpad6,
+    KeyCode::Numpad7,
+    KeyCode::Numpad8,
+    KeyCode::Numpad9,
+    KeyCode::ArrowUp,
+    KeyCode::ArrowDown,
+    KeyCode::ArrowLeft,
+    KeyCode::ArrowRight,
+    KeyCode::F1,
+    KeyCode::F2,
+    KeyCode::F3,
+    KeyCode::F4,
+    KeyCode::F5,
+    KeyCode::F6,
+    KeyCode::F7,
+    KeyCode::F8,
+    KeyCode::F9,
+    KeyCode::F10,
+    KeyCode::F11,
+    KeyCode::F12,
+    KeyCode::Space,
+    KeyCode::Enter,
+    KeyCode::Escape,
+    KeyCode::Tab,
+    KeyCode::Backspace,
+    KeyCode::ShiftLeft,
+    KeyCode::ShiftRight,
+    KeyCode::ControlLeft,
+    KeyCode::ControlRight,
+    KeyCode::AltLeft,
+    KeyCode::AltRight,
+    KeyCode::Comma,
+    KeyCode::Period,
+    KeyCode::Slash,
+    KeyCode::Semicolon,
+    KeyCode::Quote,
+    KeyCode::BracketLeft,
+    KeyCode::BracketRight,
+    KeyCode::Minus,
+    KeyCode::Equal,
+    KeyCode::Backquote,
+    KeyCode::Backslash,
+    KeyCode::Insert,
+    KeyCode::Home,
+    KeyCode::End,
+    KeyCode::PageUp,
+    KeyCode::PageDown,
+];
+
+/// Gamepad buttons which may be bound to actions, named in bindings files as gilrs names them
+const BUTTONS: &[gilrs::Button] = &[
+    gilrs::Button::South,
+    gilrs::Button::East,
+    gilrs::Button::North,
+    gilrs::Button::West,
+    gilrs::Button::C,
+    gilrs::Button::Z,
+    gilrs::Button::LeftTrigger,
+    gilrs::Button::LeftTrigger2,
+    gilrs::Button::RightTrigger,
+    gilrs::Button::RightTrigger2,
+    gilrs::Button::Select,
+    gilrs::Button::Start,
+    gilrs::Button::Mode,
+    gilrs::Button::LeftThumb,
+    gilrs::Button::RightThumb,
+    gilrs::Button::DPadUp,
+    gilrs::Button::DPadDown,
+    gilrs::Button::DPadLeft,
+    gilrs::Button::DPadRight,
+];
+
+/// Gamepad axes whose directions may be bound to actions, named in bindings files as gilrs names them
+const AXES: &[gilrs::Axis] = &[
+    gilrs::Axis::LeftStickX,
+    gilrs::Axis::LeftStickY,
+    gilrs::Axis::RightStickX,
+    gilrs::Axis::RightStickY,
+    gilrs::Axis::LeftZ,
+    gilrs::Axis::RightZ,
+    gilrs::Axis::DPadX,
+    gilrs::Axis::DPadY,
+];
+
+/// Bindings file used when neither --input-bindings nor the settings file provide bindings
+pub const DEFAULT_BINDINGS_FILE: &str = "res/input.bindings";
+
+const DEFAULT_STICK_DEADZONE: f32 = 0.25;
+const DEFAULT_STICK_HYSTERESIS: f32 = 0.1;
+
+// ---------------------------------------------------------------------------------------------------------------------
+
+/// A physical input which may be bound to an Action
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Binding {
+    Key(KeyCode),
+    Button(gilrs::Button),
+    /// A gamepad axis pushed past the deadzone, in the positive or negative direction
+    Axis {
+        axis: gilrs::Axis,
+        positive: bool,
+    },
+}
+
+impl Binding {
+    /// Parses a binding's name, e.g., "KeyA", "ArrowLeft", "South", "DPadLeft" or "LeftStickX-"
+    pub fn from_name(name: &str) -> Option<Binding> {
+        fn find<T: Copy + fmt::Debug>(name: &str, candidates: &[T]) -> Option<T> {
+            candidates
+                .iter()
+                .copied()
+                .find(|c| format!("{:?}", c) == name)
+        }
+
+        if let Some(axis) = name.strip_suffix('+') {
+            find(axis, AXES).map(|axis| Binding::Axis {
+                axis,
+                positive: true,
+            })
+        } else if let Some(axis) = name.strip_suffix('-') {
+            find(axis, AXES).map(|axis| Binding::Axis {
+                axis,
+                positive: false,
+            })
+        } else {
+            find(name, KEYS)
+                .map(Binding::Key)
+                .or_else(|| find(name, BUTTONS).map(Binding::Button))
+        }
+    }
+}
+
+impl fmt::Display for Binding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Binding::Key(key) => write!(f, "{:?}", key),
+            Binding::Button(button) => write!(f, "{:?}", button),
+            Binding::Axis { axis, positive } => {
+                write!(f, "{:?}{}", axis, if *positive { "+" } else { "-" })
+            }
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+
+/// Bindings of keys, gamepad buttons and gamepad axis directions to actions, loaded from a
+/// bindings file. The file is line oriented; each action's line lists its bindings, separated
+/// by whitespace:
+///
+/// ```text
+/// # comments and blank lines are ignored
+/// stick_deadzone 0.25
+/// stick_hysteresis 0.1
+/// move_left KeyA ArrowLeft DPadLeft LeftStickX-
+/// move_right KeyD ArrowRight DPadRight LeftStickX+
+/// jump KeyW ArrowUp East West DPadUp LeftStickY+
+/// shoot Space South North
+/// menu_up KeyW ArrowUp DPadUp LeftStickY+
+/// menu_down KeyS ArrowDown DPadDown LeftStickY-
+/// pause F1 Start
+/// toggle_fullscreen F11
+/// quit Escape
+/// ```
+///
+/// Keys are named as winit's `KeyCode` variants, and buttons and axes as gilrs' `Button` and `Axis`
+/// variants; an axis is suffixed with the direction, '+' or '-', bound. `stick_deadzone` is how far,
+/// from 0 to 1, an axis must be pushed before it counts as held, and `stick_hysteresis` how much
+/// further than that it must return before it's released, so a stick resting near the edge of the
+/// deadzone doesn't chatter. Actions without a line are unbound.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Bindings {
+    bindings: HashMap<Action, Vec<Binding>>,
+    pub stick_deadzone: f32,
+    pub stick_hysteresis: f32,
+}
+
+impl Default for Bindings {
+    fn default() -> Self {
+        Self {
+            bindings: HashMap::new(),
+            stick_deadzone: DEFAULT_STICK_DEADZONE,
+            stick_hysteresis: DEFAULT_STICK_HYSTERESIS,
+        }
+    }
+}
+
+impl Bindings {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let file = assets::open(path)
+            .with_context(|| format!("Unable to open input bindings {:?}", path))?;
+        Self::read(file).with_context(|| format!("Unable to read input bindings {:?}", path))
+    }
+
+    /// Returns the bindings of `action`
+    pub fn get(&self, action: Action) -> &[Binding] {
+        self.bindings.get(&action).map_or(&[], Vec::as_slice)
+    }
+
+    /// Replaces the bindings of `action`
+    pub fn set(&mut self, action: Action, bindings: Vec<Binding>) {
+        self.bindings.insert(action, bindings);
+    }
+
+    pub fn is_bound(&self, binding: Binding) -> bool {
+        self.actions(binding).next().is_some()
+    }
+
+    /// Returns the actions `binding` is bound to
+    pub fn actions(&self, binding: Binding) -> impl Iterator<Item = Action> + '_ {
+        Action::ALL
+            .into_iter()
+            .filter(move |action| self.get(*action).contains(&binding))
+    }
+
+    fn read<R: BufRead>(reader: R) -> Result<Self> {
+        let mut bindings = Bindings::default();
+
+        for (idx, line) in reader.lines().enumerate() {
+            let line = line?;
+            let line_number = idx + 1;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let mut tokens = line.split_whitespace();
+            let key = tokens.next().unwrap_or_default();
+            let values = tokens.collect::<Vec<_>>();
+            if !bindings.read_entry(key, &values, line_number)? {
+                bail!("Unrecognized action \"{}\" on line {}", key, line_number);
+            }
+        }
+
+        bindings.validate()?;
+        Ok(bindings)
+    }
+
+    /// Reads a `<key> <values>` line of a bindings file, e.g., an action and its bindings, into
+    /// these bindings. Returns false if `key` is neither an action nor a stick setting.
+    pub fn read_entry(&mut self, key: &str, values: &[&str], line_number: usize) -> Result<bool> {
+        let is_stick_setting = key == "stick_deadzone" || key == "stick_hysteresis";
+        let action = Action::from_name(key);
+        if !is_stick_setting && action.is_none() {
+            return Ok(false);
+        }
+        if values.is_empty() {
+            bail!("Expected a value for \"{}\" on line {}", key, line_number);
+        }
+
+        if is_stick_setting {
+            let value = values[0]
+                .parse::<f32>()
+                .ok()
+                .filter(|value| (0.0..1.0).contains(value))
+                .with_context(|| {
+                    format!(
+                        "Expected {} on line {} to be a number from 0 to 1",
+                        key, line_number
+                    )
+                })?;
+            if key == "stick_deadzone" {
+                self.stick_deadzone = value;
+            } else {
+                self.stick_hysteresis = value;
+            }
+        } else if let Some(action) = action {
+            let action_bindings = values
+                .iter()
+                .map(|name| {
+                    Binding::from_name(name).with_context(|| {
+                        format!(
+                            "Unrecognized key, button or axis \"{}\" on line {}",
+                            name, line_number
+                        )
+                    })
+                })
+                .collect::<Result<Vec<_>>>()?;
+            self.set(action, action_bindings);
+        }
+        Ok(true)
+    }
+
+    /// Checks the bindings' settings are consistent with one another
+    pub fn validate(&self) -> Result<()> {
+        if self.stick_hysteresis > self.stick_deadzone {
+            bail!(
+                "stick_hysteresis ({}) must not exceed stick_deadzone ({})",
+                self.stick_hysteresis,
+                self.stick_deadzone
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Writes the bindings in the bindings file format
+impl fmt::Display for Bindings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "stick_deadzone {}", self.stick_deadzone)?;
+        writeln!(f, "stick_hysteresis {}", self.stick_hysteresis)?;
+        for action in Action::ALL {
+            let bindings = self.get(action);
+            if !bindings.is_empty() {
+                write!(f, "{}", action.name())?;
+                for binding in bindings {
+                    write!(f, " {}", binding)?;
+                }
+                writeln!(f)?;
+            }
+        }
+        std::result::Result::Ok(())
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+
+/// Translates keyboard and gamepad input into actions per a set of Bindings. An action is held
+/// while any of its bindings are held, so an action bound to both a key and a gamepad button is
+/// only released when both are.
+#[derive(Debug)]
+pub struct InputMap {
+    bindings: Bindings,
+    held: HashMap<Action, HashSet<Binding>>,
+}
+
+impl InputMap {
+    pub fn new(bindings: Bindings) -> Self {
+        Self {
+            bindings,
+            held: HashMap::new(),
+        }
+    }
+
+    pub fn bindings(&self) -> &Bindings {
+        &self.bindings
+    }
+
+    /// Returns the actions pressed or released by a key event
+    pub fn process_keyboard(
+        &mut self,
+        key: KeyCode,
+        state: ElementState,
+    ) -> Vec<(Action, ElementState)> {
+        self.set_held(Binding::Key(key), state == ElementState::Pressed)
+    }
+
+    /// Returns the actions pressed or released by a gamepad event
+    pub fn process_gamepad(&mut self, event: gilrs::EventType) -> Vec<(Action, ElementState)> {
+        match event {
+            gilrs::EventType::ButtonPressed(button, ..) => self.process_button(button, true),
+            gilrs::EventType::ButtonReleased(button, ..) => self.process_button(button, false),
+            gilrs::EventType::AxisChanged(axis, value, ..) => self.process_axis(axis, value),
+            _ => vec![],
+        }
+    }
+
+    /// Releases every held gamepad button and axis, e.g., when the gamepad is disconnected,
+    /// returning the actions this releases.
+    pub fn release_gamepad(&mut self) -> Vec<(Action, ElementState)> {
+        let held_gamepad_bindings = self
+            .held
+            .values()
+            .flatten()
+            .filter(|binding| !matches!(binding, Binding::Key(_)))
+            .copied()
+            .collect::<HashSet<_>>();
+        held_gamepad_bindings
+            .into_iter()
+            .flat_map(|binding| self.set_held(binding, false))
+            .collect()
+    }
+
+    fn process_button(
+        &mut self,
+        button: gilrs::Button,
+        pressed: bool,
+    ) -> Vec<(Action, ElementState)> {
+        self.set_held(Binding::Button(button), pressed)
+    }
+
+    /// An axis direction is held once pushed past the deadzone, and released once it returns
+    /// within the deadzone less the hysteresis.
+    fn process_axis(&mut self, axis: gilrs::Axis, value: f32) -> Vec<(Action, ElementState)> {
+        let mut actions = vec![];
+        for (positive, magnitude) in [(true, value), (false, -value)] {
+            let binding = Binding::Axis { axis, positive };
+            let threshold = if self.is_held(binding) {
+                self.bindings.stick_deadzone - self.bindings.stick_hysteresis
+            } else {
+                self.bindings.stick_deadzone
+            };
+            actions.extend(self.set_held(binding, magnitude > threshold));
+        }
+        actions
+    }
+
+    fn is_held(&self, binding: Binding) -> bool {
+        self.held.values().any(|held| held.contains(&binding))
+    }
+
+    /// Records `binding` as held or not, returning the actions this presses or releases.
+    fn set_held(&mut self, binding: Binding, is_held: bool) -> Vec<(Action, ElementState)> {
+        let actions = self.bindings.actions(binding).collect::<Vec<_>>();
+        let mut changes = vec![];
+        for action in actions {
+            let held = self.held.entry(action).or_default();
+            let was_active = !held.is_empty();
+            if is_held {
+                held.insert(binding);
+            } else {
+                held.remove(&binding);
+            }
+
+            match (was_active, !held.is_empty()) {
+                (false, true) => changes.push((action, ElementState::Pressed)),
+                (true, false) => changes.push((action, ElementState::Released)),
+                _ => {}
+            }
+        }
+        changes
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod input_map_tests {
+    use super::*;
+
+    const BINDINGS: &str = "# test bindings\n\
+                            stick_deadzone 0.5\n\
+                            stick_hysteresis 0.2\n\
+                            \n\
+                            move_left KeyA ArrowLeft DPadLeft LeftStickX-\n\
+                            move_right KeyD LeftStickX+\n\
+                            jump KeyW South\n";
+
+    #[test]
+    fn bindings_parse_keys_buttons_and_axes() {
+        let bindings = Bindings::read(BINDINGS.as_bytes()).unwrap();
+        assert_eq!(bindings.stick_deadzone, 0.5);
+        assert_eq!(bindings.stick_hysteresis, 0.2);
+        assert_eq!(
+            bindings.get(Action::MoveLeft),
+            &[
+                Binding::Key(KeyCode::KeyA),
+                Binding::Key(KeyCode::ArrowLeft),
+                Binding::Button(gilrs::Button::DPadLeft),
+                Binding::Axis {
+                    axis: gilrs::Axis::LeftStickX,
+                    positive: false
+                },
+            ]
+        );
+        assert!(bindings.get(Action::Pause).is_empty());
+        assert_eq!(bindings.get(Action::MoveLeft)[3].to_string(), "LeftStickX-");
+
+        assert!(Bindings::read("jump KeyW Shift\n".as_bytes()).is_err());
+        assert!(Bindings::read("fly KeyW\n".as_bytes()).is_err());
+        assert!(Bindings::read("jump\n".as_bytes()).is_err());
+        assert!(Bindings::read("stick_deadzone 2\n".as_bytes()).is_err());
+        assert!(Bindings::read("stick_deadzone 0.2\nstick_hysteresis 0.3\n".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn action_is_held_while_any_binding_is_held() {
+        let mut input_map = InputMap::new(Bindings::read(BINDINGS.as_bytes()).unwrap());
+
+        assert_eq!(
+            input_map.process_keyboard(KeyCode::KeyA, ElementState::Pressed),
+            vec![(Action::MoveLeft, ElementState::Pressed)]
+        );
+        // key repeat doesn't press the action again
+        assert!(input_map
+            .process_keyboard(KeyCode::KeyA, ElementState::Pressed)
+            .is_empty());
+        assert!(input_map
+            .process_button(gilrs::Button::DPadLeft, true)
+            .is_empty());
+        assert!(input_map
+            .process_keyboard(KeyCode::KeyA, ElementState::Released)
+            .is_empty());
+        assert_eq!(
+            input_map.process_button(gilrs::Button::DPadLeft, false),
+            vec![(Action::MoveLeft, ElementState::Released)]
+        );
+
+        // unbound input maps to no action
+        assert!(input_map
+            .process_keyboard(KeyCode::KeyQ, ElementState::Pressed)
+            .is_empty());
+    }
+
+    #[test]
+    fn axis_is_held_outside_deadzone() {
+        let mut input_map = InputMap::new(Bindings::read(BINDINGS.as_bytes()).unwrap());
+
+        assert!(input_map
+            .process_axis(gilrs::Axis::LeftStickX, 0.4)
+            .is_empty());
+        assert_eq!(
+            input_map.process_axis(gilrs::Axis::LeftStickX, 0.6),
+            vec![(Action::MoveRight, ElementState::Pressed)]
+        );
+        assert_eq!(
+            input_map.process_axis(gilrs::Axis::LeftStickX, -0.8),
+            vec![
+                (Action::MoveRight, ElementState::Released),
+                (Action::MoveLeft, ElementState::Pressed)
+            ]
+        );
+        assert_eq!(
+            input_map.process_axis(gilrs::Axis::LeftStickX, 0.0),
+            vec![(Action::MoveLeft, ElementState::Released)]
+        );
+    }
+
+    #[test]
+    fn axis_is_released_past_hysteresis() {
+        let mut input_map = InputMap::new(Bindings::read(BINDINGS.as_bytes()).unwrap());
+
+        assert_eq!(
+            input_map.process_axis(gilrs::Axis::LeftStickX, 0.6),
+            vec![(Action::MoveRight, ElementState::Pressed)]
+        );
+        // within the deadzone, but not by more than the hysteresis
+        assert!(input_map
+            .process_axis(gilrs::Axis::LeftStickX, 0.4)
+            .is_empty());
+        assert_eq!(
+            input_map.process_axis(gilrs::Axis::LeftStickX, 0.25),
+            vec![(Action::MoveRight, ElementState::Released)]
+        );
+        // once released, the stick must be pushed past the deadzone again
+        assert!(input_map
+            .process_axis(gilrs::Axis::LeftStickX, 0.45)
+            .is_empty());
+    }
+
+    #[test]
+    fn releasing_gamepad_releases_its_actions() {
+        let mut input_map = InputMap::new(Bindings::read(BINDINGS.as_bytes()).unwrap());
+
+        input_map.process_keyboard(KeyCode::KeyA, ElementState::Pressed);
+        input_map.process_button(gilrs::Button::DPadLeft, true);
+        input_map.process_button(gilrs::Button::South, true);
+        input_map.process_axis(gilrs::Axis::LeftStickX, 0.9);
+
+        let mut released = input_map.release_gamepad();
+        released.sort_by_key(|(action, _)| action.name());
+        // move_left remains held by its key
+        assert_eq!(
+            released,
+            vec![
+                (Action::Jump, ElementState::Released),
+                (Action::MoveRight, ElementState::Released)
+            ]
+        );
+        assert_eq!(
+            input_map.process_keyboard(KeyCode::KeyA, ElementState::Released),
+            vec![(Action::MoveLeft, ElementState::Released)]
+        );
+    }
+
+    #[test]
+    fn default_bindings_load() {
+        let bindings = Bindings::load("res/input.bindings").unwrap();
+        for action in Action::ALL {
+            assert!(!bindings.get(action).is_empty(), "{:?} is unbound", action);
+        }
+    }
+}
@@ -1,24 +1,42 @@
 #![allow(dead_code)]
 
-use gilrs::Gilrs;
+use std::path::{Path, PathBuf};
+
 use state::constants::{ORIGINAL_WINDOW_HEIGHT, ORIGINAL_WINDOW_WIDTH};
 
+use anyhow::Context;
 use structopt::StructOpt;
-use winit::{dpi::LogicalSize, event::*, event_loop::EventLoop, window::Window};
+use winit::{
+    dpi::LogicalSize,
+    event::*,
+    event_loop::EventLoop,
+    window::{Fullscreen, Window},
+};
 
+mod assets;
 mod audio;
 mod camera;
+mod campaign;
 mod collision;
 mod entities;
 mod entity;
 mod event_dispatch;
+mod file_watcher;
+mod gamepad;
 mod input;
+mod input_map;
 mod map;
+mod palette;
+mod property;
+mod replay;
+mod save;
+mod settings;
 mod sprite;
 mod state;
 mod texture;
 mod tileset;
 mod util;
+mod validation;
 
 // ---------------------------------------------------------------------------------------------------------------------
 
@@ -32,6 +50,14 @@ pub struct Options {
     #[structopt(short, long)]
     pub gameboy: bool,
 
+    /// Campaign manifest listing the game's levels
+    #[structopt(long, parse(from_os_str), default_value = "res/campaign.manifest")]
+    pub campaign: PathBuf,
+
+    /// Starts gameplay at specified level (index into the campaign's levels)
+    #[structopt(long, default_value = "0")]
+    pub level: u32,
+
     /// Starts gameplay at specified checkpoint
     #[structopt(short, long)]
     pub checkpoint: Option<u32>,
@@ -40,7 +66,25 @@ pub struct Options {
     #[structopt(short, long, default_value = "3")]
     pub lives: u32,
 
-    /// Palette to use; options are "gameboy", "mist", "nostalgia", and "nymph"
+    /// Number of vials player starts with
+    #[structopt(long, default_value = "0")]
+    pub vials: u32,
+
+    /// Number of continues offered after a game over; each restarts with the starting lives
+    #[structopt(long, default_value = "3")]
+    pub continues: u32,
+
+    /// Lives remaining when resuming a saved game, which may be fewer than --lives; set by the
+    /// continue command
+    #[structopt(long, hidden = true)]
+    pub lives_remaining: Option<u32>,
+
+    /// Continues remaining when resuming a saved game; set by the continue command
+    #[structopt(long, hidden = true)]
+    pub continues_remaining: Option<u32>,
+
+    /// Palette to use; options are "gameboy", "mist", "nostalgia", and "nymph", or the path to a
+    /// palette file: a 4 pixel PNG strip, or a list of 4 hex colors from darkest to lightest
     #[structopt(short, long, default_value = "gameboy")]
     pub palette: String,
 
@@ -51,47 +95,238 @@ pub struct Options {
     /// Disables music
     #[structopt(short, long)]
     pub no_music: bool,
+
+    /// Input bindings file mapping keys and gamepad buttons and sticks to the game's actions;
+    /// defaults to the settings file's bindings, if it has any, otherwise res/input.bindings
+    #[structopt(long, parse(from_os_str))]
+    pub input_bindings: Option<PathBuf>,
+
+    /// Rate, in Hz, at which the game simulation is stepped; rendering interpolates between steps
+    #[structopt(long, default_value = "60")]
+    pub simulation_rate: u32,
+
+    /// Seed for the game's random number generator; if unset, a random seed is used
+    #[structopt(long)]
+    pub seed: Option<u64>,
+
+    /// Records the session's input to the specified replay file
+    #[structopt(long, parse(from_os_str))]
+    pub record: Option<PathBuf>,
+
+    /// Plays back a session from the specified replay file
+    #[structopt(long, parse(from_os_str), conflicts_with = "record")]
+    pub replay: Option<PathBuf>,
+
+    /// Reloads the level, keeping firebrand where he stands, when maps, tilesets or textures in res/ change
+    #[structopt(long, conflicts_with = "replay")]
+    pub hot_reload: bool,
+
+    /// Directory holding the game's res/ directory; defaults to the executable's directory if it
+    /// holds the game's assets, otherwise the working directory. Relative asset paths resolve against it
+    #[structopt(long, parse(from_os_str), env = "PLATFORMER_ASSET_ROOT")]
+    pub asset_root: Option<PathBuf>,
+
+    /// Archive, built by the pack command, to load assets missing from the asset root from;
+    /// defaults to the asset root's assets.pak, if present
+    #[structopt(long, parse(from_os_str), env = "PLATFORMER_ASSET_ARCHIVE")]
+    pub asset_archive: Option<PathBuf>,
+
+    /// File progress is saved to, and continued from; defaults to save.txt in the user's data directory
+    #[structopt(long, parse(from_os_str), env = "PLATFORMER_SAVE_FILE")]
+    pub save_file: Option<PathBuf>,
+
+    /// File the player's settings are loaded from, and saved to when changed in game; defaults
+    /// to settings.txt in the user's configuration directory
+    #[structopt(long, parse(from_os_str), env = "PLATFORMER_SETTINGS_FILE")]
+    pub settings_file: Option<PathBuf>,
+
+    #[structopt(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Clone, StructOpt, Debug)]
+pub enum Command {
+    /// Checks level maps for problems, reporting each with its file and tile coordinates. If no
+    /// maps are specified, the campaign's maps are checked.
+    Validate {
+        #[structopt(parse(from_os_str))]
+        maps: Vec<PathBuf>,
+    },
+    /// Resumes play from the saved game, with the current settings
+    Continue,
+    /// Packs the asset root's res/ directory into a single archive file, which may be shipped in
+    /// place of it
+    Pack {
+        #[structopt(parse(from_os_str), default_value = "assets.pak")]
+        output: PathBuf,
+    },
+}
+
+impl Options {
+    /// The file progress is saved to, if one can be determined
+    pub fn save_path(&self) -> Option<PathBuf> {
+        self.save_file.clone().or_else(save::default_save_path)
+    }
+
+    /// The file settings are loaded from and saved to, if one can be determined
+    pub fn settings_path(&self) -> Option<PathBuf> {
+        self.settings_file
+            .clone()
+            .or_else(settings::default_settings_path)
+    }
+
+    /// The fixed timestep at which the game simulation advances
+    pub fn simulation_timestep(&self) -> std::time::Duration {
+        std::time::Duration::from_secs_f64(1.0 / self.simulation_rate.max(1) as f64)
+    }
+
+    /// Returns command line arguments reproducing these options' effect on gameplay; record/replay
+    /// are omitted, as is the seed, which replays record separately.
+    pub fn to_args(&self) -> Vec<String> {
+        // destructured so that new options must be either recorded here or explicitly left out
+        let Options {
+            debug_overlay,
+            gameboy,
+            campaign,
+            level,
+            checkpoint,
+            lives,
+            vials,
+            continues,
+            lives_remaining,
+            continues_remaining,
+            palette,
+            no_sludgy,
+            no_music,
+            simulation_rate,
+            // input is recorded as firebrand's actions, whatever it's bound to
+            input_bindings: _,
+            seed: _,
+            record: _,
+            replay: _,
+            hot_reload: _,
+            asset_root: _,
+            asset_archive: _,
+            save_file: _,
+            settings_file: _,
+            command: _,
+        } = self;
+
+        let mut args = vec![];
+        if *debug_overlay {
+            args.push("--debug-overlay".to_string());
+        }
+        if *gameboy {
+            args.push("--gameboy".to_string());
+        }
+        args.push("--campaign".to_string());
+        args.push(campaign.to_string_lossy().to_string());
+        args.push("--level".to_string());
+        args.push(level.to_string());
+        if let Some(checkpoint) = checkpoint {
+            args.push("--checkpoint".to_string());
+            args.push(checkpoint.to_string());
+        }
+        args.push("--lives".to_string());
+        args.push(lives.to_string());
+        if *vials > 0 {
+            args.push("--vials".to_string());
+            args.push(vials.to_string());
+        }
+        args.push("--continues".to_string());
+        args.push(continues.to_string());
+        if let Some(lives_remaining) = lives_remaining {
+            args.push("--lives-remaining".to_string());
+            args.push(lives_remaining.to_string());
+        }
+        if let Some(continues_remaining) = continues_remaining {
+            args.push("--continues-remaining".to_string());
+            args.push(continues_remaining.to_string());
+        }
+        args.push("--palette".to_string());
+        args.push(palette.clone());
+        if *no_sludgy {
+            args.push("--no-sludgy".to_string());
+        }
+        if *no_music {
+            args.push("--no-music".to_string());
+        }
+        args.push("--simulation-rate".to_string());
+        args.push(simulation_rate.to_string());
+        args
+    }
 }
 
 // ---------------------------------------------------------------------------------------------------------------------
 
 struct WinitApp {
     app: Option<state::app_state::AppState>,
-    gamepad_input: Option<Gilrs>,
     options: Options,
+    // taken by the AppState when it's created
+    settings: Option<(settings::Settings, Option<PathBuf>)>,
+    replay: Option<replay::Replay>,
     last_render_time: std::time::Instant,
     frame_index: u32,
+    // set if the app failed to start or to load a level, to be reported when the event loop exits
+    error: Option<anyhow::Error>,
 }
 
 impl WinitApp {
-    fn new(options: Options) -> Self {
+    fn new(
+        options: Options,
+        settings: settings::Settings,
+        settings_path: Option<PathBuf>,
+        replay: Option<replay::Replay>,
+    ) -> Self {
         Self {
             app: None,
-            gamepad_input: None,
             options,
+            settings: Some((settings, settings_path)),
+            replay,
             last_render_time: std::time::Instant::now(),
             frame_index: 0,
+            error: None,
         }
     }
 }
 
 impl winit::application::ApplicationHandler for WinitApp {
     fn resumed(&mut self, event_loop: &winit::event_loop::ActiveEventLoop) {
+        let Some((settings, settings_path)) = self.settings.take() else {
+            return;
+        };
+        let session_settings = settings.session();
+
         let mut window_attrs = Window::default_attributes().with_title("Gargoyle's Quest");
-        if self.options.gameboy {
+        let replay_viewport = self.replay.as_ref().and_then(|replay| replay.viewport);
+        if let Some(viewport) = replay_viewport {
+            let size = winit::dpi::PhysicalSize::new(viewport.width, viewport.height);
+            window_attrs = window_attrs.with_inner_size(size);
+        } else if self.options.gameboy {
             let size = LogicalSize::new(ORIGINAL_WINDOW_WIDTH * 4, ORIGINAL_WINDOW_HEIGHT * 4);
             window_attrs = window_attrs.with_inner_size(size);
+        } else if let Some((width, height)) = session_settings.window_size {
+            window_attrs = window_attrs.with_inner_size(LogicalSize::new(width, height));
+        }
+        if session_settings.fullscreen {
+            window_attrs = window_attrs.with_fullscreen(Some(Fullscreen::Borderless(None)));
         }
 
         let window = event_loop.create_window(window_attrs).unwrap();
 
-        self.app = Some(state::app_state::AppState::new(window, self.options.clone()).unwrap());
-
-        let gamepad_input = Gilrs::new().unwrap();
-        for (_id, gamepad) in gamepad_input.gamepads() {
-            log::info!("{} is {:?}", gamepad.name(), gamepad.power_info());
+        match state::app_state::AppState::new(
+            window,
+            self.options.clone(),
+            settings,
+            settings_path,
+            self.replay.take(),
+        ) {
+            Ok(app) => self.app = Some(app),
+            Err(e) => {
+                self.error = Some(e.context("Unable to start Gargoyle's Quest"));
+                event_loop.exit();
+            }
         }
-        self.gamepad_input = Some(gamepad_input);
     }
 
     fn window_event(
@@ -100,31 +335,148 @@ impl winit::application::ApplicationHandler for WinitApp {
         window_id: winit::window::WindowId,
         event: WindowEvent,
     ) {
-        let app = self.app.as_mut().unwrap();
+        let Some(app) = self.app.as_mut() else {
+            return;
+        };
 
-        while let Some(event) = self.gamepad_input.as_mut().unwrap().next_event() {
-            app.gamepad_input(event);
-        }
+        app.poll_gamepads();
 
         if app.window().id() == window_id {
             app.event(&event, event_loop);
         }
+        if let Some(e) = app.take_error() {
+            self.error = Some(e);
+        }
     }
 
     fn about_to_wait(&mut self, _event_loop: &winit::event_loop::ActiveEventLoop) {
-        let window = self.app.as_ref().unwrap().window();
-        window.request_redraw();
+        if let Some(app) = self.app.as_ref() {
+            app.window().request_redraw();
+        }
     }
 }
 
-async fn run(options: Options) {
-    let event_loop = EventLoop::new().unwrap();
-    let mut app = WinitApp::new(options);
+async fn run(
+    options: Options,
+    settings: settings::Settings,
+    settings_path: Option<PathBuf>,
+    replay: Option<replay::Replay>,
+) -> anyhow::Result<()> {
+    let event_loop = EventLoop::new()?;
+    let mut app = WinitApp::new(options, settings, settings_path, replay);
     let _ = event_loop.run_app(&mut app);
+    match app.error {
+        Some(e) => Err(e),
+        None => Ok(()),
+    }
 }
 
-fn main() {
+/// Reports the problems found in each of the provided maps, failing if there are any
+fn validate(options: &Options, maps: &[PathBuf]) -> anyhow::Result<()> {
+    let maps = if maps.is_empty() {
+        campaign::Campaign::load(&options.campaign)?
+            .levels
+            .into_iter()
+            .map(|level| level.map)
+            .collect()
+    } else {
+        maps.to_vec()
+    };
+
+    let mut tilesets = tileset::TileSetCache::default();
+    let mut num_problems = 0;
+    for map in maps {
+        for problem in validation::validate_map(&map, &mut tilesets) {
+            println!("{}", problem);
+            num_problems += 1;
+        }
+    }
+
+    if num_problems > 0 {
+        anyhow::bail!("Found {} problem(s)", num_problems);
+    }
+    println!("No problems found");
+    Ok(())
+}
+
+/// Packs the asset root's res/ directory into the archive `output`
+fn pack(output: &std::path::Path) -> anyhow::Result<()> {
+    let archive = assets::Archive::pack(assets::locator().root())?;
+    archive.save(output)?;
+    println!("Packed {} files into {:?}", archive.len(), output);
+    Ok(())
+}
+
+/// Moves the preferences a version 1 save recorded to the settings, and saves the settings.
+fn migrate_version_1_preferences(
+    preferences: &save::Version1Preferences,
+    settings: &mut settings::Settings,
+    settings_path: Option<&Path>,
+) -> anyhow::Result<()> {
+    preferences.migrate(settings);
+    settings.save(settings_path.context("No settings file to keep the save file's preferences in")?)
+}
+
+fn main() -> anyhow::Result<()> {
     env_logger::init();
-    let options = Options::from_args();
-    pollster::block_on(run(options));
+    let matches = Options::clap().get_matches();
+    let mut options = Options::from_clap(&matches);
+
+    assets::install(assets::AssetLocator::from_options(&options)?)?;
+
+    // Settings which fail to load are left untouched on disk, rather than overwritten with defaults
+    let (mut settings, settings_path) = match options.settings_path() {
+        Some(path) => match settings::Settings::load(&path) {
+            Ok(settings) => (settings, Some(path)),
+            Err(e) => {
+                log::warn!("{:#}; using default settings", e);
+                (settings::Settings::default(), None)
+            }
+        },
+        None => (settings::Settings::default(), None),
+    };
+
+    match &options.command {
+        Some(Command::Validate { maps }) => return validate(&options, maps),
+        Some(Command::Pack { output }) => return pack(output),
+        Some(Command::Continue) => {
+            let save_path = options.save_path().context(
+                "Unable to locate the user's data directory; specify a save file with --save-file",
+            )?;
+            let mut save = save::SaveGame::load(&save_path)?;
+            // the save is only rewritten without its preferences once they're in the settings file
+            if let Some(preferences) = save.version_1_preferences.take() {
+                match migrate_version_1_preferences(
+                    &preferences,
+                    &mut settings,
+                    settings_path.as_deref(),
+                ) {
+                    Ok(()) => save.save(&save_path)?,
+                    Err(e) => log::warn!("{:#}", e),
+                }
+            }
+            save.apply(&mut options);
+        }
+        None => {}
+    }
+    settings.apply(&mut options, &matches);
+
+    // When playing back a replay, the game runs with the options the replay was recorded with,
+    // and default settings, as the player's window size and zoom would change its viewport.
+    let (options, replay, settings, settings_path) = if let Some(replay_path) = &options.replay {
+        let replay = replay::Replay::load(replay_path)?;
+        let mut replay_options = replay.options()?;
+        replay_options.replay = Some(replay_path.clone());
+        replay_options.seed = replay.seed;
+        (
+            replay_options,
+            Some(replay),
+            settings::Settings::default(),
+            None,
+        )
+    } else {
+        (options, None, settings, settings_path)
+    };
+
+    pollster::block_on(run(options, settings, settings_path, replay))
 }
@@ -1,24 +1,79 @@
 use anyhow::{Context, Result};
 use cgmath::*;
 use sprite::core::*;
-use std::path::Path;
-use std::{collections::HashMap, io::BufReader};
-use std::{fs::File, time::Duration};
-use xml::reader::{EventReader, XmlEvent};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::time::Duration;
+use std::{collections::HashMap, io::Read};
+use xml::{
+    attribute::OwnedAttribute,
+    reader::{EventReader, XmlEvent},
+};
 
 use crate::{
-    collision, entities, entity, sprite, state::constants::sprite_masks::*, tileset, util::*,
+    assets, collision, entities, entity,
+    property::Properties,
+    sprite,
+    state::constants::{layers, sprite_masks::*},
+    tileset,
+    util::*,
 };
 
 // ---------------------------------------------------------------------------------------------------------------------
 
+// https://doc.mapeditor.org/en/stable/reference/tmx-map-format/#tile-flipping
+const FLIPPED_HORIZONTALLY_FLAG: u32 = 0x80000000;
+const FLIPPED_VERTICALLY_FLAG: u32 = 0x40000000;
+const FLIPPED_DIAGONALLY_FLAG: u32 = 0x20000000;
+const FLIPPED_FLAGS: u32 =
+    FLIPPED_HORIZONTALLY_FLAG | FLIPPED_VERTICALLY_FLAG | FLIPPED_DIAGONALLY_FLAG;
+
+// ---------------------------------------------------------------------------------------------------------------------
+
+/// The role a tile layer plays in a stage, read from the layer's "role" property.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LayerRole {
+    /// The layer's tiles are drawn as part of the stage; this is the default.
+    Stage,
+    /// The layer's tiles mark where entities are instantiated.
+    Entities,
+    /// The layer's tiles are drawn by an entity which owns the layer, named by the "entity" property.
+    Entity,
+}
+
+/// A rectangular block of a layer's tiles. Finite maps store each layer as a single chunk
+/// covering the whole map; infinite maps store layers as any number of chunks.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LayerChunk {
+    pub x: i32,      // in tiles
+    pub y: i32,      // in tiles, increasing downwards
+    pub width: u32,  // tiles wide
+    pub height: u32, // tiles tall
+    pub tile_data: Vec<u32>,
+}
+
+impl LayerChunk {
+    fn verify(&self) -> Result<()> {
+        let expected_count = self.width as usize * self.height as usize;
+        if self.tile_data.len() != expected_count {
+            anyhow::bail!(
+                "Expected layer tile_data to have {} entries, but got {}",
+                expected_count,
+                self.tile_data.len()
+            );
+        }
+        Ok(())
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct Layer {
     pub id: i32,
     pub name: String,
     pub width: u32,  // tiles wide
     pub height: u32, // tiles tall
-    pub tile_data: Vec<u32>,
+    pub chunks: Vec<LayerChunk>,
+    pub properties: Properties,
 }
 
 impl Default for Layer {
@@ -28,59 +83,388 @@ impl Default for Layer {
             name: "".to_string(),
             width: 0,
             height: 0,
-            tile_data: vec![],
+            chunks: vec![],
+            properties: Properties::default(),
+        }
+    }
+}
+
+impl Layer {
+    /// Returns the tile extents of the layer's chunks as (min, max) with max exclusive,
+    /// or None if the layer has no chunks.
+    pub fn tile_extents(&self) -> Option<(Point2<i32>, Point2<i32>)> {
+        self.chunks.iter().fold(None, |extents, chunk| {
+            let chunk_min = point2(chunk.x, chunk.y);
+            let chunk_max = point2(chunk.x + chunk.width as i32, chunk.y + chunk.height as i32);
+            Some(match extents {
+                Some((min, max)) => (
+                    point2(min.x.min(chunk_min.x), min.y.min(chunk_min.y)),
+                    point2(max.x.max(chunk_max.x), max.y.max(chunk_max.y)),
+                ),
+                None => (chunk_min, chunk_max),
+            })
+        })
+    }
+
+    /// Returns the named string property, or None if it's unset or not a string
+    pub fn property(&self, named: &str) -> Option<&str> {
+        self.properties.string(named)
+    }
+
+    /// Describes the layer in errors about its properties
+    fn owner(name: &str) -> String {
+        format!("<layer name=\"{}\">", name)
+    }
+
+    /// Returns the layer's role from its "role" property ("stage", "entities" or "entity").
+    /// Layers without a role are stage layers.
+    pub fn role(&self) -> Result<LayerRole> {
+        match self.property("role") {
+            None | Some("stage") => Ok(LayerRole::Stage),
+            Some("entities") => Ok(LayerRole::Entities),
+            Some("entity") => Ok(LayerRole::Entity),
+            Some(role) => {
+                anyhow::bail!("Layer \"{}\" has unrecognized role \"{}\"", self.name, role)
+            }
         }
     }
+
+    /// Returns the z depth of the layer's sprites from its "z_depth" property, which may be a number or the
+    /// name of one of the `layers::stage` depths, e.g., "background".
+    pub fn z_depth(&self) -> Result<f32> {
+        match self.property("z_depth").and_then(layers::stage::named) {
+            Some(z_depth) => Ok(z_depth),
+            None => self
+                .properties
+                .float("z_depth")
+                .context("Expected z_depth to be a number or a named depth"),
+        }
+    }
+
+    /// True if the layer's sprites participate in collision detection ("collision" property)
+    pub fn collision(&self) -> bool {
+        self.properties.flag("collision")
+    }
+
+    /// True if the layer's animated tiles should be played as flipbook animations ("animated" property)
+    pub fn animated(&self) -> bool {
+        self.properties.flag("animated")
+    }
+
+    /// The class name of the entity which owns this layer's sprites ("entity" property)
+    pub fn entity_class_name(&self) -> Result<&str> {
+        self.property("entity").with_context(|| {
+            format!(
+                "Layer \"{}\" has role \"entity\" but no \"entity\" property",
+                self.name
+            )
+        })
+    }
 }
 
 // ---------------------------------------------------------------------------------------------------------------------
 
-#[derive(Clone, Debug)]
-pub struct ObjectGroupProperty {
-    pub name: String,
-    pub value: String,
+/// Compression applied to base64 encoded layer data
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum DataCompression {
+    None,
+    Zlib,
+    Gzip,
+    Zstd,
 }
 
-impl ObjectGroupProperty {
-    fn new(name: &str, value: &str) -> Self {
-        Self {
-            name: name.trim().to_owned(),
-            value: value.trim().to_owned(),
+/// The encoding of a tile layer's <data> block, from its 'encoding' and 'compression' attributes
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum DataEncoding {
+    Csv,
+    Base64(DataCompression),
+}
+
+impl DataEncoding {
+    fn new(encoding: Option<&str>, compression: Option<&str>) -> Result<Self> {
+        match (encoding, compression) {
+            (Some("csv"), None) => Ok(DataEncoding::Csv),
+            (Some("base64"), None) => Ok(DataEncoding::Base64(DataCompression::None)),
+            (Some("base64"), Some("zlib")) => Ok(DataEncoding::Base64(DataCompression::Zlib)),
+            (Some("base64"), Some("gzip")) => Ok(DataEncoding::Base64(DataCompression::Gzip)),
+            (Some("base64"), Some("zstd")) => Ok(DataEncoding::Base64(DataCompression::Zstd)),
+            (Some(encoding), compression) => anyhow::bail!(
+                "Unsupported <data> encoding '{}' with compression '{}'",
+                encoding,
+                compression.unwrap_or("none")
+            ),
+            (None, _) => anyhow::bail!(
+                "<data> blocks with XML <tile> elements are unsupported; use csv or base64 encoding"
+            ),
         }
     }
+
+    /// Decodes the text of a <data> block to a vec of tile GIDs
+    fn decode(&self, data: &str) -> Result<Vec<u32>> {
+        match self {
+            DataEncoding::Csv => data
+                .split(|c: char| c == ',' || c.is_whitespace())
+                .filter(|index| !index.is_empty())
+                .map(|index| {
+                    index
+                        .parse::<u32>()
+                        .with_context(|| format!("Expected to parse '{}' to u32", index))
+                })
+                .collect(),
+            DataEncoding::Base64(compression) => {
+                use base64::Engine;
+                let bytes = base64::engine::general_purpose::STANDARD
+                    .decode(data.trim())
+                    .context("Expected <data> to be valid base64")?;
+                let bytes = decompress(*compression, &bytes)?;
+                if bytes.len() % 4 != 0 {
+                    anyhow::bail!(
+                        "Expected decoded <data> length to be a multiple of 4, got {}",
+                        bytes.len()
+                    );
+                }
+                Ok(bytes
+                    .chunks_exact(4)
+                    .map(|gid| u32::from_le_bytes([gid[0], gid[1], gid[2], gid[3]]))
+                    .collect())
+            }
+        }
+    }
+}
+
+fn decompress(compression: DataCompression, bytes: &[u8]) -> Result<Vec<u8>> {
+    let mut decompressed = vec![];
+    match compression {
+        DataCompression::None => return Ok(bytes.to_vec()),
+        DataCompression::Zlib => flate2::read::ZlibDecoder::new(bytes)
+            .read_to_end(&mut decompressed)
+            .context("Unable to decompress zlib <data>")?,
+        DataCompression::Gzip => flate2::read::GzDecoder::new(bytes)
+            .read_to_end(&mut decompressed)
+            .context("Unable to decompress gzip <data>")?,
+        DataCompression::Zstd => ruzstd::decoding::StreamingDecoder::new(bytes)
+            .context("Unable to read zstd <data>")?
+            .read_to_end(&mut decompressed)
+            .context("Unable to decompress zstd <data>")?,
+    };
+    Ok(decompressed)
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+
+/// The shape of an object in an object group. Points are in pixels, relative to the object's position.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ObjectShape {
+    Rectangle,
+    Ellipse,
+    Point,
+    Polygon(Vec<Point2<f32>>),
+    Polyline(Vec<Point2<f32>>),
 }
 
 #[derive(Clone, Debug)]
 pub struct ObjectGroupObject {
     pub id: i32,
-    pub x: i32,
-    pub y: i32,
+    pub name: String,
+    /// The object's Tiled type (or class)
+    pub class: String,
+    pub x: i32, // in pixels, rounded
+    pub y: i32, // in pixels, rounded, increasing downwards
     pub width: u32,
     pub height: u32,
-    pub properties: Vec<ObjectGroupProperty>,
+    pub shape: ObjectShape,
+    /// The gid, including flip flags, of a tile object's tile
+    pub gid: Option<u32>,
+    pub properties: Properties,
 }
 
 impl Default for ObjectGroupObject {
     fn default() -> Self {
         Self {
             id: -1,
+            name: "".to_owned(),
+            class: "".to_owned(),
             x: 0,
             y: 0,
             width: 0,
             height: 0,
-            properties: vec![],
+            shape: ObjectShape::Rectangle,
+            gid: None,
+            properties: Properties::default(),
         }
     }
 }
 
 impl ObjectGroupObject {
+    /// Returns the named string property, or None if it's unset or not a string
     pub fn property(&self, named: &str) -> Option<&str> {
-        for prop in self.properties.iter() {
-            if prop.name == named {
-                return Some(&prop.value);
+        self.properties.string(named)
+    }
+
+    /// Parses an <object> element with the provided attributes, consuming `events` through its closing
+    /// </object>. An object referencing a template starts as a copy of the template's object, which
+    /// the element's attributes, shape and properties override.
+    fn parse<I>(
+        attributes: Vec<OwnedAttribute>,
+        events: &mut I,
+        templates: &mut ObjectTemplates,
+    ) -> Result<Self>
+    where
+        I: Iterator<Item = xml::reader::Result<XmlEvent>>,
+    {
+        let mut object = match attributes
+            .iter()
+            .find(|attr| attr.name.local_name == "template")
+        {
+            Some(template) => templates.load(&template.value)?,
+            None => ObjectGroupObject::default(),
+        };
+
+        // Tiled writes fractional positions and sizes for objects not snapped to the grid
+        fn pixels(value: &str, attr: &str) -> Result<f32> {
+            value.parse::<f32>().with_context(|| {
+                format!("Expected to parse '{}' field of <object> to a number", attr)
+            })
+        }
+
+        for attr in attributes {
+            match attr.name.local_name.as_str() {
+                "id" => {
+                    object.id = attr
+                        .value
+                        .parse()
+                        .context("Expected to parse 'id' field of <object> to i32")?
+                }
+                "name" => object.name = attr.value,
+                "type" | "class" => object.class = attr.value,
+                "x" => object.x = pixels(&attr.value, "x")?.round() as i32,
+                "y" => object.y = pixels(&attr.value, "y")?.round() as i32,
+                "width" => object.width = pixels(&attr.value, "width")?.round() as u32,
+                "height" => object.height = pixels(&attr.value, "height")?.round() as u32,
+                "gid" => {
+                    object.gid = Some(
+                        attr.value
+                            .parse()
+                            .context("Expected to parse 'gid' field of <object> to u32")?,
+                    )
+                }
+                _ => {}
+            }
+        }
+
+        let owner = if object.id == -1 {
+            "<object>".to_string()
+        } else {
+            format!("<object id=\"{}\">", object.id)
+        };
+        let mut properties = Properties::new(&owner);
+        properties.extend(std::mem::take(&mut object.properties));
+
+        while let Some(e) = events.next() {
+            match e {
+                Ok(XmlEvent::StartElement {
+                    name, attributes, ..
+                }) => match name.local_name.as_str() {
+                    "properties" => properties.extend(Properties::parse(&owner, events)?),
+                    "ellipse" => object.shape = ObjectShape::Ellipse,
+                    "point" => object.shape = ObjectShape::Point,
+                    "polygon" => {
+                        object.shape = ObjectShape::Polygon(
+                            parse_points(&attributes)
+                                .with_context(|| format!("Invalid <polygon> of {}", owner))?,
+                        )
+                    }
+                    "polyline" => {
+                        object.shape = ObjectShape::Polyline(
+                            parse_points(&attributes)
+                                .with_context(|| format!("Invalid <polyline> of {}", owner))?,
+                        )
+                    }
+                    _ => {}
+                },
+                Ok(XmlEvent::EndElement { name }) if name.local_name.as_str() == "object" => {
+                    break;
+                }
+                Err(_) => {}
+                _ => {}
             }
         }
-        None
+
+        object.properties = properties;
+        Ok(object)
+    }
+}
+
+/// Parses the 'points' attribute of a <polygon> or <polyline>, e.g., "0,0 16,0 16,16"
+fn parse_points(attributes: &[OwnedAttribute]) -> Result<Vec<Point2<f32>>> {
+    let points = attributes
+        .iter()
+        .find(|attr| attr.name.local_name == "points")
+        .context("Expected a 'points' attribute")?;
+    points
+        .value
+        .split_whitespace()
+        .map(|point| {
+            let (x, y) = point
+                .split_once(',')
+                .with_context(|| format!("Expected a point like \"x,y\", got \"{}\"", point))?;
+            Ok(point2(
+                x.parse()
+                    .with_context(|| format!("Expected to parse \"{}\" to f32", x))?,
+                y.parse()
+                    .with_context(|| format!("Expected to parse \"{}\" to f32", y))?,
+            ))
+        })
+        .collect()
+}
+
+/// Loads the object templates (.tx files) referenced by a map's objects, each once.
+struct ObjectTemplates {
+    // template paths are relative to the map
+    dir: PathBuf,
+    templates: HashMap<PathBuf, ObjectGroupObject>,
+}
+
+impl ObjectTemplates {
+    fn new(dir: &Path) -> Self {
+        Self {
+            dir: dir.to_path_buf(),
+            templates: HashMap::new(),
+        }
+    }
+
+    fn load(&mut self, source: &str) -> Result<ObjectGroupObject> {
+        let path = self.dir.join(source);
+        if let Some(template) = self.templates.get(&path) {
+            return Ok(template.clone());
+        }
+
+        let template = Self::read(&path)
+            .with_context(|| format!("Unable to load object template {}", path.display()))?;
+        self.templates.insert(path, template.clone());
+        Ok(template)
+    }
+
+    fn read(path: &Path) -> Result<ObjectGroupObject> {
+        let file = assets::open(path)?;
+        let mut events = EventReader::new(file).into_iter();
+        while let Some(e) = events.next() {
+            if let Ok(XmlEvent::StartElement {
+                name, attributes, ..
+            }) = e
+            {
+                if name.local_name == "object" {
+                    // a template's own template references are relative to it
+                    let dir = path.parent().unwrap_or_else(|| Path::new(""));
+                    return ObjectGroupObject::parse(
+                        attributes,
+                        &mut events,
+                        &mut ObjectTemplates::new(dir),
+                    );
+                }
+            }
+        }
+        anyhow::bail!("Expected an <object> element in template")
     }
 }
 
@@ -89,6 +473,7 @@ pub struct ObjectGroup {
     pub id: i32,
     pub name: String,
     pub objects: Vec<ObjectGroupObject>,
+    pub properties: Properties,
 }
 
 impl Default for ObjectGroup {
@@ -97,6 +482,7 @@ impl Default for ObjectGroup {
             id: -1,
             name: "".to_owned(),
             objects: vec![],
+            properties: Properties::default(),
         }
     }
 }
@@ -117,91 +503,100 @@ pub struct SpriteFlipbookAnimation {
 }
 
 impl SpriteFlipbookAnimation {
+    /// Builds the animation from `sequence`, tiles which must each have "animation_frame" and
+    /// "animation_duration" properties.
     fn new(
         name: &str,
         sprite: Sprite,
         sequence: Vec<&tileset::Tile>,
         tileset: &tileset::TileSet,
-    ) -> Self {
+    ) -> Result<Self> {
         let mut offsets = vec![];
         let mut durations = vec![];
 
         // ensure our frame sequence is in order by "animation_frame" property
-        let mut sequence = sequence.to_vec();
-        sequence.sort_by(|a, b| {
-            let a_frame = a.get_property("animation_frame").expect(
-                "Tiles passed to SpriteAnimationSequence must have \"animation_frame\" property",
-            ).parse::<i32>()
-            .expect("Expect \"animation_frame\" to parse to i32");
-
-            let b_frame = b.get_property("animation_frame").expect(
-                "Tiles passed to SpriteAnimationSequence must have \"animation_frame\" property",
-            ).parse::<i32>()
-            .expect("Expect \"animation_frame\" to parse to i32");
-
-            a_frame.partial_cmp(&b_frame).unwrap()
-        });
-
-        let first_tile = sequence
+        let mut sequence = sequence
+            .into_iter()
+            .map(|tile| Ok((tile.properties().int("animation_frame")?, tile)))
+            .collect::<Result<Vec<_>>>()?;
+        sequence.sort_by_key(|(frame, _)| *frame);
+
+        let (_, first_tile) = sequence
             .first()
             .expect("Animation sequence must not be empty");
         let first_tile_tex_coords = tileset.get_tex_coords_for_tile(first_tile);
 
-        for tile in sequence {
+        for (_, tile) in sequence {
             let tex_coords = tileset.get_tex_coords_for_tile(tile);
             offsets.push(tex_coords.origin - first_tile_tex_coords.origin);
 
-            let duration = tile.get_property("animation_duration").expect("Tiles passed to SpriteAnimationSequence must have \"animation_duration\" property")
-                .parse::<f32>()
-                .expect("Expect \"animation_duration\" to parse as f32");
+            let duration = tile.properties().float("animation_duration")?;
             durations.push(Duration::from_secs_f32(duration));
         }
 
-        Self {
+        Ok(Self {
             name: name.to_string(),
             sprites: vec![sprite],
             offsets,
             durations,
-        }
+        })
     }
 }
 
 // ---------------------------------------------------------------------------------------------------------------------
 
+/// A tileset referenced by a map, and the first global tile id (GID) its tiles are numbered from
+#[derive(Debug)]
+pub struct MapTileSet {
+    pub first_gid: u32,
+    pub tileset: Rc<tileset::TileSet>,
+    /// Path to the tileset's spritesheet, resolved relative to the tsx file
+    pub image_path: PathBuf,
+}
+
 #[derive(Debug)]
 pub struct Map {
-    pub tileset: tileset::TileSet,
-    tileset_first_gid: u32,
+    /// The map's tilesets, ordered by first_gid
+    pub tilesets: Vec<MapTileSet>,
+    // tile coordinate of the map's top-left corner; non-zero only for infinite maps
+    tile_origin: Point2<i32>,
     pub width: u32,
     pub height: u32,
     tile_width: u32,
     tile_height: u32,
     pub layers: Vec<Layer>,
     pub object_groups: Vec<ObjectGroup>,
+    pub properties: Properties,
 }
 
 impl Map {
-    pub fn new_tmx(tmx_file: &Path) -> Result<Self> {
+    /// Loads the tmx map at `tmx_file`, drawing external tilesets it references from `tilesets_cache`.
+    pub fn new_tmx(tmx_file: &Path, tilesets_cache: &mut tileset::TileSetCache) -> Result<Self> {
         let parent_dir = tmx_file
             .parent()
             .context("Expect tmx_file to have parent dir")?;
-        let file = File::open(tmx_file)
+        let file = assets::open(tmx_file)
             .with_context(|| format!("Unable to open {}", tmx_file.display()))?;
-        let file = BufReader::new(file);
         let parser = EventReader::new(file);
 
-        let mut tileset: Option<tileset::TileSet> = None;
-        let mut tileset_first_gid: Option<u32> = None;
+        let mut tilesets: Vec<MapTileSet> = vec![];
         let mut width: Option<u32> = None;
         let mut height: Option<u32> = None;
         let mut tile_width: Option<u32> = None;
         let mut tile_height: Option<u32> = None;
         let mut layers: Vec<Layer> = vec![];
         let mut current_layer: Option<Layer> = None;
-        let mut handle_current_layer_data = false;
+        let mut current_layer_data: Option<(DataEncoding, String)> = None;
+        let mut current_chunk: Option<LayerChunk> = None;
+        let mut infinite = false;
         let mut object_groups: Vec<ObjectGroup> = vec![];
+        let mut properties = Properties::new("<map>");
+        // true while reading an <objectgroup>, for assigning <properties> to it
+        let mut in_object_group = false;
+        let mut templates = ObjectTemplates::new(parent_dir);
 
-        for e in parser {
+        let mut events = parser.into_iter();
+        while let Some(e) = events.next() {
             match e {
                 Ok(XmlEvent::StartElement {
                     name, attributes, ..
@@ -233,6 +628,7 @@ impl Map {
                                             "Expected to parse 'tileheight' attr of <map> to u32.",
                                         )?)
                                     }
+                                    "infinite" => infinite = attr.value == "1",
                                     _ => {}
                                 }
                             }
@@ -242,30 +638,50 @@ impl Map {
                         // Handle the <tileset> block
                         //
                         "tileset" => {
-                            for attr in attributes {
+                            let mut source: Option<String> = None;
+                            let mut first_gid: Option<u32> = None;
+                            for attr in &attributes {
                                 match attr.name.local_name.as_str() {
-                                    "source" => {
-                                        let tileset_path = parent_dir.join(attr.value);
-                                        tileset = Some(
-                                            tileset::TileSet::new_tsx(&tileset_path).with_context(
-                                                || {
-                                                    format!(
-                                                    "Expected to load referenced <tileset> from {}",
-                                                    tileset_path.display()
-                                                )
-                                                },
-                                            )?,
-                                        );
-                                    }
+                                    "source" => source = Some(attr.value.clone()),
                                     "firstgid" => {
-                                        tileset_first_gid = Some(attr.value.parse().context(
+                                        first_gid = Some(attr.value.parse().context(
                                             "Expected to parse <tileset> 'firstgid' to u32",
                                         )?);
                                     }
                                     _ => {}
                                 }
-                                if attr.name.local_name == "source" {}
                             }
+
+                            let first_gid = first_gid
+                                .context("Expected to read 'firstgid' attr on <tileset> block")?;
+
+                            // tilesets are either external tsx files, or embedded in the tmx;
+                            // their image paths are relative to the file defining them
+                            let (tileset, tileset_dir) = if let Some(source) = source {
+                                let tileset_path = parent_dir.join(source);
+                                let tileset =
+                                    tilesets_cache.load(&tileset_path).with_context(|| {
+                                        format!(
+                                            "Expected to load referenced <tileset> from {}",
+                                            tileset_path.display()
+                                        )
+                                    })?;
+                                let tileset_dir = tileset_path
+                                    .parent()
+                                    .unwrap_or_else(|| Path::new(""))
+                                    .to_path_buf();
+                                (tileset, tileset_dir)
+                            } else {
+                                let tileset = tileset::TileSet::parse(attributes, &mut events)
+                                    .context("Expected to parse embedded <tileset>")?;
+                                (Rc::new(tileset), parent_dir.to_path_buf())
+                            };
+                            let image_path = tileset_dir.join(&tileset.image_path);
+                            tilesets.push(MapTileSet {
+                                first_gid,
+                                tileset,
+                                image_path,
+                            });
                         }
 
                         //
@@ -300,10 +716,11 @@ impl Map {
                             if layer.id == -1 {
                                 anyhow::bail!("<layer> element missing an 'id' attribute.");
                             }
-                            if layer.width == 0 {
+                            // infinite maps' layers may have zero width/height; their extent comes from chunks
+                            if layer.width == 0 && !infinite {
                                 anyhow::bail!("<layer> element missing a 'width' attribute.");
                             }
-                            if layer.height == 0 {
+                            if layer.height == 0 && !infinite {
                                 anyhow::bail!("<layer> element missing a 'height' attribute.");
                             }
                             current_layer = Some(layer);
@@ -313,15 +730,62 @@ impl Map {
                         // Handle the <data> block - requires that current_layer is Some
                         //
                         "data" => {
-                            handle_current_layer_data = false;
+                            let mut encoding: Option<String> = None;
+                            let mut compression: Option<String> = None;
                             for attr in attributes {
-                                if attr.name.local_name == "encoding" && attr.value == "csv" {
-                                    handle_current_layer_data = true;
+                                match attr.name.local_name.as_str() {
+                                    "encoding" => encoding = Some(attr.value),
+                                    "compression" => compression = Some(attr.value),
+                                    _ => {}
                                 }
                             }
-                            if !handle_current_layer_data {
-                                anyhow::bail!("Only supported encoding for <data> block is 'csv'");
+                            let encoding =
+                                DataEncoding::new(encoding.as_deref(), compression.as_deref())?;
+                            current_layer_data = Some((encoding, String::new()));
+                        }
+
+                        //
+                        // Handle <chunk> blocks of infinite maps' <data> - requires that current_layer_data is Some
+                        //
+                        "chunk" => {
+                            let (_, data) = current_layer_data
+                                .as_mut()
+                                .context("Expect <chunk> to be nested in <data>")?;
+                            data.clear();
+
+                            let mut chunk = LayerChunk {
+                                x: 0,
+                                y: 0,
+                                width: 0,
+                                height: 0,
+                                tile_data: vec![],
+                            };
+                            for attr in attributes {
+                                match attr.name.local_name.as_str() {
+                                    "x" => {
+                                        chunk.x = attr.value.parse().context(
+                                            "Expected to parse 'x' field of <chunk> to i32",
+                                        )?
+                                    }
+                                    "y" => {
+                                        chunk.y = attr.value.parse().context(
+                                            "Expected to parse 'y' field of <chunk> to i32",
+                                        )?
+                                    }
+                                    "width" => {
+                                        chunk.width = attr.value.parse().context(
+                                            "Expected to parse 'width' field of <chunk> to u32",
+                                        )?
+                                    }
+                                    "height" => {
+                                        chunk.height = attr.value.parse().context(
+                                            "Expected to parse 'height' field of <chunk> to u32",
+                                        )?
+                                    }
+                                    _ => {}
+                                }
                             }
+                            current_chunk = Some(chunk);
                         }
 
                         //
@@ -349,50 +813,16 @@ impl Map {
                             }
 
                             object_groups.push(object_group);
+                            in_object_group = true;
                         }
 
                         "object" => {
-                            let mut object = ObjectGroupObject::default();
-                            for attr in attributes {
-                                match attr.name.local_name.as_str() {
-                                    "id" => {
-                                        object.id = attr.value.parse().context(
-                                            "Expected to parse 'id' field of <object> to i32",
-                                        )?
-                                    }
-                                    "x" => {
-                                        object.x = attr.value.parse().context(
-                                            "Expected to parse 'x' field of <object> to i32",
-                                        )?
-                                    }
-                                    "y" => {
-                                        object.y = attr.value.parse().context(
-                                            "Expected to parse 'y' field of <object> to i32",
-                                        )?
-                                    }
-                                    "width" => {
-                                        object.width = attr.value.parse().context(
-                                            "Expected to parse 'width' field of <object> to u32",
-                                        )?
-                                    }
-                                    "height" => {
-                                        object.height = attr.value.parse().context(
-                                            "Expected to parse 'height' field of <object> to u32",
-                                        )?
-                                    }
-                                    _ => {}
-                                }
-                            }
+                            let object =
+                                ObjectGroupObject::parse(attributes, &mut events, &mut templates)?;
                             // verify required fields
                             if object.id == -1 {
                                 anyhow::bail!("<object> element missing an 'id' attribute");
                             }
-                            if object.width == 0 {
-                                anyhow::bail!("<object> element missing a 'width' attribute");
-                            }
-                            if object.height == 0 {
-                                anyhow::bail!("<object> element missing a 'height' attribute");
-                            }
 
                             object_groups
                                 .last_mut()
@@ -401,78 +831,85 @@ impl Map {
                                 .push(object);
                         }
 
-                        "property" => {
-                            let mut name: Option<String> = None;
-                            let mut value: Option<String> = None;
-
-                            for attr in attributes {
-                                match attr.name.local_name.as_str() {
-                                    "name" => {
-                                        name = Some(attr.value);
-                                    }
-                                    "value" => {
-                                        value = Some(attr.value);
-                                    }
-                                    _ => {}
-                                }
+                        //
+                        // Handle <properties> - of the innermost element which may have them
+                        //
+                        "properties" => {
+                            if let Some(layer) = &mut current_layer {
+                                layer.properties =
+                                    Properties::parse(&Layer::owner(&layer.name), &mut events)?;
+                            } else if in_object_group {
+                                let object_group = object_groups
+                                    .last_mut()
+                                    .context("Expect to be reading an <objectgroup>")?;
+                                object_group.properties = Properties::parse(
+                                    &format!("<objectgroup name=\"{}\">", object_group.name),
+                                    &mut events,
+                                )?;
+                            } else {
+                                properties = Properties::parse("<map>", &mut events)?;
                             }
-
-                            let property = ObjectGroupProperty::new(
-                                &name.context(
-                                    "Expect <property> element to have a 'name' attribute",
-                                )?,
-                                &value.context(
-                                    "Expect <property> element to have a 'value' attribute",
-                                )?,
-                            );
-
-                            object_groups
-                                .last_mut()
-                                .context("Expect <object> to be nested in <objectgroup>")?
-                                .objects
-                                .last_mut()
-                                .context(
-                                    "Expect <property> element to be nested in an <object> element",
-                                )?
-                                .properties
-                                .push(property);
                         }
 
                         _ => {}
                     }
                 }
-                Ok(XmlEvent::Characters(characters)) if handle_current_layer_data => {
-                    if let Some(layer) = &mut current_layer {
-                        for line in characters.split_whitespace() {
-                            for index in line.split(',') {
-                                let index = index.trim();
-                                if !index.is_empty() {
-                                    let index = index.parse::<u32>().with_context(|| {
-                                        format!("Expected to parse '{}' to u32", index)
-                                    })?;
-                                    layer.tile_data.push(index);
-                                }
-                            }
-                        }
-                    } else {
-                        anyhow::bail!(
-                            "Entered a <data> character section without having an active current_layer."
-                        );
+                Ok(XmlEvent::Characters(characters)) if current_layer_data.is_some() => {
+                    if let Some((_, data)) = &mut current_layer_data {
+                        data.push_str(&characters);
                     }
                 }
-                Ok(XmlEvent::EndElement { name }) => {
-                    if name.local_name.as_str() == "layer" {
-                        let layer = current_layer.take().context("Expected current_layer to have been populated when finishing <layer> block.")?;
-                        let expected_count = layer.width as usize * layer.height as usize;
-                        if layer.tile_data.len() != expected_count {
-                            anyhow::bail!(
-                                "Expected layer tile_data to have {} entries, but got {}",
-                                expected_count,
-                                layer.tile_data.len()
-                            );
-                        }
-                        layers.push(layer);
+                Ok(XmlEvent::EndElement { name }) if name.local_name.as_str() == "objectgroup" => {
+                    in_object_group = false;
+                }
+                Ok(XmlEvent::EndElement { name }) if name.local_name.as_str() == "chunk" => {
+                    let mut chunk = current_chunk
+                        .take()
+                        .context("Expected current_chunk to have been populated when finishing <chunk> block.")?;
+                    let (encoding, data) = current_layer_data
+                        .as_mut()
+                        .context("Expect <chunk> to be nested in <data>")?;
+                    let layer = current_layer.as_mut().context(
+                        "Entered a <chunk> section without having an active current_layer.",
+                    )?;
+                    chunk.tile_data = encoding.decode(data).with_context(|| {
+                        format!(
+                            "Unable to decode <chunk> at ({}, {}) of layer \"{}\"",
+                            chunk.x, chunk.y, layer.name
+                        )
+                    })?;
+                    data.clear();
+                    layer.chunks.push(chunk);
+                }
+                Ok(XmlEvent::EndElement { name }) if name.local_name.as_str() == "data" => {
+                    let (encoding, data) = current_layer_data
+                        .take()
+                        .context("Expected <data> block to have an encoding")?;
+                    let layer = current_layer.as_mut().context(
+                        "Entered a <data> character section without having an active current_layer.",
+                    )?;
+                    // finite maps store a layer's tiles directly in <data>, as one chunk covering the map
+                    if !infinite {
+                        let tile_data = encoding.decode(&data).with_context(|| {
+                            format!("Unable to decode <data> of layer \"{}\"", layer.name)
+                        })?;
+                        layer.chunks.push(LayerChunk {
+                            x: 0,
+                            y: 0,
+                            width: layer.width,
+                            height: layer.height,
+                            tile_data,
+                        });
+                    }
+                }
+                Ok(XmlEvent::EndElement { name }) if name.local_name.as_str() == "layer" => {
+                    let layer = current_layer.take().context("Expected current_layer to have been populated when finishing <layer> block.")?;
+                    for chunk in &layer.chunks {
+                        chunk.verify().with_context(|| {
+                            format!("Invalid tile data in layer \"{}\"", layer.name)
+                        })?;
                     }
+                    layers.push(layer);
                 }
                 Err(_) => {}
                 _ => {}
@@ -480,23 +917,42 @@ impl Map {
         }
 
         // verify all required fields were loaded
-        let tileset = tileset.context("Expected to read <tileset> from tmx file.")?;
-        let tileset_first_gid =
-            tileset_first_gid.context("Expected to read 'firstgid' attr on <tileset> block")?;
+        if tilesets.is_empty() {
+            anyhow::bail!("Expected to read <tileset> from tmx file.");
+        }
+        tilesets.sort_by_key(|tileset| tileset.first_gid);
         let width = width.context("Expected to read width attribute on <map>")?;
         let height = height.context("Expected to read height attribute on <map>")?;
         let tile_width = tile_width.context("Expected to read tile_width attribute on <map>")?;
         let tile_height = tile_height.context("Expected to read tile_height attribute on <map>")?;
 
+        // infinite maps' extents are those of the union of their layers' chunks
+        let (tile_origin, width, height) = if infinite {
+            let (min, max) = layers
+                .iter()
+                .filter_map(|layer| layer.tile_extents())
+                .reduce(|(a_min, a_max), (b_min, b_max)| {
+                    (
+                        point2(a_min.x.min(b_min.x), a_min.y.min(b_min.y)),
+                        point2(a_max.x.max(b_max.x), a_max.y.max(b_max.y)),
+                    )
+                })
+                .context("Expected infinite map to have at least one <chunk>")?;
+            (min, (max.x - min.x) as u32, (max.y - min.y) as u32)
+        } else {
+            (point2(0, 0), width, height)
+        };
+
         Ok(Map {
-            tileset,
-            tileset_first_gid,
+            tilesets,
+            tile_origin,
             width,
             height,
             tile_width,
             tile_height,
             layers,
             object_groups,
+            properties,
         })
     }
 
@@ -508,6 +964,30 @@ impl Map {
         )
     }
 
+    /// Returns the size of the map's tiles in pixels
+    pub fn tile_size(&self) -> Vector2<u32> {
+        vec2(self.tile_width, self.tile_height)
+    }
+
+    /// Resolves a global tile id (with flip flags cleared) to the index of the tileset it belongs to,
+    /// and the tile. Returns None for empty (zero) gids, or gids not in any of the map's tilesets.
+    pub fn tile_for_gid(&self, gid: u32) -> Option<(usize, &tileset::Tile)> {
+        if gid == 0 {
+            return None;
+        }
+        let index = self
+            .tilesets
+            .iter()
+            .rposition(|tileset| tileset.first_gid <= gid)?;
+        let map_tileset = &self.tilesets[index];
+        let id = gid - map_tileset.first_gid;
+        if id < map_tileset.tileset.tile_count {
+            map_tileset.tileset.get_tile(id).map(|tile| (index, tile))
+        } else {
+            None
+        }
+    }
+
     /// Returns the layer by the provided name, or None if not found
     pub fn layer_named(&self, name: &str) -> Option<&Layer> {
         self.layers.iter().find(|layer| layer.name == name)
@@ -520,38 +1000,45 @@ impl Map {
             .find(|object_group| object_group.name == name)
     }
 
-    /// Finds the ObjectGroup object in the provided layer occupying the same position and extent on
-    /// the map as the provided sprite.
-    pub fn object_group_properties_for_sprite(
-        &self,
-        sprite: &Sprite,
-        object_group_layer: &str,
-    ) -> Option<&ObjectGroupObject> {
-        if let Some(object_group) = self.object_group_named(object_group_layer) {
-            let x = sprite.origin.x.floor() as i32 * self.tile_width as i32;
-            let y = (self.height * self.tile_height) as i32
-                - (sprite.origin.y.floor() as i32 * self.tile_height as i32);
-            let width = sprite.extent.x.floor() as u32 * self.tile_width;
-            let height = sprite.extent.y.floor() as u32 * self.tile_height;
-
-            for obj in object_group.objects.iter() {
-                if obj.x == x && obj.y == y && obj.width == width && obj.height == height {
-                    return Some(obj);
-                }
-            }
+    /// Returns the tile of a tile object, or None if the object isn't a tile object
+    pub fn object_tile(&self, object: &ObjectGroupObject) -> Option<&tileset::Tile> {
+        let (_, tile) = self.tile_for_gid(object.gid? & !FLIPPED_FLAGS)?;
+        Some(tile)
+    }
 
-            None
-        } else {
-            None
-        }
+    /// Returns the tiles of the provided layer with their positions in the map's tile coordinates,
+    /// as displayed by Tiled (y increasing downwards).
+    pub fn tiles<'a>(
+        &'a self,
+        layer: &'a Layer,
+    ) -> impl Iterator<Item = (Point2<i32>, &'a tileset::Tile)> + 'a {
+        layer.chunks.iter().flat_map(move |chunk| {
+            chunk
+                .tile_data
+                .iter()
+                .enumerate()
+                .filter_map(move |(index, gid)| {
+                    let (_, tile) = self.tile_for_gid(gid & !FLIPPED_FLAGS)?;
+                    let x = chunk.x + (index as u32 % chunk.width) as i32;
+                    let y = chunk.y + (index as u32 / chunk.width) as i32;
+                    Some((point2(x, y), tile))
+                })
+        })
     }
 
-    /// Returns a vector of all animated sprite names
-    pub fn generate_animations<Z>(&self, layer: &Layer, z_depth: Z) -> Vec<SpriteFlipbookAnimation>
+    /// Returns a vector of all animated sprite names, or an error if an animation's tiles are
+    /// missing their frame or duration properties
+    pub fn generate_animations<Z>(
+        &self,
+        layer: &Layer,
+        z_depth: Z,
+    ) -> Result<Vec<SpriteFlipbookAnimation>>
     where
         Z: Fn(&Sprite, &tileset::Tile) -> f32,
     {
-        let mut animations_by_name: HashMap<String, SpriteFlipbookAnimation> = HashMap::new();
+        let mut animations_by_name: HashMap<(usize, String), SpriteFlipbookAnimation> =
+            HashMap::new();
+        let mut error = None;
 
         self.generate(
             layer,
@@ -560,35 +1047,48 @@ impl Map {
             |sprite, tile| {
                 if sprite.mask & ENTITY == 0 {
                     if let Some(animation_name) = tile.get_property("animation") {
-                        if !animations_by_name.contains_key(animation_name) {
+                        // animation names are scoped to the tileset defining them
+                        let key = (sprite.tileset, animation_name.to_string());
+                        if let Some(animation) = animations_by_name.get_mut(&key) {
+                            animation.sprites.push(*sprite);
+                        } else {
                             // only generate the animation once, because all sprites with this animation name will
                             // share the same animation sequence
-                            let animation_sequence = self
-                                .tileset
-                                .get_tiles_with_property("animation", animation_name);
-
-                            animations_by_name.insert(
-                                animation_name.to_string(),
-                                SpriteFlipbookAnimation::new(
-                                    animation_name,
-                                    *sprite,
-                                    animation_sequence,
-                                    &self.tileset,
-                                ),
-                            );
-                        } else if let Some(animation) = animations_by_name.get_mut(animation_name) {
-                            animation.sprites.push(*sprite);
+                            let tileset = &self.tilesets[sprite.tileset].tileset;
+                            let animation_sequence =
+                                tileset.get_tiles_with_property("animation", animation_name);
+
+                            match SpriteFlipbookAnimation::new(
+                                animation_name,
+                                *sprite,
+                                animation_sequence,
+                                tileset,
+                            ) {
+                                Ok(animation) => {
+                                    animations_by_name.insert(key, animation);
+                                }
+                                Err(e) => {
+                                    error.get_or_insert(e.context(format!(
+                                        "Invalid animation \"{}\"",
+                                        animation_name
+                                    )));
+                                }
+                            }
                         }
                     }
                 }
             },
         );
 
+        if let Some(error) = error {
+            return Err(error);
+        }
+
         let mut animations: Vec<SpriteFlipbookAnimation> = vec![];
         for v in animations_by_name.values() {
             animations.push(v.clone());
         }
-        animations
+        Ok(animations)
     }
 
     /// Generates a vector of Sprite for the contents of the specified layer
@@ -618,11 +1118,12 @@ impl Map {
         collision_space: &mut collision::Space,
         entity_id_vendor: &mut entity::IdVendor,
         z_depth: Z,
-    ) -> Vec<Box<dyn entity::Entity>>
+    ) -> Result<Vec<Box<dyn entity::Entity>>>
     where
         Z: Fn(&Sprite, &tileset::Tile) -> f32,
     {
         let mut entities: Vec<Box<dyn entity::Entity>> = vec![];
+        let mut error = None;
 
         self.generate(
             layer,
@@ -630,23 +1131,130 @@ impl Map {
             z_depth,
             |sprite, tile| {
                 if let Some(name) = tile.get_property("entity_class") {
-                    let entity = entities::instantiate_map_sprite(
+                    match entities::instantiate_map_sprite(
                         name,
                         sprite,
                         tile,
+                        &Properties::default(),
                         self,
                         collision_space,
                         None,
-                    )
-                    .unwrap_or_else(|_| {
-                        panic!("Unable to instantiate Entity with class name \"{}\"", name)
-                    });
-                    entities.push(entity);
+                    ) {
+                        Ok(entity) => entities.push(entity),
+                        Err(e) => {
+                            error.get_or_insert(e.context(format!(
+                                "Unable to instantiate entity \"{}\" in layer \"{}\"",
+                                name, layer.name
+                            )));
+                        }
+                    }
                 }
             },
         );
 
-        entities
+        if let Some(error) = error {
+            return Err(error);
+        }
+
+        Ok(entities)
+    }
+
+    /// Instantiates an Entity for each object in the map's object groups whose class names an entity
+    /// class; a tile object without a class uses its tile's "entity_class". The object's properties are
+    /// passed to `Entity::init_from_map_sprite`. Objects which don't name an entity class are ignored.
+    pub fn generate_object_entities(
+        &self,
+        collision_space: &mut collision::Space,
+        entity_id_vendor: &mut entity::IdVendor,
+    ) -> Result<Vec<Box<dyn entity::Entity>>> {
+        let mut entities = vec![];
+        for group in &self.object_groups {
+            entities.extend(self.generate_object_group_entities(
+                group,
+                collision_space,
+                entity_id_vendor,
+            )?);
+        }
+        Ok(entities)
+    }
+
+    /// Generates entities for the objects of a single object group, e.g., one of several
+    /// groups of entities which are positioned independently of one another.
+    pub fn generate_object_group_entities(
+        &self,
+        group: &ObjectGroup,
+        collision_space: &mut collision::Space,
+        entity_id_vendor: &mut entity::IdVendor,
+    ) -> Result<Vec<Box<dyn entity::Entity>>> {
+        let mut entities: Vec<Box<dyn entity::Entity>> = vec![];
+        let untiled = tileset::Tile::new(0);
+
+        for obj in &group.objects {
+            // tile objects are positioned by their bottom-left corner, all others by their top-left
+            let bottom = if obj.gid.is_some() {
+                obj.y
+            } else {
+                obj.y + obj.height as i32
+            };
+            let origin = point2(
+                obj.x.div_euclid(self.tile_width as i32) - self.tile_origin.x,
+                self.tile_origin.y + self.height as i32 + 1
+                    - bottom.div_euclid(self.tile_height as i32),
+            );
+
+            let (mut sprite, tile) = match obj.gid.and_then(|gid| self.sprite_for_gid(gid, origin))
+            {
+                Some((sprite, tile)) => (sprite, tile),
+                None => {
+                    let mut sprite = Sprite::unit(
+                        collision::Shape::Square,
+                        origin,
+                        0.0,
+                        point2(0.0, 0.0),
+                        vec2(0.0, 0.0),
+                        vec4(1.0, 1.0, 1.0, 1.0),
+                        ENTITY,
+                    );
+                    sprite.extent = vec2(
+                        (obj.width / self.tile_width).max(1) as f32,
+                        (obj.height / self.tile_height).max(1) as f32,
+                    );
+                    (sprite, &untiled)
+                }
+            };
+
+            let class_name = if obj.class.is_empty() {
+                match tile.get_property("entity_class") {
+                    Some(class_name) => class_name,
+                    None => continue,
+                }
+            } else {
+                obj.class.as_str()
+            };
+
+            if let Some(mut entity) = entities::instantiate_entity_by_class_name(class_name) {
+                let entity_id = entity_id_vendor.next_id();
+                sprite.entity_id = Some(entity_id);
+                entity
+                    .init_from_map_sprite(
+                        entity_id,
+                        &sprite,
+                        tile,
+                        &obj.properties,
+                        self,
+                        collision_space,
+                    )
+                    .with_context(|| {
+                        format!(
+                        "Unable to instantiate entity \"{}\" for object {} in object group \"{}\"",
+                        class_name, obj.id, group.name
+                    )
+                    })?;
+                entities.push(entity);
+            }
+        }
+
+        Ok(entities)
     }
 
     fn generate<Z, C, E>(&self, layer: &Layer, mut entity_id_vendor: E, z_depth: Z, mut consumer: C)
@@ -655,83 +1263,423 @@ impl Map {
         C: FnMut(&Sprite, &tileset::Tile),
         E: FnMut(&Sprite, &tileset::Tile) -> u32,
     {
-        // https://doc.mapeditor.org/en/stable/reference/tmx-map-format/#tile-flipping
-        let flipped_horizontally_flag = 0x80000000;
-        let flipped_vertically_flag = 0x40000000;
-        let flipped_diagonally_flag = 0x20000000;
-
-        for y in 0..layer.height {
-            for x in 0..layer.width {
-                let index: usize = (y * layer.width + x) as usize;
-                let tile_id = layer.tile_data[index];
-                let flipped_horizontally = tile_id & flipped_horizontally_flag != 0;
-                let flipped_vertically = tile_id & flipped_vertically_flag != 0;
-                let flipped_diagonally = tile_id & flipped_diagonally_flag != 0;
-                let tile_id = tile_id
-                    & !(flipped_diagonally_flag
-                        | flipped_vertically_flag
-                        | flipped_horizontally_flag);
-
-                if self.tileset_first_gid <= tile_id
-                    && tile_id - self.tileset_first_gid < self.tileset.tile_count
-                {
-                    let tile = &self
-                        .tileset
-                        .get_tile(tile_id - self.tileset_first_gid)
-                        .unwrap();
-                    let tex_coord_bounds = self.tileset.get_tex_coords_for_tile(tile);
-                    let mut mask = 0;
-
-                    if tile.boolean_property("ground") {
-                        mask |= GROUND;
-                    }
-                    if tile.boolean_property("water") {
-                        mask |= WATER;
-                    }
-                    if tile.boolean_property("ratchet") {
-                        mask |= RATCHET;
-                    }
-                    if tile.has_property("entity_class") {
-                        mask |= ENTITY;
-                    }
-                    if tile.boolean_property("contact_damage") {
-                        mask |= CONTACT_DAMAGE;
-                    }
-                    if tile.boolean_property("shootable") {
-                        mask |= SHOOTABLE;
-                    }
-
-                    let mut sd = Sprite::unit(
-                        tile.shape(),
-                        point2(x as i32, (layer.height - y) as i32),
-                        0.0,
-                        tex_coord_bounds.origin,
-                        tex_coord_bounds.extent,
-                        vec4(1.0, 1.0, 1.0, 1.0),
-                        mask,
-                    );
-
-                    if mask & ENTITY != 0 {
+        // Map tile coordinates (y increasing downwards) to world coordinates (y increasing upwards),
+        // such that the map's tiles occupy (0,0) to (width,height)
+        let to_world = |x: i32, y: i32| {
+            point2(
+                x - self.tile_origin.x,
+                self.tile_origin.y + self.height as i32 - y,
+            )
+        };
+
+        for chunk in &layer.chunks {
+            for (index, gid) in chunk.tile_data.iter().copied().enumerate() {
+                let x = chunk.x + (index as u32 % chunk.width) as i32;
+                let y = chunk.y + (index as u32 / chunk.width) as i32;
+
+                if let Some((mut sd, tile)) = self.sprite_for_gid(gid, to_world(x, y)) {
+                    if sd.mask & ENTITY != 0 {
                         sd.entity_id = Some(entity_id_vendor(&sd, tile));
                     }
 
                     sd.origin.z = z_depth(&sd, tile);
+                    consumer(&sd, tile);
+                }
+            }
+        }
+    }
 
-                    if flipped_diagonally {
-                        sd = sd.flipped_diagonally();
-                    }
+    /// Creates the unit sprite for the tile referenced by `gid`, at `origin` in world coordinates,
+    /// applying the gid's flip flags. Returns None for an empty or unrecognized gid.
+    fn sprite_for_gid(&self, gid: u32, origin: Point2<i32>) -> Option<(Sprite, &tileset::Tile)> {
+        let flipped_horizontally = gid & FLIPPED_HORIZONTALLY_FLAG != 0;
+        let flipped_vertically = gid & FLIPPED_VERTICALLY_FLAG != 0;
+        let flipped_diagonally = gid & FLIPPED_DIAGONALLY_FLAG != 0;
+        let gid = gid & !FLIPPED_FLAGS;
+
+        let (tileset_index, tile) = self.tile_for_gid(gid)?;
+        let tex_coord_bounds = self.tilesets[tileset_index]
+            .tileset
+            .get_tex_coords_for_tile(tile);
+        let mut mask = 0;
+
+        if tile.boolean_property("ground") {
+            mask |= GROUND;
+        }
+        if tile.boolean_property("water") {
+            mask |= WATER;
+        }
+        if tile.boolean_property("ratchet") {
+            mask |= RATCHET;
+        }
+        if tile.has_property("entity_class") {
+            mask |= ENTITY;
+        }
+        if tile.boolean_property("contact_damage") {
+            mask |= CONTACT_DAMAGE;
+        }
+        if tile.boolean_property("shootable") {
+            mask |= SHOOTABLE;
+        }
 
-                    if flipped_horizontally {
-                        sd = sd.flipped_horizontally();
-                    }
+        let mut sd = Sprite::unit(
+            tile.shape(),
+            origin,
+            0.0,
+            tex_coord_bounds.origin,
+            tex_coord_bounds.extent,
+            vec4(1.0, 1.0, 1.0, 1.0),
+            mask,
+        );
 
-                    if flipped_vertically {
-                        sd = sd.flipped_vertically();
-                    }
+        sd.tileset = tileset_index;
 
-                    consumer(&sd, tile);
-                }
-            }
+        if flipped_diagonally {
+            sd = sd.flipped_diagonally();
+        }
+
+        if flipped_horizontally {
+            sd = sd.flipped_horizontally();
+        }
+
+        if flipped_vertically {
+            sd = sd.flipped_vertically();
+        }
+
+        Some((sd, tile))
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod map_tests {
+    use super::*;
+    use crate::property::PropertyValue;
+
+    fn layer_with_properties(properties: &[(&str, &str)]) -> Layer {
+        Layer {
+            name: "Test".to_string(),
+            properties: properties.iter().fold(
+                Properties::new("<layer name=\"Test\">"),
+                |mut properties, (k, v)| {
+                    properties.insert(k, PropertyValue::String(v.to_string()));
+                    properties
+                },
+            ),
+            ..Default::default()
         }
     }
+
+    #[test]
+    fn layer_data_is_decoded_from_each_encoding() {
+        let expected = vec![1, 2, 3, 0, 0, 9, 10, 0, 17, 18, 19, 20];
+        for fixture in [
+            "layer_csv.tmx",
+            "layer_base64.tmx",
+            "layer_base64_zlib.tmx",
+            "layer_base64_gzip.tmx",
+            "layer_base64_zstd.tmx",
+        ] {
+            let path = Path::new("test_fixtures/maps").join(fixture);
+            let map = Map::new_tmx(&path, &mut tileset::TileSetCache::default())
+                .unwrap_or_else(|e| panic!("Expected {:?} to load: {:?}", path, e));
+            assert_eq!(
+                map.layer_named("Level").unwrap().chunks[0].tile_data,
+                expected,
+                "Unexpected tile data decoded from {:?}",
+                path
+            );
+        }
+    }
+
+    #[test]
+    fn infinite_map_bounds_and_sprites_come_from_chunks() {
+        let map = Map::new_tmx(
+            Path::new("test_fixtures/maps/infinite_chunks.tmx"),
+            &mut tileset::TileSetCache::default(),
+        )
+        .unwrap();
+        let layer = map.layer_named("Level").unwrap();
+        assert_eq!(layer.chunks.len(), 2);
+
+        // chunks span tiles (-2,-1) to (2,3)
+        assert_eq!(layer.tile_extents(), Some((point2(-2, -1), point2(2, 3))));
+        assert_eq!(map.bounds(), Bounds::new(point2(0.0, 0.0), vec2(4.0, 4.0)));
+
+        // tiles are placed relative to the top-left of the chunk extents, y flipped
+        let mut positions = map
+            .generate_sprites(layer, |_, _| 0.0)
+            .iter()
+            .map(|sprite| (sprite.origin.x as i32, sprite.origin.y as i32))
+            .collect::<Vec<_>>();
+        positions.sort();
+        assert_eq!(positions, vec![(0, 4), (1, 3), (2, 2), (3, 1)]);
+    }
+
+    #[test]
+    fn gids_resolve_to_their_tilesets() {
+        let map = Map::new_tmx(
+            Path::new("test_fixtures/maps/multiple_tilesets.tmx"),
+            &mut tileset::TileSetCache::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            map.tilesets
+                .iter()
+                .map(|tileset| tileset.first_gid)
+                .collect::<Vec<_>>(),
+            vec![1, 65]
+        );
+        assert_eq!(
+            map.tilesets[1].image_path,
+            Path::new("test_fixtures/maps/../../res/game_ui_tileset.png")
+        );
+
+        assert!(map.tile_for_gid(0).is_none());
+        assert_eq!(map.tile_for_gid(64).map(|(i, t)| (i, t.id)), Some((0, 63)));
+        assert_eq!(map.tile_for_gid(65).map(|(i, t)| (i, t.id)), Some((1, 0)));
+        assert!(map.tile_for_gid(1000).is_none());
+
+        // the out of range gid 1000 produces no sprite
+        let sprites = map.generate_sprites(map.layer_named("Level").unwrap(), |_, _| 0.0);
+        assert_eq!(sprites.len(), 2);
+        let level_tileset = &map.tilesets[0].tileset;
+        let ui_tileset = &map.tilesets[1].tileset;
+        assert_eq!(sprites[0].tileset, 0);
+        assert_eq!(
+            sprites[0].tex_coord_origin,
+            level_tileset
+                .get_tex_coords_for_tile(level_tileset.get_tile(1).unwrap())
+                .origin
+        );
+        assert_eq!(sprites[1].tileset, 1);
+        assert_eq!(
+            sprites[1].tex_coord_origin,
+            ui_tileset
+                .get_tex_coords_for_tile(ui_tileset.get_tile(1).unwrap())
+                .origin
+        );
+    }
+
+    #[test]
+    fn embedded_tileset_is_parsed() {
+        let map = Map::new_tmx(
+            Path::new("test_fixtures/maps/embedded_tileset.tmx"),
+            &mut tileset::TileSetCache::default(),
+        )
+        .unwrap();
+        assert_eq!(map.tilesets.len(), 1);
+        assert_eq!(
+            map.tilesets[0].image_path,
+            Path::new("test_fixtures/maps/../../res/level_1_tileset.png")
+        );
+
+        let (_, tile) = map.tile_for_gid(2).unwrap();
+        assert!(tile.boolean_property("ground"));
+
+        // the layer following the embedded tileset is still read
+        let sprites = map.generate_sprites(map.layer_named("Level").unwrap(), |_, _| 0.0);
+        assert_eq!(sprites.len(), 2);
+        assert_eq!(sprites[1].mask, GROUND);
+    }
+
+    #[test]
+    fn animations_read_typed_frame_properties() {
+        let map = Map::new_tmx(
+            Path::new("test_fixtures/maps/typed_animation.tmx"),
+            &mut tileset::TileSetCache::default(),
+        )
+        .unwrap();
+
+        let animations = map
+            .generate_animations(map.layer_named("Water").unwrap(), |_, _| 0.0)
+            .unwrap();
+        assert_eq!(animations.len(), 1);
+        // frames are ordered by animation_frame, rather than by tile id
+        assert_eq!(
+            animations[0].durations,
+            vec![Duration::from_secs_f32(0.25), Duration::from_secs_f32(0.5)]
+        );
+
+        let error = map
+            .generate_animations(map.layer_named("Broken").unwrap(), |_, _| 0.0)
+            .expect_err("Expect animation without durations to fail");
+        assert!(
+            format!("{:#}", error).contains("animation_duration"),
+            "{:#}",
+            error
+        );
+    }
+
+    #[test]
+    fn external_tilesets_are_loaded_once() {
+        let mut tilesets = tileset::TileSetCache::default();
+        let a = Map::new_tmx(Path::new("test_fixtures/maps/layer_csv.tmx"), &mut tilesets).unwrap();
+        let b = Map::new_tmx(Path::new("res/level_1.tmx"), &mut tilesets).unwrap();
+        assert!(Rc::ptr_eq(&a.tilesets[0].tileset, &b.tilesets[0].tileset));
+    }
+
+    #[test]
+    fn tile_objects_spawn_entities_where_their_tiles_would_be() {
+        let map = Map::new_tmx(
+            Path::new("res/game_ui.tmx"),
+            &mut tileset::TileSetCache::default(),
+        )
+        .unwrap();
+        let entities = map
+            .generate_object_entities(
+                &mut collision::Space::new(&[]),
+                &mut entity::IdVendor::default(),
+            )
+            .unwrap();
+
+        // the drawer's digits sit on the tile row 6 of the 8 row map, the pause menu's
+        // volume digits on rows 4 and 5, and the continue prompt's digits on rows 1 and 6
+        let mut origins = entities
+            .iter()
+            .map(|e| {
+                assert_eq!(e.entity_class(), entities::EntityClass::UiDigit);
+                (e.position().x as i32, e.position().y as i32)
+            })
+            .collect::<Vec<_>>();
+        origins.sort();
+        assert_eq!(
+            origins,
+            vec![
+                (10, 3),
+                (10, 4),
+                (11, 2),
+                (11, 3),
+                (11, 4),
+                (12, 2),
+                (15, 2),
+                (15, 7),
+                (17, 2)
+            ]
+        );
+    }
+
+    #[test]
+    fn misconfigured_entities_are_errors() {
+        let map = Map::new_tmx(
+            Path::new("test_fixtures/maps/invalid_level.tmx"),
+            &mut tileset::TileSetCache::default(),
+        )
+        .unwrap();
+        let mut collision_space = collision::Space::new(&[]);
+        let mut id_vendor = entity::IdVendor::default();
+
+        let error = map
+            .generate_entities(
+                map.layer_named("Entities").unwrap(),
+                &mut collision_space,
+                &mut id_vendor,
+                |_, _| 0.0,
+            )
+            .err()
+            .unwrap();
+        assert_eq!(
+            format!("{:#}", error),
+            "Unable to instantiate entity \"SpawnPoint\" in layer \"Entities\": BossFish arena: <tile id=\"5\"> has no property \"arena_height\""
+        );
+
+        let error = map
+            .generate_object_entities(&mut collision_space, &mut id_vendor)
+            .err()
+            .unwrap();
+        assert_eq!(
+            format!("{:#}", error),
+            "Unable to instantiate entity \"SpawnPoint\" for object 1 in object group \"Objects\": SpawnPoint has no \"spawned_entity_class\" property"
+        );
+    }
+
+    #[test]
+    fn object_shapes_and_templates_are_parsed() {
+        let map = Map::new_tmx(
+            Path::new("test_fixtures/maps/objects.tmx"),
+            &mut tileset::TileSetCache::default(),
+        )
+        .unwrap();
+        let objects = &map.object_group_named("Objects").unwrap().objects;
+
+        // point objects have no size
+        assert_eq!(objects[0].shape, ObjectShape::Point);
+        assert_eq!((objects[0].x, objects[0].y), (8, 40));
+        assert_eq!((objects[0].width, objects[0].height), (0, 0));
+
+        assert_eq!(
+            objects[1].shape,
+            ObjectShape::Polyline(vec![
+                point2(0.0, 0.0),
+                point2(32.0, 0.0),
+                point2(32.0, -16.0)
+            ])
+        );
+        assert!(matches!(&objects[2].shape, ObjectShape::Polygon(points) if points.len() == 4));
+
+        // templated objects inherit the template's shape, size, class and properties
+        let trigger = &objects[3];
+        assert_eq!(trigger.id, 4);
+        assert_eq!(trigger.name, "trigger");
+        assert_eq!(trigger.class, "Trigger");
+        assert_eq!(trigger.shape, ObjectShape::Ellipse);
+        assert_eq!((trigger.x, trigger.width, trigger.height), (32, 32, 16));
+        assert_eq!(trigger.property("event"), Some("boss_arena"));
+
+        // ...which the instance may override
+        let overridden = &objects[4];
+        assert_eq!((overridden.width, overridden.height), (16, 16));
+        assert_eq!(overridden.property("event"), Some("checkpoint"));
+        assert!(overridden.properties.flag("once"));
+    }
+
+    #[test]
+    fn unsupported_layer_data_encoding_is_rejected() {
+        assert!(DataEncoding::new(Some("base64"), Some("lzma")).is_err());
+        assert!(DataEncoding::new(None, None).is_err());
+        assert!(DataEncoding::Base64(DataCompression::Zlib)
+            .decode("bm90IHpsaWI=")
+            .is_err());
+    }
+
+    #[test]
+    fn layer_configuration_is_read_from_tmx_properties() {
+        let map = Map::new_tmx(
+            Path::new("res/level_1.tmx"),
+            &mut tileset::TileSetCache::default(),
+        )
+        .unwrap();
+
+        let level = map.layer_named("Level").unwrap();
+        assert_eq!(level.role().unwrap(), LayerRole::Stage);
+        assert_eq!(level.z_depth().unwrap(), layers::stage::LEVEL);
+        assert!(level.collision());
+
+        let entities = map.layer_named("Entities").unwrap();
+        assert_eq!(entities.role().unwrap(), LayerRole::Entities);
+
+        let rising_floor = map.layer_named("RisingFloor").unwrap();
+        assert_eq!(rising_floor.role().unwrap(), LayerRole::Entity);
+        assert_eq!(rising_floor.entity_class_name().unwrap(), "RisingFloor");
+    }
+
+    #[test]
+    fn layer_z_depth_accepts_names_and_numbers() {
+        let named = layer_with_properties(&[("z_depth", "foreground")]);
+        assert_eq!(named.z_depth().unwrap(), layers::stage::FOREGROUND);
+
+        let numeric = layer_with_properties(&[("z_depth", "85.5")]);
+        assert_eq!(numeric.z_depth().unwrap(), 85.5);
+
+        let invalid = layer_with_properties(&[("z_depth", "sky")]);
+        assert!(invalid.z_depth().is_err());
+    }
+
+    #[test]
+    fn layer_with_unrecognized_role_is_rejected() {
+        let layer = layer_with_properties(&[("role", "parallax")]);
+        assert!(layer.role().is_err());
+        assert_eq!(layer_with_properties(&[]).role().unwrap(), LayerRole::Stage);
+    }
 }
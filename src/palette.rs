@@ -0,0 +1,205 @@
+use std::{fs, path::Path};
+
+use anyhow::*;
+use image::GenericImageView;
+
+use crate::assets;
+
+// ---------------------------------------------------------------------------------------------------------------------
+
+/// The palettes in res/tonemaps, in the order the player cycles through them
+pub const BUILTIN_PALETTES: [&str; 4] = ["gameboy", "mist", "nostalgia", "nymph"];
+
+/// Number of colors in a palette, as on the original gameboy's LCD
+pub const PALETTE_SIZE: usize = 4;
+
+// ---------------------------------------------------------------------------------------------------------------------
+
+/// A palette of PALETTE_SIZE colors, from darkest to lightest, which the LcdFilter maps the
+/// game's shades onto. Besides the builtin palettes, players may supply their own, as a PNG
+/// strip of 4 pixels, or as a text file listing 4 hex colors, e.g.:
+///
+/// ```text
+/// #0f380f
+/// #306230
+/// #8bac0f
+/// #9bbc0f
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Palette {
+    /// The builtin palette's name, or the path of the file the palette was loaded from
+    pub name: String,
+    pub colors: [[u8; 3]; PALETTE_SIZE],
+}
+
+impl Palette {
+    /// Loads the palette `name`, which is either a builtin palette's name, or the path to a
+    /// PNG strip or hex list.
+    pub fn load(name: &str) -> Result<Self> {
+        if Self::is_builtin(name) {
+            let path = format!("res/tonemaps/{}.png", name);
+            let bytes = assets::read(&path)?;
+            return Self::from_png(name, &bytes)
+                .with_context(|| format!("Unable to load palette {:?}", path));
+        }
+
+        let path = Path::new(name);
+        let bytes = fs::read(path).with_context(|| {
+            format!(
+                "Expected palette \"{}\" to be one of {:?}, or a palette file",
+                name, BUILTIN_PALETTES
+            )
+        })?;
+        let is_png = path
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("png"));
+        if is_png {
+            Self::from_png(name, &bytes)
+        } else {
+            Self::from_hex_list(name, &String::from_utf8_lossy(&bytes))
+        }
+        .with_context(|| format!("Unable to load palette file {:?}", path))
+    }
+
+    pub fn is_builtin(name: &str) -> bool {
+        BUILTIN_PALETTES.contains(&name)
+    }
+
+    /// Reads a palette from a PNG strip of PALETTE_SIZE pixels, horizontal or vertical.
+    pub fn from_png(name: &str, bytes: &[u8]) -> Result<Self> {
+        let image = image::load_from_memory(bytes)?;
+        let (width, height) = image.dimensions();
+        if width.min(height) != 1 || width.max(height) as usize != PALETTE_SIZE {
+            bail!(
+                "Expected palette image to be a strip of {} pixels, found {}x{}",
+                PALETTE_SIZE,
+                width,
+                height
+            );
+        }
+
+        let rgba = image.to_rgba8();
+        let mut colors = [[0; 3]; PALETTE_SIZE];
+        for (color, pixel) in colors.iter_mut().zip(rgba.pixels()) {
+            color.copy_from_slice(&pixel.0[..3]);
+        }
+        Ok(Self {
+            name: name.to_string(),
+            colors,
+        })
+    }
+
+    /// Reads a palette from a list of PALETTE_SIZE hex colors, `#rrggbb` or `rrggbb`, separated
+    /// by whitespace, commas or newlines.
+    pub fn from_hex_list(name: &str, text: &str) -> Result<Self> {
+        let mut colors = vec![];
+        for (idx, line) in text.lines().enumerate() {
+            let line_number = idx + 1;
+            for token in line
+                .split(|c: char| c.is_whitespace() || c == ',')
+                .filter(|token| !token.is_empty())
+            {
+                let hex = token.strip_prefix('#').unwrap_or(token);
+                if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+                    bail!(
+                        "Expected \"{}\" on line {} to be a color like #rrggbb",
+                        token,
+                        line_number
+                    );
+                }
+                let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).unwrap();
+                colors.push([channel(0), channel(2), channel(4)]);
+            }
+        }
+
+        let colors: [[u8; 3]; PALETTE_SIZE] = colors.try_into().map_err(|colors: Vec<_>| {
+            anyhow!(
+                "Expected palette to have {} colors, found {}",
+                PALETTE_SIZE,
+                colors.len()
+            )
+        })?;
+        Ok(Self {
+            name: name.to_string(),
+            colors,
+        })
+    }
+
+    /// Returns the palette as a horizontal strip image, as the LcdFilter's tonemap expects.
+    pub fn to_image(&self) -> image::DynamicImage {
+        let mut image = image::RgbaImage::new(PALETTE_SIZE as u32, 1);
+        for (pixel, color) in image.pixels_mut().zip(self.colors) {
+            *pixel = image::Rgba([color[0], color[1], color[2], 255]);
+        }
+        image::DynamicImage::ImageRgba8(image)
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod palette_tests {
+    use super::*;
+
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let image = image::RgbaImage::from_fn(width, height, |x, y| {
+            let shade = ((x + y) * 80) as u8;
+            image::Rgba([shade, shade, shade, 255])
+        });
+        let mut bytes = vec![];
+        image::DynamicImage::ImageRgba8(image)
+            .write_to(
+                &mut std::io::Cursor::new(&mut bytes),
+                image::ImageFormat::Png,
+            )
+            .unwrap();
+        bytes
+    }
+
+    #[test]
+    fn builtin_palettes_load() {
+        for name in BUILTIN_PALETTES {
+            let palette = Palette::load(name).unwrap();
+            assert_eq!(palette.name, name);
+        }
+        assert_eq!(
+            Palette::load("gameboy").unwrap().colors[0],
+            [0x20, 0x20, 0x20]
+        );
+    }
+
+    #[test]
+    fn hex_lists_need_four_colors() {
+        let palette =
+            Palette::from_hex_list("custom", "#0f380f\n306230, #8BAC0F\n\n#9bbc0f\n").unwrap();
+        assert_eq!(
+            palette.colors,
+            [
+                [0x0f, 0x38, 0x0f],
+                [0x30, 0x62, 0x30],
+                [0x8b, 0xac, 0x0f],
+                [0x9b, 0xbc, 0x0f]
+            ]
+        );
+        assert_eq!(
+            palette.to_image().to_rgba8().get_pixel(3, 0).0,
+            [0x9b, 0xbc, 0x0f, 255]
+        );
+
+        let error = Palette::from_hex_list("custom", "#000000 #555555 #aaaaaa")
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("found 3"), "{}", error);
+        assert!(Palette::from_hex_list("custom", "#000000 #555555 #aaaaaa #fff").is_err());
+        assert!(Palette::from_hex_list("custom", "#000000 #555555 #aaaaaa #ffffgg").is_err());
+    }
+
+    #[test]
+    fn png_strips_need_four_pixels() {
+        let palette = Palette::from_png("custom", &png(1, 4)).unwrap();
+        assert_eq!(palette.colors[1], [80, 80, 80]);
+        assert!(Palette::from_png("custom", &png(4, 1)).is_ok());
+        assert!(Palette::from_png("custom", &png(5, 1)).is_err());
+        assert!(Palette::from_png("custom", &png(4, 2)).is_err());
+    }
+}
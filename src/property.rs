@@ -0,0 +1,419 @@
+use anyhow::{Context, Result};
+use cgmath::*;
+use std::{collections::HashMap, path::PathBuf};
+use xml::reader::XmlEvent;
+
+// ---------------------------------------------------------------------------------------------------------------------
+
+/// A custom property value from a tmx or tsx file, typed by the <property> element's `type` attribute.
+/// Properties without a `type` are strings.
+#[derive(Clone, Debug, PartialEq)]
+pub enum PropertyValue {
+    String(String),
+    Int(i32),
+    Float(f32),
+    Bool(bool),
+    /// RGBA color, with components in [0,1]
+    Color(Vector4<f32>),
+    /// Path to a file, relative to the file defining the property
+    File(PathBuf),
+    /// Id of an object in the map, or 0 for no object
+    Object(u32),
+    /// A value of a custom class type, with its member properties
+    Class {
+        class: String,
+        members: Properties,
+    },
+}
+
+impl PropertyValue {
+    /// Parses a property value per Tiled's `type` attribute; `property_type` is the custom type name of
+    /// class properties, and `members` their nested properties.
+    fn parse(
+        value_type: Option<&str>,
+        property_type: Option<String>,
+        value: &str,
+        members: Option<Properties>,
+    ) -> Result<Self> {
+        match value_type.unwrap_or("string") {
+            "string" => Ok(PropertyValue::String(value.to_string())),
+            "int" => {
+                Ok(PropertyValue::Int(value.parse().with_context(|| {
+                    format!("Expected an int, got \"{}\"", value)
+                })?))
+            }
+            "float" => {
+                Ok(PropertyValue::Float(value.parse().with_context(|| {
+                    format!("Expected a float, got \"{}\"", value)
+                })?))
+            }
+            "bool" => Ok(PropertyValue::Bool(parse_bool(value)?)),
+            "color" => Ok(PropertyValue::Color(parse_color(value)?)),
+            "file" => Ok(PropertyValue::File(PathBuf::from(value))),
+            "object" => Ok(PropertyValue::Object(value.parse().with_context(|| {
+                format!("Expected an object id, got \"{}\"", value)
+            })?)),
+            "class" => Ok(PropertyValue::Class {
+                class: property_type.unwrap_or_default(),
+                members: members.unwrap_or_default(),
+            }),
+            other => anyhow::bail!("Unrecognized property type \"{}\"", other),
+        }
+    }
+
+    /// Name of the value's type, as written in the `type` attribute
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            PropertyValue::String(_) => "string",
+            PropertyValue::Int(_) => "int",
+            PropertyValue::Float(_) => "float",
+            PropertyValue::Bool(_) => "bool",
+            PropertyValue::Color(_) => "color",
+            PropertyValue::File(_) => "file",
+            PropertyValue::Object(_) => "object",
+            PropertyValue::Class { .. } => "class",
+        }
+    }
+
+    // Typed accessors. String values are parsed, since properties of maps authored before a
+    // property was given a type are strings.
+
+    pub fn as_int(&self) -> Result<i32> {
+        match self {
+            PropertyValue::Int(value) => Ok(*value),
+            PropertyValue::String(value) => value
+                .parse()
+                .with_context(|| format!("Expected an int, got \"{}\"", value)),
+            other => anyhow::bail!("Expected an int, got a {}", other.type_name()),
+        }
+    }
+
+    pub fn as_float(&self) -> Result<f32> {
+        match self {
+            PropertyValue::Float(value) => Ok(*value),
+            PropertyValue::Int(value) => Ok(*value as f32),
+            PropertyValue::String(value) => value
+                .parse()
+                .with_context(|| format!("Expected a float, got \"{}\"", value)),
+            other => anyhow::bail!("Expected a float, got a {}", other.type_name()),
+        }
+    }
+
+    pub fn as_bool(&self) -> Result<bool> {
+        match self {
+            PropertyValue::Bool(value) => Ok(*value),
+            PropertyValue::String(value) => parse_bool(value),
+            other => anyhow::bail!("Expected a bool, got a {}", other.type_name()),
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            PropertyValue::String(value) => Some(value),
+            _ => None,
+        }
+    }
+}
+
+fn parse_bool(value: &str) -> Result<bool> {
+    match value {
+        "true" => Ok(true),
+        "false" => Ok(false),
+        _ => anyhow::bail!("Expected \"true\" or \"false\", got \"{}\"", value),
+    }
+}
+
+/// Parses Tiled's "#AARRGGBB" or "#RRGGBB" colors; an empty value is transparent black.
+fn parse_color(value: &str) -> Result<Vector4<f32>> {
+    if value.is_empty() {
+        return Ok(vec4(0.0, 0.0, 0.0, 0.0));
+    }
+    let hex = value
+        .strip_prefix('#')
+        .with_context(|| format!("Expected a color like \"#AARRGGBB\", got \"{}\"", value))?;
+    let component = |i: usize| -> Result<f32> {
+        let c = hex
+            .get(i * 2..i * 2 + 2)
+            .and_then(|c| u8::from_str_radix(c, 16).ok())
+            .with_context(|| format!("Expected a color like \"#AARRGGBB\", got \"{}\"", value))?;
+        Ok(c as f32 / 255.0)
+    };
+    match hex.len() {
+        6 => Ok(vec4(component(0)?, component(1)?, component(2)?, 1.0)),
+        8 => Ok(vec4(
+            component(1)?,
+            component(2)?,
+            component(3)?,
+            component(0)?,
+        )),
+        _ => anyhow::bail!("Expected a color like \"#AARRGGBB\", got \"{}\"", value),
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+
+/// The custom properties of a tmx or tsx element. `owner` describes the element, e.g., `<layer name="Level">`,
+/// and is used to name it in errors.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Properties {
+    owner: String,
+    values: HashMap<String, PropertyValue>,
+}
+
+impl Properties {
+    pub fn new(owner: &str) -> Self {
+        Self {
+            owner: owner.to_string(),
+            values: HashMap::new(),
+        }
+    }
+
+    /// Parses the <property> elements of a <properties> element, consuming `events` through its
+    /// closing </properties>.
+    pub fn parse<I>(owner: &str, events: &mut I) -> Result<Self>
+    where
+        I: Iterator<Item = xml::reader::Result<XmlEvent>>,
+    {
+        // name, type, propertytype, value and members of the <property> being read; a string's value
+        // may be its text content rather than an attribute, and a class's members are nested <properties>
+        type PendingProperty = (
+            String,
+            Option<String>,
+            Option<String>,
+            String,
+            Option<Properties>,
+        );
+
+        let mut properties = Properties::new(owner);
+        let mut current: Option<PendingProperty> = None;
+
+        while let Some(e) = events.next() {
+            match e {
+                Ok(XmlEvent::StartElement {
+                    name, attributes, ..
+                }) => match name.local_name.as_str() {
+                    "property" => {
+                        let mut property_name: Option<String> = None;
+                        let mut value_type: Option<String> = None;
+                        let mut property_type: Option<String> = None;
+                        let mut value = String::new();
+                        for attr in attributes {
+                            match attr.name.local_name.as_str() {
+                                "name" => property_name = Some(attr.value),
+                                "type" => value_type = Some(attr.value),
+                                "propertytype" => property_type = Some(attr.value),
+                                "value" => value = attr.value,
+                                _ => {}
+                            }
+                        }
+                        let property_name = property_name.with_context(|| {
+                            format!(
+                                "Expected <property> of {} to have a 'name' attribute",
+                                owner
+                            )
+                        })?;
+                        current = Some((property_name, value_type, property_type, value, None));
+                    }
+                    "properties" => {
+                        let (property_name, _, _, _, members) =
+                            current.as_mut().with_context(|| {
+                                format!("Expected <properties> of {} to be in a <property>", owner)
+                            })?;
+                        let member_owner = format!("{} property \"{}\"", owner, property_name);
+                        *members = Some(Self::parse(&member_owner, events)?);
+                    }
+                    _ => {}
+                },
+                Ok(XmlEvent::Characters(text)) => {
+                    if let Some((_, _, _, value, _)) = current.as_mut() {
+                        value.push_str(&text);
+                    }
+                }
+                Ok(XmlEvent::EndElement { name }) if name.local_name.as_str() == "property" => {
+                    let (property_name, value_type, property_type, value, members) =
+                        current.take().with_context(|| {
+                            format!("Unexpected </property> in properties of {}", owner)
+                        })?;
+                    let value = PropertyValue::parse(
+                        value_type.as_deref(),
+                        property_type,
+                        value.trim(),
+                        members,
+                    )
+                    .with_context(|| {
+                        format!("Invalid property \"{}\" of {}", property_name, owner)
+                    })?;
+                    properties.values.insert(property_name, value);
+                }
+                Ok(XmlEvent::EndElement { name }) if name.local_name.as_str() == "properties" => {
+                    return Ok(properties);
+                }
+                Err(_) => {}
+                _ => {}
+            }
+        }
+
+        anyhow::bail!("Expected </properties> closing the properties of {}", owner)
+    }
+
+    pub fn insert(&mut self, name: &str, value: PropertyValue) {
+        self.values.insert(name.to_string(), value);
+    }
+
+    /// Adds the values of `other`, replacing any of the same name
+    pub fn extend(&mut self, other: Properties) {
+        self.values.extend(other.values);
+    }
+
+    pub fn get(&self, name: &str) -> Option<&PropertyValue> {
+        self.values.get(name)
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.values.contains_key(name)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &PropertyValue)> {
+        self.values.iter()
+    }
+
+    /// Returns the named string property, or None if it's unset or not a string.
+    pub fn string(&self, name: &str) -> Option<&str> {
+        self.get(name).and_then(|value| value.as_str())
+    }
+
+    /// True if the named property is set to true; false if it's unset, false, or not a bool.
+    pub fn flag(&self, name: &str) -> bool {
+        self.get(name)
+            .map(|value| value.as_bool().unwrap_or(false))
+            .unwrap_or(false)
+    }
+
+    pub fn int(&self, name: &str) -> Result<i32> {
+        self.required(name)?
+            .as_int()
+            .with_context(|| self.invalid(name))
+    }
+
+    pub fn float(&self, name: &str) -> Result<f32> {
+        self.required(name)?
+            .as_float()
+            .with_context(|| self.invalid(name))
+    }
+
+    pub fn bool(&self, name: &str) -> Result<bool> {
+        self.required(name)?
+            .as_bool()
+            .with_context(|| self.invalid(name))
+    }
+
+    fn required(&self, name: &str) -> Result<&PropertyValue> {
+        self.get(name)
+            .with_context(|| format!("{} has no property \"{}\"", self.owner, name))
+    }
+
+    fn invalid(&self, name: &str) -> String {
+        format!("Invalid property \"{}\" of {}", name, self.owner)
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod property_tests {
+    use super::*;
+    use xml::reader::EventReader;
+
+    fn parse(xml: &str) -> Result<Properties> {
+        let mut events = EventReader::new(xml.as_bytes()).into_iter();
+        while let Some(e) = events.next() {
+            if let Ok(XmlEvent::StartElement { name, .. }) = e {
+                if name.local_name == "properties" {
+                    return Properties::parse("<test>", &mut events);
+                }
+            }
+        }
+        anyhow::bail!("No <properties> in test xml")
+    }
+
+    #[test]
+    fn properties_are_typed_by_type_attribute() {
+        let properties = parse(
+            r##"<properties>
+                <property name="name" value="Bat"/>
+                <property name="count" type="int" value="3"/>
+                <property name="speed" type="float" value="1.5"/>
+                <property name="enabled" type="bool" value="true"/>
+                <property name="tint" type="color" value="#80ff0000"/>
+                <property name="music" type="file" value="audio/boss.ogg"/>
+                <property name="target" type="object" value="12"/>
+                <property name="notes">first line
+second line</property>
+            </properties>"##,
+        )
+        .unwrap();
+
+        assert_eq!(properties.string("name"), Some("Bat"));
+        assert_eq!(properties.get("count"), Some(&PropertyValue::Int(3)));
+        assert_eq!(properties.float("speed").unwrap(), 1.5);
+        assert!(properties.flag("enabled"));
+        assert_eq!(
+            properties.get("tint"),
+            Some(&PropertyValue::Color(vec4(1.0, 0.0, 0.0, 128.0 / 255.0)))
+        );
+        assert_eq!(
+            properties.get("music"),
+            Some(&PropertyValue::File(PathBuf::from("audio/boss.ogg")))
+        );
+        assert_eq!(properties.get("target"), Some(&PropertyValue::Object(12)));
+        assert_eq!(properties.string("notes"), Some("first line\nsecond line"));
+    }
+
+    #[test]
+    fn class_properties_have_members() {
+        let properties = parse(
+            r#"<properties>
+                <property name="arena" type="class" propertytype="Arena">
+                    <properties>
+                        <property name="width" type="float" value="17"/>
+                    </properties>
+                </property>
+                <property name="after" value="read"/>
+            </properties>"#,
+        )
+        .unwrap();
+
+        match properties.get("arena") {
+            Some(PropertyValue::Class { class, members }) => {
+                assert_eq!(class, "Arena");
+                assert_eq!(members.float("width").unwrap(), 17.0);
+            }
+            other => panic!("Expected a class property, got {:?}", other),
+        }
+        assert_eq!(properties.string("after"), Some("read"));
+    }
+
+    #[test]
+    fn untyped_values_are_parsed_by_typed_accessors() {
+        let properties =
+            parse(r#"<properties><property name="width" value="10"/></properties>"#).unwrap();
+        assert_eq!(properties.float("width").unwrap(), 10.0);
+        assert_eq!(properties.int("width").unwrap(), 10);
+        assert!(properties.bool("width").is_err());
+    }
+
+    #[test]
+    fn errors_name_element_and_property() {
+        let error =
+            parse(r#"<properties><property name="count" type="int" value="many"/></properties>"#)
+                .unwrap_err();
+        let message = format!("{:#}", error);
+        assert!(message.contains("\"count\""), "{}", message);
+        assert!(message.contains("<test>"), "{}", message);
+
+        let properties = Properties::new("<layer name=\"Level\">");
+        let message = format!("{:#}", properties.float("arena_width").unwrap_err());
+        assert!(message.contains("<layer name=\"Level\">"), "{}", message);
+        assert!(message.contains("\"arena_width\""), "{}", message);
+    }
+}
@@ -0,0 +1,487 @@
+use std::{
+    fs::File,
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::Path,
+    time::Duration,
+};
+
+use anyhow::*;
+use structopt::StructOpt;
+
+use crate::{
+    entities::firebrand::FirebrandInput, input::ButtonState, state::events::UiCommand, Options,
+};
+
+// ---------------------------------------------------------------------------------------------------------------------
+
+/// Bump this whenever the replay file format, or the simulation in a way which breaks
+/// reproducibility of existing replays, changes.
+pub const REPLAY_FORMAT_VERSION: u32 = 3;
+
+const REPLAY_FILE_MAGIC: &str = "platformer-replay";
+
+// ---------------------------------------------------------------------------------------------------------------------
+
+/// A single simulation step of a replay
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReplayFrame {
+    pub real_delta_time: Duration,
+    pub game_delta_time: Duration,
+    /// The input firebrand acted on this frame, or None if firebrand didn't exist.
+    pub firebrand_input: Option<FirebrandInput>,
+    /// Commands the player issued through the game's UI before this frame, e.g., restarting
+    pub ui_commands: Vec<UiCommand>,
+}
+
+/// The camera viewport a session was played with. Entity visibility and spawning depend on it,
+/// so playback uses it regardless of the window's size or the player's preferred zoom.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Viewport {
+    /// Size, in pixels, of the window the game was rendered to
+    pub width: u32,
+    pub height: u32,
+    /// The camera's scale, i.e., its zoom
+    pub scale: f32,
+}
+
+/// A Replay captures everything needed to reproduce a play session: the options the game was
+/// launched with, the rng seed, the camera viewport, and per-frame delta times and firebrand's
+/// input.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Replay {
+    pub seed: Option<u64>,
+    pub options: Vec<String>,
+    pub viewport: Option<Viewport>,
+    pub frames: Vec<ReplayFrame>,
+}
+
+impl Replay {
+    pub fn new(options: &Options, seed: Option<u64>) -> Self {
+        Self {
+            seed,
+            options: options.to_args(),
+            viewport: None,
+            frames: Vec::new(),
+        }
+    }
+
+    /// Returns the Options this replay was recorded with.
+    pub fn options(&self) -> Result<Options> {
+        Options::from_iter_safe(
+            std::iter::once("platformer".to_string()).chain(self.options.iter().cloned()),
+        )
+        .context("Replay options are not valid")
+    }
+
+    pub fn push_frame(&mut self, frame: ReplayFrame) {
+        self.frames.push(frame);
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let file = File::open(path).with_context(|| format!("Unable to open replay {:?}", path))?;
+        Self::read(BufReader::new(file))
+            .with_context(|| format!("Unable to read replay {:?}", path))
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        let file =
+            File::create(path).with_context(|| format!("Unable to create replay {:?}", path))?;
+        let mut writer = BufWriter::new(file);
+        self.write(&mut writer)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    fn write<W: Write>(&self, writer: &mut W) -> Result<()> {
+        writeln!(writer, "{} {}", REPLAY_FILE_MAGIC, REPLAY_FORMAT_VERSION)?;
+        match self.seed {
+            Some(seed) => writeln!(writer, "seed {}", seed)?,
+            None => writeln!(writer, "seed none")?,
+        }
+        for option in &self.options {
+            writeln!(writer, "option {}", option)?;
+        }
+        if let Some(viewport) = self.viewport {
+            writeln!(
+                writer,
+                "viewport {} {} {}",
+                viewport.width, viewport.height, viewport.scale
+            )?;
+        }
+        for frame in &self.frames {
+            let input = match frame.firebrand_input {
+                Some(input) => [input.move_left, input.move_right, input.jump, input.fire]
+                    .iter()
+                    .map(|b| encode_button_state(*b))
+                    .collect::<String>(),
+                None => "-".to_string(),
+            };
+            let commands = frame
+                .ui_commands
+                .iter()
+                .map(|command| format!(" {}", encode_ui_command(*command)))
+                .collect::<String>();
+            writeln!(
+                writer,
+                "frame {} {} {}{}",
+                frame.real_delta_time.as_micros(),
+                frame.game_delta_time.as_micros(),
+                input,
+                commands
+            )?;
+        }
+        Ok(())
+    }
+
+    fn read<R: BufRead>(reader: R) -> Result<Self> {
+        let mut lines = reader.lines().enumerate();
+
+        let (_, header) = lines.next().context("Replay file is empty")?;
+        let header = header?;
+        let version = header
+            .strip_prefix(REPLAY_FILE_MAGIC)
+            .context("Replay file has no header; is this a replay?")?
+            .trim()
+            .parse::<u32>()
+            .context("Expected replay version number in header")?;
+        if version != REPLAY_FORMAT_VERSION {
+            bail!(
+                "Replay version {} is not supported, expected version {}",
+                version,
+                REPLAY_FORMAT_VERSION
+            );
+        }
+
+        let mut replay = Replay {
+            seed: None,
+            options: Vec::new(),
+            viewport: None,
+            frames: Vec::new(),
+        };
+
+        for (idx, line) in lines {
+            let line = line?;
+            let line_number = idx + 1;
+            let (tag, value) = line.split_once(' ').unwrap_or((line.as_str(), ""));
+            match tag {
+                "seed" => {
+                    replay.seed = match value {
+                        "none" => None,
+                        v => Some(v.parse().with_context(|| {
+                            format!("Expected seed on line {} to be a number", line_number)
+                        })?),
+                    };
+                }
+                "option" => replay.options.push(value.to_string()),
+                "viewport" => {
+                    replay.viewport =
+                        Some(parse_viewport(value).with_context(|| {
+                            format!("Malformed viewport on line {}", line_number)
+                        })?)
+                }
+                "frame" => replay.frames.push(
+                    parse_frame(value)
+                        .with_context(|| format!("Malformed frame on line {}", line_number))?,
+                ),
+                "" => {}
+                _ => bail!(
+                    "Unrecognized replay entry \"{}\" on line {}",
+                    tag,
+                    line_number
+                ),
+            }
+        }
+
+        if replay.viewport.is_none() {
+            bail!("Replay has no viewport");
+        }
+        Ok(replay)
+    }
+}
+
+fn parse_viewport(value: &str) -> Result<Viewport> {
+    let mut components = value.split_whitespace();
+    let mut next = || {
+        components
+            .next()
+            .context("Expected width, height and scale")
+    };
+    let width = next()?.parse().context("Expected width to be a number")?;
+    let height = next()?.parse().context("Expected height to be a number")?;
+    let scale = next()?.parse().context("Expected scale to be a number")?;
+    Ok(Viewport {
+        width,
+        height,
+        scale,
+    })
+}
+
+fn parse_frame(value: &str) -> Result<ReplayFrame> {
+    let mut components = value.split_whitespace();
+    let mut next_duration = || -> Result<Duration> {
+        let micros = components
+            .next()
+            .context("Expected delta time")?
+            .parse::<u64>()
+            .context("Expected delta time to be microseconds")?;
+        Ok(Duration::from_micros(micros))
+    };
+    let real_delta_time = next_duration()?;
+    let game_delta_time = next_duration()?;
+
+    let input = components.next().context("Expected firebrand input")?;
+    let firebrand_input = if input == "-" {
+        None
+    } else {
+        let buttons = input
+            .chars()
+            .map(decode_button_state)
+            .collect::<Result<Vec<_>>>()?;
+        if buttons.len() != 4 {
+            bail!("Expected 4 button states, got {}", buttons.len());
+        }
+        Some(FirebrandInput {
+            move_left: buttons[0],
+            move_right: buttons[1],
+            jump: buttons[2],
+            fire: buttons[3],
+        })
+    };
+
+    let ui_commands = components
+        .map(decode_ui_command)
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(ReplayFrame {
+        real_delta_time,
+        game_delta_time,
+        firebrand_input,
+        ui_commands,
+    })
+}
+
+fn encode_ui_command(command: UiCommand) -> &'static str {
+    match command {
+        UiCommand::NewGame => "new_game",
+        UiCommand::RestartFromCheckpoint => "restart",
+        UiCommand::CyclePalette { forward: true } => "next_palette",
+        UiCommand::CyclePalette { forward: false } => "previous_palette",
+        UiCommand::ContinueGame {
+            from_last_checkpoint: true,
+        } => "continue_from_checkpoint",
+        UiCommand::ContinueGame {
+            from_last_checkpoint: false,
+        } => "continue_from_start",
+        UiCommand::QuitGame => "quit",
+    }
+}
+
+fn decode_ui_command(command: &str) -> Result<UiCommand> {
+    Ok(match command {
+        "new_game" => UiCommand::NewGame,
+        "restart" => UiCommand::RestartFromCheckpoint,
+        "next_palette" => UiCommand::CyclePalette { forward: true },
+        "previous_palette" => UiCommand::CyclePalette { forward: false },
+        "continue_from_checkpoint" => UiCommand::ContinueGame {
+            from_last_checkpoint: true,
+        },
+        "continue_from_start" => UiCommand::ContinueGame {
+            from_last_checkpoint: false,
+        },
+        "quit" => UiCommand::QuitGame,
+        _ => bail!("Unrecognized ui command \"{}\"", command),
+    })
+}
+
+fn encode_button_state(state: ButtonState) -> char {
+    match state {
+        ButtonState::Pressed => 'P',
+        ButtonState::Down => 'D',
+        ButtonState::Released => 'R',
+        ButtonState::Up => 'U',
+    }
+}
+
+fn decode_button_state(c: char) -> Result<ButtonState> {
+    match c {
+        'P' => Ok(ButtonState::Pressed),
+        'D' => Ok(ButtonState::Down),
+        'R' => Ok(ButtonState::Released),
+        'U' => Ok(ButtonState::Up),
+        _ => bail!("Unrecognized button state '{}'", c),
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+
+/// Feeds the frames of a Replay back, one at a time.
+pub struct ReplayPlayback {
+    replay: Replay,
+    cursor: usize,
+}
+
+impl ReplayPlayback {
+    pub fn new(replay: Replay) -> Self {
+        Self { replay, cursor: 0 }
+    }
+
+    pub fn replay(&self) -> &Replay {
+        &self.replay
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.cursor >= self.replay.frames.len()
+    }
+
+    pub fn next_frame(&mut self) -> Option<ReplayFrame> {
+        let frame = self.replay.frames.get(self.cursor).cloned();
+        if frame.is_some() {
+            self.cursor += 1;
+        }
+        frame
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod replay_tests {
+    use super::*;
+
+    fn options(args: &[&str]) -> Options {
+        Options::from_iter(std::iter::once("platformer").chain(args.iter().copied()))
+    }
+
+    #[test]
+    fn replay_round_trips_through_file_format() {
+        let mut replay = Replay::new(&options(&["--lives", "5", "-p", "mist"]), Some(1234));
+        replay.viewport = Some(Viewport {
+            width: 1280,
+            height: 720,
+            scale: 18.3,
+        });
+        replay.push_frame(ReplayFrame {
+            real_delta_time: Duration::from_micros(16_667),
+            game_delta_time: Duration::from_micros(0),
+            firebrand_input: None,
+            ui_commands: vec![],
+        });
+        replay.push_frame(ReplayFrame {
+            real_delta_time: Duration::from_micros(16_000),
+            game_delta_time: Duration::from_micros(16_000),
+            firebrand_input: Some(FirebrandInput {
+                move_left: ButtonState::Up,
+                move_right: ButtonState::Pressed,
+                jump: ButtonState::Down,
+                fire: ButtonState::Released,
+            }),
+            ui_commands: vec![
+                UiCommand::RestartFromCheckpoint,
+                UiCommand::CyclePalette { forward: false },
+                UiCommand::ContinueGame {
+                    from_last_checkpoint: true,
+                },
+            ],
+        });
+
+        let mut buffer = Vec::new();
+        replay.write(&mut buffer).unwrap();
+        let loaded = Replay::read(buffer.as_slice()).unwrap();
+
+        assert_eq!(loaded, replay);
+        let loaded_options = loaded.options().unwrap();
+        assert_eq!(loaded_options.lives, 5);
+        assert_eq!(loaded_options.palette, "mist");
+    }
+
+    #[test]
+    fn options_round_trip_through_args() {
+        let recorded = options(&[
+            "--debug-overlay",
+            "--gameboy",
+            "--campaign",
+            "test_fixtures/campaign.manifest",
+            "--level",
+            "1",
+            "--checkpoint",
+            "2",
+            "--lives",
+            "5",
+            "--vials",
+            "3",
+            "--continues",
+            "1",
+            "--lives-remaining",
+            "4",
+            "--continues-remaining",
+            "0",
+            "--palette",
+            "nymph",
+            "--no-sludgy",
+            "--no-music",
+            "--simulation-rate",
+            "120",
+        ]);
+        let replay = Replay::new(&recorded, None);
+        let replayed = replay.options().unwrap();
+        assert_eq!(replayed.to_args(), recorded.to_args());
+
+        let Options {
+            debug_overlay,
+            gameboy,
+            campaign,
+            level,
+            checkpoint,
+            lives,
+            vials,
+            continues,
+            lives_remaining,
+            continues_remaining,
+            palette,
+            no_sludgy,
+            no_music,
+            simulation_rate,
+            ..
+        } = replayed;
+        assert!(debug_overlay && gameboy && no_sludgy && no_music);
+        assert_eq!(campaign, recorded.campaign);
+        assert_eq!(
+            (level, checkpoint, lives, vials, continues),
+            (1, Some(2), 5, 3, 1)
+        );
+        assert_eq!((lives_remaining, continues_remaining), (Some(4), Some(0)));
+        assert_eq!(palette, "nymph");
+        assert_eq!(simulation_rate, 120);
+    }
+
+    #[test]
+    fn replay_without_viewport_is_rejected() {
+        let data = format!(
+            "{} {}\nseed 1\nframe 16000 16000 -\n",
+            REPLAY_FILE_MAGIC, REPLAY_FORMAT_VERSION
+        );
+        assert!(Replay::read(data.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn replay_with_unsupported_version_is_rejected() {
+        let data = format!("{} {}\n", REPLAY_FILE_MAGIC, REPLAY_FORMAT_VERSION + 1);
+        assert!(Replay::read(data.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn malformed_frame_is_rejected() {
+        let data = format!(
+            "{} {}\nviewport 160 144 10\nframe 16000 16000 UUX\n",
+            REPLAY_FILE_MAGIC, REPLAY_FORMAT_VERSION
+        );
+        assert!(Replay::read(data.as_bytes()).is_err());
+        let data = format!(
+            "{} {}\nviewport 160 144 10\nframe 16000 16000 - jump\n",
+            REPLAY_FILE_MAGIC, REPLAY_FORMAT_VERSION
+        );
+        assert!(Replay::read(data.as_bytes()).is_err());
+    }
+}
@@ -0,0 +1,343 @@
+use std::{
+    fs,
+    io::BufRead,
+    path::{Path, PathBuf},
+};
+
+use anyhow::*;
+
+use crate::{settings::Settings, state::game_controller::GameController, util, Options};
+
+// ---------------------------------------------------------------------------------------------------------------------
+
+/// The current save file format version; save files from newer versions are rejected.
+pub const SAVE_VERSION: u32 = 2;
+
+const SAVE_FILE_NAME: &str = "save.txt";
+
+/// Returns the default save file path, in the user's data directory, or None if the
+/// user's data directory can't be determined.
+pub fn default_save_path() -> Option<PathBuf> {
+    util::user_data_dir().map(|dir| dir.join(util::APP_DIR_NAME).join(SAVE_FILE_NAME))
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+
+/// A player's progress through the campaign; their preferences are kept in the settings file.
+/// Saved as a line oriented text file of `<key> <value>` lines, the first of which is the format
+/// version:
+///
+/// ```text
+/// version 2
+/// level 0
+/// checkpoint 1
+/// lives 2
+/// continues 1
+/// vials 3
+/// levels_completed 0
+/// ```
+///
+/// Version 1 saves also recorded the palette, gameboy, sludgy and music settings; see
+/// `Version1Preferences`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SaveGame {
+    /// Index of the campaign level being played
+    pub level: u32,
+    /// The highest checkpoint reached in `level`
+    pub checkpoint: u32,
+    pub lives: u32,
+    /// Continues the player may still use after a game over; saves written before continues were
+    /// recorded leave this unset, resuming with the session's full allowance
+    pub continues: Option<u32>,
+    pub vials: u32,
+    /// Number of campaign levels the player has completed
+    pub levels_completed: u32,
+    /// The preferences recorded by a version 1 save, to be moved to the settings file
+    pub version_1_preferences: Option<Version1Preferences>,
+}
+
+/// The preferences version 1 saves recorded along with progress, which are now kept in the
+/// settings file. The gameboy preference has no setting, so is dropped.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Version1Preferences {
+    pub palette: String,
+    pub sludgy: bool,
+    pub music: bool,
+}
+
+impl Version1Preferences {
+    /// Moves the preferences to `settings`: the palette, and sludge and music if they were off.
+    pub fn migrate(&self, settings: &mut Settings) {
+        settings.palette = Some(self.palette.clone());
+        if !self.sludgy {
+            settings.lcd.sludge = None;
+        }
+        if !self.music {
+            settings.music_volume = 0.0;
+        }
+    }
+}
+
+impl SaveGame {
+    /// Captures the progress tracked by `game_controller`.
+    pub fn new(game_controller: &GameController) -> Self {
+        Self {
+            level: game_controller.current_level(),
+            checkpoint: game_controller.current_checkpoint(),
+            lives: game_controller.lives_remaining(),
+            continues: Some(game_controller.continues_remaining()),
+            vials: game_controller.vials(),
+            levels_completed: game_controller.levels_completed(),
+            version_1_preferences: None,
+        }
+    }
+
+    /// Configures `options` to resume play from this save. `options.lives` is left as the lives
+    /// each game, and each continue, starts with.
+    pub fn apply(&self, options: &mut Options) {
+        options.level = self.level;
+        options.checkpoint = Some(self.checkpoint);
+        options.lives_remaining = Some(self.lives);
+        options.continues_remaining = self.continues;
+        options.vials = self.vials;
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let file =
+            fs::File::open(path).with_context(|| format!("Unable to open save file {:?}", path))?;
+        Self::read(std::io::BufReader::new(file))
+            .with_context(|| format!("Unable to read save file {:?}", path))
+    }
+
+    /// Writes the save to `path`, creating its directory if needed, without corrupting an
+    /// existing save if the write fails.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        util::write_file_atomically(path, &self.to_string())
+            .with_context(|| format!("Unable to write save file {:?}", path))
+    }
+
+    fn read<R: BufRead>(reader: R) -> Result<Self> {
+        let mut version = None;
+        let mut save = SaveGame {
+            level: 0,
+            checkpoint: 0,
+            lives: 0,
+            continues: None,
+            vials: 0,
+            levels_completed: 0,
+            version_1_preferences: None,
+        };
+        let mut has_lives = false;
+        let mut preferences = Version1Preferences {
+            palette: String::new(),
+            sludgy: true,
+            music: true,
+        };
+
+        for (idx, line) in reader.lines().enumerate() {
+            let line = line?;
+            let line_number = idx + 1;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+            let value = value.trim();
+            if value.is_empty() {
+                bail!("Expected a value for \"{}\" on line {}", key, line_number);
+            }
+
+            let number = || {
+                value.parse::<u32>().with_context(|| {
+                    format!("Expected {} on line {} to be a number", key, line_number)
+                })
+            };
+            let flag = || {
+                value.parse::<bool>().with_context(|| {
+                    format!(
+                        "Expected {} on line {} to be true or false",
+                        key, line_number
+                    )
+                })
+            };
+
+            if version.is_none() {
+                if key != "version" {
+                    bail!("Expected the save file to begin with its version");
+                }
+                let v = number()?;
+                if v > SAVE_VERSION {
+                    bail!(
+                        "Save file version {} is newer than this game's version {}",
+                        v,
+                        SAVE_VERSION
+                    );
+                }
+                version = Some(v);
+                continue;
+            }
+
+            match key {
+                "level" => save.level = number()?,
+                "checkpoint" => save.checkpoint = number()?,
+                "lives" => {
+                    save.lives = number()?;
+                    has_lives = true;
+                }
+                "continues" => save.continues = Some(number()?),
+                "vials" => save.vials = number()?,
+                "levels_completed" => save.levels_completed = number()?,
+                "palette" if version == Some(1) => preferences.palette = value.to_string(),
+                "gameboy" if version == Some(1) => {
+                    flag()?;
+                }
+                "sludgy" if version == Some(1) => preferences.sludgy = flag()?,
+                "music" if version == Some(1) => preferences.music = flag()?,
+                _ => bail!(
+                    "Unrecognized save entry \"{}\" on line {}",
+                    key,
+                    line_number
+                ),
+            }
+        }
+
+        if version.is_none() {
+            bail!("Save file is empty");
+        }
+        if !has_lives {
+            bail!("Save file is incomplete");
+        }
+        if version == Some(1) {
+            if preferences.palette.is_empty() {
+                bail!("Save file is incomplete");
+            }
+            save.version_1_preferences = Some(preferences);
+        }
+        Ok(save)
+    }
+}
+
+impl std::fmt::Display for SaveGame {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "version {}", SAVE_VERSION)?;
+        writeln!(f, "level {}", self.level)?;
+        writeln!(f, "checkpoint {}", self.checkpoint)?;
+        writeln!(f, "lives {}", self.lives)?;
+        if let Some(continues) = self.continues {
+            writeln!(f, "continues {}", continues)?;
+        }
+        writeln!(f, "vials {}", self.vials)?;
+        writeln!(f, "levels_completed {}", self.levels_completed)
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod save_tests {
+    use super::*;
+    use structopt::StructOpt;
+
+    fn options(args: &[&str]) -> Options {
+        Options::from_iter(std::iter::once("platformer").chain(args.iter().copied()))
+    }
+
+    #[test]
+    fn save_round_trips_through_file() {
+        let game_controller = GameController::new(2, 3, 1, 0, 1);
+        let save = SaveGame::new(&game_controller);
+        assert_eq!(save.lives, 2);
+        assert_eq!(save.vials, 3);
+        assert_eq!(save.checkpoint, 1);
+
+        let path = std::env::temp_dir()
+            .join(format!("platformer-test-save-{}", std::process::id()))
+            .join(SAVE_FILE_NAME);
+        save.save(&path).unwrap();
+        assert_eq!(SaveGame::load(&path).unwrap(), save);
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn continue_applies_save_to_options() {
+        let save = SaveGame::read(
+            "version 2\nlevel 0\ncheckpoint 2\nlives 1\ncontinues 0\nvials 4\nlevels_completed 0\n"
+                .as_bytes(),
+        )
+        .unwrap();
+        let mut options = options(&["--palette", "mist"]);
+        save.apply(&mut options);
+        assert_eq!(options.checkpoint, Some(2));
+        assert_eq!(options.lives_remaining, Some(1));
+        assert_eq!(options.continues_remaining, Some(0));
+        // the starting lives are kept for game overs and continues
+        assert_eq!(options.lives, 3);
+        assert_eq!(options.vials, 4);
+        assert_eq!(options.palette, "mist");
+    }
+
+    #[test]
+    fn version_1_saves_move_their_preferences_to_settings() {
+        let save = SaveGame::read(
+            "version 1\nlevel 0\ncheckpoint 2\nlives 1\nvials 4\n\
+             levels_completed 0\npalette nymph\ngameboy true\nsludgy false\nmusic false\n"
+                .as_bytes(),
+        )
+        .unwrap();
+        let mut options = options(&["--palette", "mist"]);
+        save.apply(&mut options);
+        assert_eq!(options.checkpoint, Some(2));
+        assert_eq!(options.palette, "mist");
+        assert!(!options.gameboy);
+
+        let preferences = save.version_1_preferences.as_ref().unwrap();
+        let mut settings = Settings::default();
+        preferences.migrate(&mut settings);
+        assert_eq!(settings.palette.as_deref(), Some("nymph"));
+        assert_eq!(settings.lcd.sludge, None);
+        assert_eq!(settings.music_volume, 0.0);
+
+        // once migrated, the save is rewritten without them
+        let save = SaveGame::read(save.to_string().as_bytes()).unwrap();
+        assert_eq!(save.version_1_preferences, None);
+
+        assert!(SaveGame::read("version 1\nlives 1\n".as_bytes()).is_err());
+        assert!(
+            SaveGame::read("version 1\nlives 1\npalette mist\nmusic loud\n".as_bytes()).is_err()
+        );
+        assert!(SaveGame::read("version 2\nlives 1\npalette nymph\n".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn version_1_preferences_which_were_on_keep_the_settings() {
+        let save = SaveGame::read(
+            "version 1\nlives 1\npalette mist\ngameboy false\nsludgy true\nmusic true\n".as_bytes(),
+        )
+        .unwrap();
+        let mut settings = Settings {
+            music_volume: 0.5,
+            ..Default::default()
+        };
+        save.version_1_preferences.unwrap().migrate(&mut settings);
+        assert_eq!(settings.palette.as_deref(), Some("mist"));
+        assert_eq!(settings.lcd.sludge, Settings::default().lcd.sludge);
+        assert_eq!(settings.music_volume, 0.5);
+    }
+
+    #[test]
+    fn saves_without_continues_keep_the_full_allowance() {
+        let save = SaveGame::read("version 2\nlives 2\n".as_bytes()).unwrap();
+        let mut options = options(&[]);
+        save.apply(&mut options);
+        assert_eq!(options.continues_remaining, None);
+    }
+
+    #[test]
+    fn unsupported_saves_are_rejected() {
+        assert!(SaveGame::read("level 0\nlives 3\npalette gameboy\n".as_bytes()).is_err());
+        assert!(SaveGame::read("version 99\nlives 3\npalette gameboy\n".as_bytes()).is_err());
+        assert!(SaveGame::read("version 2\nlevel 0\n".as_bytes()).is_err());
+        assert!(SaveGame::read("version 2\nlives three\n".as_bytes()).is_err());
+    }
+}
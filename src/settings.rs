@@ -0,0 +1,444 @@
+use std::{
+    fs,
+    io::BufRead,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use anyhow::*;
+use structopt::clap::ArgMatches;
+
+use crate::{input_map::Bindings, state::lcd_filter::LcdFilter, util, Options};
+
+// ---------------------------------------------------------------------------------------------------------------------
+
+const SETTINGS_FILE_NAME: &str = "settings.txt";
+
+/// Returns the default settings file path, in the user's configuration directory, or None if
+/// the user's configuration directory can't be determined.
+pub fn default_settings_path() -> Option<PathBuf> {
+    util::user_config_dir().map(|dir| dir.join(util::APP_DIR_NAME).join(SETTINGS_FILE_NAME))
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+
+/// Tunables of the LCD filter; unset tunables keep the filter's defaults.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LcdSettings {
+    /// Time LCD pixels take to change state; if None, pixels change instantly
+    pub sludge: Option<Duration>,
+    pub lcd_shadow_alpha: Option<f32>,
+    pub frame_shadow_alpha: Option<f32>,
+    pub column_bleed_power: Option<f32>,
+    pub column_bleed_alpha: Option<f32>,
+    pub noisiness: Option<f32>,
+    pub clarity: Option<f32>,
+}
+
+impl Default for LcdSettings {
+    fn default() -> Self {
+        Self {
+            sludge: Some(LcdFilter::DEFAULT_HYSTERESIS),
+            lcd_shadow_alpha: None,
+            frame_shadow_alpha: None,
+            column_bleed_power: None,
+            column_bleed_alpha: None,
+            noisiness: None,
+            clarity: None,
+        }
+    }
+}
+
+/// Which settings the command line overrides for the session. Overridden settings aren't applied
+/// from the settings file, nor are changes to them written back to it.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Overrides {
+    /// --palette
+    pub palette: bool,
+    /// --no-sludgy, which disables the LCD's sludge
+    pub sludge: bool,
+    /// --no-music, which mutes the music
+    pub music_volume: bool,
+    /// --gameboy, which sizes the window, and zooms the camera, to the original gameboy viewport
+    pub window: bool,
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+
+/// The player's preferences, which persist between sessions. Saved as a line oriented text file
+/// of `<key> <value>` lines, followed by optional input bindings in the bindings file format:
+///
+/// ```text
+/// palette mist
+/// music_volume 0.8
+/// sfx_volume 1
+/// window_size 1280 720
+/// fullscreen false
+/// zoom 18.5
+/// lcd_sludge 65
+/// lcd_shadow_alpha 0.375
+/// lcd_frame_shadow_alpha 0.5
+/// lcd_column_bleed_power 0.0625
+/// lcd_column_bleed_alpha 0.25
+/// lcd_noisiness 0.0125
+/// lcd_clarity 0.75
+/// stick_deadzone 0.25
+/// stick_hysteresis 0.1
+/// jump KeyW South
+/// ```
+///
+/// `lcd_sludge` is in milliseconds, or "off". Unset entries keep the game's defaults. If any
+/// bindings are present they replace the default bindings file, unless --input-bindings is set.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Settings {
+    /// Palette to use, unless --palette is set
+    pub palette: Option<String>,
+    pub lcd: LcdSettings,
+    /// Volume, from 0 to 1, of the music
+    pub music_volume: f32,
+    /// Volume, from 0 to 1, of sound effects
+    pub sfx_volume: f32,
+    /// Logical size of the window, when not in gameboy mode
+    pub window_size: Option<(u32, u32)>,
+    pub fullscreen: bool,
+    /// Camera scale, when not in gameboy mode
+    pub zoom: Option<f32>,
+    pub bindings: Option<Bindings>,
+    /// Set by `apply`; not saved
+    pub overrides: Overrides,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            palette: None,
+            lcd: LcdSettings::default(),
+            music_volume: 1.0,
+            sfx_volume: 1.0,
+            window_size: None,
+            fullscreen: false,
+            zoom: None,
+            bindings: None,
+            overrides: Overrides::default(),
+        }
+    }
+}
+
+impl Settings {
+    /// Loads the settings at `path`; if there's no file at `path`, the default settings are returned.
+    pub fn load(path: &Path) -> Result<Self> {
+        let file = match fs::File::open(path) {
+            Result::Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => {
+                return Err(e).with_context(|| format!("Unable to open settings file {:?}", path))
+            }
+        };
+        Self::read(std::io::BufReader::new(file))
+            .with_context(|| format!("Unable to read settings file {:?}", path))
+    }
+
+    /// Writes the settings to `path`, creating its directory if needed, without corrupting
+    /// existing settings if the write fails.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        util::write_file_atomically(path, &self.to_string())
+            .with_context(|| format!("Unable to write settings file {:?}", path))
+    }
+
+    /// Applies the settings to `options`, and records which settings are overridden by those set
+    /// explicitly on the command line, per `matches`.
+    pub fn apply(&mut self, options: &mut Options, matches: &ArgMatches) {
+        let is_set = |name: &str| matches.occurrences_of(name) > 0;
+        self.overrides = Overrides {
+            palette: is_set("palette"),
+            sludge: is_set("no-sludgy"),
+            music_volume: is_set("no-music"),
+            window: is_set("gameboy"),
+        };
+
+        if let Some(palette) = &self.palette {
+            if !self.overrides.palette {
+                options.palette = palette.clone();
+            }
+        }
+    }
+
+    /// Returns the settings in effect for the session, i.e., these settings with those the
+    /// command line overrides replaced by what the command line asks for.
+    pub fn session(&self) -> Settings {
+        let mut session = self.clone();
+        if self.overrides.sludge {
+            session.lcd.sludge = None;
+        }
+        if self.overrides.music_volume {
+            session.music_volume = 0.0;
+        }
+        if self.overrides.window {
+            session.window_size = None;
+            session.fullscreen = false;
+            session.zoom = None;
+        }
+        session
+    }
+
+    fn read<R: BufRead>(reader: R) -> Result<Self> {
+        let mut settings = Settings::default();
+        let mut bindings = Bindings::default();
+        let mut has_bindings = false;
+
+        for (idx, line) in reader.lines().enumerate() {
+            let line = line?;
+            let line_number = idx + 1;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let mut tokens = line.split_whitespace();
+            let key = tokens.next().unwrap_or_default();
+            let values = tokens.collect::<Vec<_>>();
+
+            if bindings.read_entry(key, &values, line_number)? {
+                has_bindings = true;
+                continue;
+            }
+
+            let Some(value) = values.first().copied() else {
+                bail!("Expected a value for \"{}\" on line {}", key, line_number);
+            };
+            let number = |value: &str| {
+                value
+                    .parse::<f32>()
+                    .ok()
+                    .filter(|value| value.is_finite() && *value >= 0.0)
+                    .with_context(|| {
+                        format!(
+                            "Expected {} on line {} to be a positive number",
+                            key, line_number
+                        )
+                    })
+            };
+            let volume = || {
+                number(value)
+                    .ok()
+                    .filter(|volume| *volume <= 1.0)
+                    .with_context(|| {
+                        format!(
+                            "Expected {} on line {} to be a number from 0 to 1",
+                            key, line_number
+                        )
+                    })
+            };
+
+            match key {
+                "palette" => settings.palette = Some(value.to_string()),
+                "music_volume" => settings.music_volume = volume()?,
+                "sfx_volume" => settings.sfx_volume = volume()?,
+                "window_size" => {
+                    let size = match values[..] {
+                        [width, height] => width.parse::<u32>().ok().zip(height.parse().ok()),
+                        _ => None,
+                    };
+                    settings.window_size = Some(
+                        size.filter(|(width, height)| *width > 0 && *height > 0)
+                            .with_context(|| {
+                                format!(
+                                    "Expected window_size on line {} to be a width and height",
+                                    line_number
+                                )
+                            })?,
+                    );
+                }
+                "fullscreen" => {
+                    settings.fullscreen = value.parse::<bool>().with_context(|| {
+                        format!(
+                            "Expected fullscreen on line {} to be true or false",
+                            line_number
+                        )
+                    })?
+                }
+                "zoom" => settings.zoom = Some(number(value)?),
+                "lcd_sludge" => {
+                    settings.lcd.sludge = if value == "off" {
+                        None
+                    } else {
+                        Some(Duration::from_millis(value.parse().with_context(|| {
+                            format!(
+                                "Expected lcd_sludge on line {} to be milliseconds, or \"off\"",
+                                line_number
+                            )
+                        })?))
+                    }
+                }
+                "lcd_shadow_alpha" => settings.lcd.lcd_shadow_alpha = Some(number(value)?),
+                "lcd_frame_shadow_alpha" => settings.lcd.frame_shadow_alpha = Some(number(value)?),
+                "lcd_column_bleed_power" => settings.lcd.column_bleed_power = Some(number(value)?),
+                "lcd_column_bleed_alpha" => settings.lcd.column_bleed_alpha = Some(number(value)?),
+                "lcd_noisiness" => settings.lcd.noisiness = Some(number(value)?),
+                "lcd_clarity" => settings.lcd.clarity = Some(number(value)?),
+                _ => bail!("Unrecognized setting \"{}\" on line {}", key, line_number),
+            }
+        }
+
+        if has_bindings {
+            bindings.validate()?;
+            settings.bindings = Some(bindings);
+        }
+        Ok(settings)
+    }
+}
+
+impl std::fmt::Display for Settings {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(palette) = &self.palette {
+            writeln!(f, "palette {}", palette)?;
+        }
+        writeln!(f, "music_volume {}", self.music_volume)?;
+        writeln!(f, "sfx_volume {}", self.sfx_volume)?;
+        if let Some((width, height)) = self.window_size {
+            writeln!(f, "window_size {} {}", width, height)?;
+        }
+        writeln!(f, "fullscreen {}", self.fullscreen)?;
+        if let Some(zoom) = self.zoom {
+            writeln!(f, "zoom {}", zoom)?;
+        }
+        match self.lcd.sludge {
+            Some(sludge) => writeln!(f, "lcd_sludge {}", sludge.as_millis())?,
+            None => writeln!(f, "lcd_sludge off")?,
+        }
+        let tunables = [
+            ("lcd_shadow_alpha", self.lcd.lcd_shadow_alpha),
+            ("lcd_frame_shadow_alpha", self.lcd.frame_shadow_alpha),
+            ("lcd_column_bleed_power", self.lcd.column_bleed_power),
+            ("lcd_column_bleed_alpha", self.lcd.column_bleed_alpha),
+            ("lcd_noisiness", self.lcd.noisiness),
+            ("lcd_clarity", self.lcd.clarity),
+        ];
+        for (key, value) in tunables {
+            if let Some(value) = value {
+                writeln!(f, "{} {}", key, value)?;
+            }
+        }
+        if let Some(bindings) = &self.bindings {
+            write!(f, "{}", bindings)?;
+        }
+        std::result::Result::Ok(())
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod settings_tests {
+    use super::*;
+    use crate::{input::Action, input_map::Binding};
+    use structopt::StructOpt;
+
+    #[test]
+    fn settings_round_trip_through_file() {
+        let mut bindings = Bindings::default();
+        bindings.set(
+            Action::Jump,
+            vec![
+                Binding::Key(winit::keyboard::KeyCode::KeyW),
+                Binding::Button(gilrs::Button::South),
+            ],
+        );
+        let settings = Settings {
+            palette: Some("mist".to_string()),
+            lcd: LcdSettings {
+                sludge: None,
+                clarity: Some(0.5),
+                ..Default::default()
+            },
+            music_volume: 0.25,
+            sfx_volume: 0.75,
+            window_size: Some((1280, 720)),
+            fullscreen: true,
+            zoom: Some(18.5),
+            bindings: Some(bindings),
+            overrides: Overrides::default(),
+        };
+
+        let path = std::env::temp_dir()
+            .join(format!("platformer-test-settings-{}", std::process::id()))
+            .join(SETTINGS_FILE_NAME);
+        assert_eq!(Settings::load(&path).unwrap(), Settings::default());
+        settings.save(&path).unwrap();
+        assert_eq!(Settings::load(&path).unwrap(), settings);
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    fn apply(settings: &Settings, args: &[&str]) -> (Settings, Options) {
+        let matches = Options::clap()
+            .get_matches_from(std::iter::once("platformer").chain(args.iter().copied()));
+        let mut options = Options::from_clap(&matches);
+        let mut settings = settings.clone();
+        settings.apply(&mut options, &matches);
+        (settings, options)
+    }
+
+    #[test]
+    fn command_line_overrides_settings() {
+        let settings = Settings::read("palette nymph\nlcd_sludge 100\n".as_bytes()).unwrap();
+        assert_eq!(settings.lcd.sludge, Some(Duration::from_millis(100)));
+        assert!(settings.bindings.is_none());
+
+        assert_eq!(apply(&settings, &[]).1.palette, "nymph");
+        let (settings, options) = apply(&settings, &["--palette", "mist"]);
+        assert_eq!(options.palette, "mist");
+        assert!(settings.overrides.palette);
+        // the player's preferred palette is kept, to be saved
+        assert_eq!(settings.palette.as_deref(), Some("nymph"));
+    }
+
+    #[test]
+    fn no_sludgy_overrides_lcd_sludge() {
+        let settings = Settings::read("lcd_sludge 100\nlcd_clarity 0.5\n".as_bytes()).unwrap();
+        let session = apply(&settings, &[]).0.session();
+        assert_eq!(session.lcd.sludge, Some(Duration::from_millis(100)));
+
+        let (settings, _) = apply(&settings, &["--no-sludgy"]);
+        assert_eq!(settings.session().lcd.sludge, None);
+        assert_eq!(settings.session().lcd.clarity, Some(0.5));
+        assert_eq!(settings.lcd.sludge, Some(Duration::from_millis(100)));
+    }
+
+    #[test]
+    fn no_music_overrides_music_volume() {
+        let settings = Settings::read("music_volume 0.5\nsfx_volume 0.25\n".as_bytes()).unwrap();
+        assert_eq!(apply(&settings, &[]).0.session().music_volume, 0.5);
+
+        let (settings, _) = apply(&settings, &["--no-music"]);
+        assert_eq!(settings.session().music_volume, 0.0);
+        assert_eq!(settings.session().sfx_volume, 0.25);
+        assert_eq!(settings.music_volume, 0.5);
+    }
+
+    #[test]
+    fn gameboy_overrides_window_and_zoom() {
+        let settings =
+            Settings::read("window_size 1280 720\nfullscreen true\nzoom 18.5\n".as_bytes())
+                .unwrap();
+        let session = apply(&settings, &[]).0.session();
+        assert_eq!(session.window_size, Some((1280, 720)));
+        assert!(session.fullscreen);
+        assert_eq!(session.zoom, Some(18.5));
+
+        let (settings, _) = apply(&settings, &["--gameboy"]);
+        let session = settings.session();
+        assert_eq!(session.window_size, None);
+        assert!(!session.fullscreen);
+        assert_eq!(session.zoom, None);
+        assert_eq!(settings.zoom, Some(18.5));
+    }
+
+    #[test]
+    fn invalid_settings_are_rejected() {
+        assert!(Settings::read("music_volume 2\n".as_bytes()).is_err());
+        assert!(Settings::read("window_size 640\n".as_bytes()).is_err());
+        assert!(Settings::read("fullscreen yes\n".as_bytes()).is_err());
+        assert!(Settings::read("brightness 1\n".as_bytes()).is_err());
+        assert!(Settings::read("jump KeyW Shift\n".as_bytes()).is_err());
+    }
+}
@@ -18,6 +18,8 @@ pub struct Sprite {
     pub color: Vector4<f32>,
     pub mask: u32,
     pub entity_id: Option<u32>,
+    /// Index of the map tileset this sprite's tex coords refer to
+    pub tileset: usize,
     pub flipped_diagonally: bool,
     pub flipped_horizontally: bool,
     pub flipped_vertically: bool,
@@ -28,6 +30,7 @@ impl PartialEq for Sprite {
         self.collision_shape == other.collision_shape
             && self.entity_id == other.entity_id
             && self.mask == other.mask
+            && self.tileset == other.tileset
             && relative_eq!(self.origin, other.origin)
             && relative_eq!(self.extent, other.extent)
             && relative_eq!(self.tex_coord_origin, other.tex_coord_origin)
@@ -49,6 +52,7 @@ impl Hash for Sprite {
         hash_vec2(&self.tex_coord_extent, state);
         hash_vec4(&self.color, state);
         self.mask.hash(state);
+        self.tileset.hash(state);
         self.flipped_diagonally.hash(state);
         self.flipped_horizontally.hash(state);
         self.flipped_vertically.hash(state);
@@ -74,6 +78,7 @@ impl Default for Sprite {
             color: vec4(1.0, 1.0, 1.0, 1.0),
             mask: 0,
             entity_id: None,
+            tileset: 0,
             flipped_diagonally: false,
             flipped_horizontally: false,
             flipped_vertically: false,
@@ -101,6 +106,7 @@ impl Sprite {
             color,
             mask,
             entity_id: None,
+            tileset: 0,
             flipped_diagonally: false,
             flipped_horizontally: false,
             flipped_vertically: false,
@@ -126,6 +132,7 @@ impl Sprite {
             color,
             mask,
             entity_id: None,
+            tileset: 0,
             flipped_diagonally: false,
             flipped_horizontally: false,
             flipped_vertically: false,
@@ -163,6 +170,7 @@ impl Sprite {
             color: self.color,
             mask: self.mask,
             entity_id: self.entity_id,
+            tileset: self.tileset,
             flipped_diagonally: self.flipped_diagonally,
             flipped_horizontally: !self.flipped_horizontally,
             flipped_vertically: self.flipped_vertically,
@@ -180,6 +188,7 @@ impl Sprite {
             color: self.color,
             mask: self.mask,
             entity_id: self.entity_id,
+            tileset: self.tileset,
             flipped_diagonally: self.flipped_diagonally,
             flipped_horizontally: self.flipped_horizontally,
             flipped_vertically: !self.flipped_vertically,
@@ -199,6 +208,7 @@ impl Sprite {
             color: self.color,
             mask: self.mask,
             entity_id: self.entity_id,
+            tileset: self.tileset,
             flipped_diagonally: !self.flipped_diagonally,
             flipped_horizontally: self.flipped_horizontally,
             flipped_vertically: self.flipped_vertically,
@@ -1,3 +1,4 @@
+use anyhow::{Context, Result};
 use cgmath::{prelude::*, *};
 use core::panic;
 use std::hash::Hash;
@@ -489,6 +490,29 @@ impl Drawable {
         Self::new(vec![mesh], vec![material])
     }
 
+    /// Creates a Drawable for sprites drawn from a map's tilesets, with one mesh for the sprites of each
+    /// tileset, drawn with the material at that tileset's index in `materials`.
+    pub fn with_sprites(
+        sprites: &[Sprite],
+        materials: Vec<Rc<Material>>,
+        device: &wgpu::Device,
+        name: &str,
+    ) -> Self {
+        let mut sprites_by_tileset: Vec<Vec<Sprite>> = vec![vec![]; materials.len()];
+        for sprite in sprites {
+            sprites_by_tileset[sprite.tileset].push(*sprite);
+        }
+
+        let meshes = sprites_by_tileset
+            .iter()
+            .enumerate()
+            .filter(|(_, sprites)| !sprites.is_empty())
+            .map(|(tileset, sprites)| Mesh::new(sprites, tileset, device, name))
+            .collect();
+
+        Self::new(meshes, materials)
+    }
+
     pub fn new(meshes: Vec<Mesh>, materials: Vec<Rc<Material>>) -> Self {
         if materials.is_empty() {
             panic!("Attempted to create Drawable without materials")
@@ -564,14 +588,19 @@ impl EntityDrawable {
         device: &wgpu::Device,
         named: &str,
         mask: u32,
-    ) -> Self {
+    ) -> Result<Self> {
         let tiles = tileset.get_tiles_with_property("name", named);
 
         // collect all tiles for each cycle, and root tiles too
         let mut tiles_by_cycle: HashMap<&str, Vec<&tileset::Tile>> = HashMap::new();
         let mut root_tiles_by_cycle: HashMap<&str, &tileset::Tile> = HashMap::new();
         for tile in tiles {
-            let cycle = tile.get_property("cycle").unwrap();
+            let cycle = tile.get_property("cycle").with_context(|| {
+                format!(
+                    "Tile {} of sprite \"{}\" is missing the \"cycle\" property",
+                    tile.id, named
+                )
+            })?;
             tiles_by_cycle.entry(cycle).or_default().push(tile);
 
             if tile.get_property("role") == Some("root") {
@@ -612,7 +641,7 @@ impl EntityDrawable {
         }
 
         // Convert sprites to sprite meshes
-        Self::new(sprites_by_cycle, material, device)
+        Ok(Self::new(sprites_by_cycle, material, device))
     }
 
     pub fn new(
@@ -1,12 +1,32 @@
 use std::time;
 
 use anyhow::*;
-use winit::{event::WindowEvent, window::Window};
+use winit::{
+    event::{ElementState, KeyEvent, WindowEvent},
+    keyboard::PhysicalKey,
+    window::Window,
+};
 
-use crate::{audio::Audio, entity, event_dispatch, texture, Options};
+use crate::{
+    assets,
+    audio::Audio,
+    campaign::Campaign,
+    entity, event_dispatch,
+    file_watcher::FileWatcher,
+    gamepad::{GamepadEvent, Gamepads, Rumble},
+    input::Action,
+    input_map::{self, Binding, Bindings, InputMap},
+    palette::{self, Palette},
+    replay::{Replay, ReplayFrame, ReplayPlayback, Viewport},
+    save::SaveGame,
+    settings::Settings,
+    tileset, validation, Options,
+};
 
 use super::{
+    constants::DEFAULT_CAMERA_SCALE,
     debug_overlay::DebugOverlay,
+    events::{Event, UiCommand},
     game_controller::GameController,
     game_state::GameState,
     game_ui::GameUi,
@@ -16,12 +36,46 @@ use super::{
 
 // --------------------------------------------------------------------------------------------------------------------
 
-/// Holder for various AppState fields to pass in to GameController, GameUi, GameState update() methods
+/// Frame delta times longer than this are clamped, dropping simulation time rather than
+/// running a long burst of catch-up steps.
+const MAX_FRAME_DELTA_TIME: std::time::Duration = std::time::Duration::from_millis(250);
+
+/// When --hot-reload is set, res/ is checked for changes this often
+const HOT_RELOAD_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(500);
+
+const FIREBRAND_INJURY_RUMBLE: Rumble = Rumble {
+    strong: 0.6,
+    weak: 0.4,
+    duration: Some(std::time::Duration::from_millis(250)),
+};
+
+const BOSS_INJURY_RUMBLE: Rumble = Rumble {
+    strong: 0.0,
+    weak: 0.5,
+    duration: Some(std::time::Duration::from_millis(120)),
+};
+
+/// Settings changed in game are saved once they've gone unchanged this long, e.g., after the
+/// player stops dragging a slider, rather than on every change
+const SETTINGS_SAVE_DELAY: std::time::Duration = std::time::Duration::from_secs(1);
+
+/// Camera shakes rumble until they end
+const CAMERA_SHAKE_RUMBLE: Rumble = Rumble {
+    strong: 0.3,
+    weak: 0.2,
+    duration: None,
+};
+
+// --------------------------------------------------------------------------------------------------------------------
+
+/// Holder for various AppState fields to pass in to GameController, GameUi, GameState update() methods.
+/// `gpu` is None when the simulation is run headless.
 pub struct AppContext<'a> {
-    pub gpu: &'a mut GpuState,
+    pub gpu: Option<&'a mut GpuState>,
     pub audio: &'a mut Audio,
     pub message_dispatcher: &'a mut event_dispatch::Dispatcher,
     pub entity_id_vendor: &'a mut entity::IdVendor,
+    pub rng: &'a mut entity::GameRng,
     pub frame_idx: u32,
     pub time: std::time::Instant,
     pub game_delta_time: std::time::Duration,
@@ -32,17 +86,52 @@ pub struct AppContext<'a> {
 
 pub struct AppState {
     options: Options,
+    campaign: Campaign,
     audio: Audio,
     game_controller: GameController,
     game_state: GameState,
     game_ui: GameUi,
     debug_overlay: Option<DebugOverlay>,
     lcd_filter: LcdFilter,
+    input_map: InputMap,
+    gamepads: Gamepads,
+    quit_requested: bool,
+    // set if a level failed to load, ending the game as a failed startup does
+    error: Option<anyhow::Error>,
 
     entity_id_vendor: entity::IdVendor,
+    tilesets: tileset::TileSetCache,
     message_dispatcher: event_dispatch::Dispatcher,
+    rng: entity::GameRng,
+    // GameUi updates at render rate, outside the simulation, so it draws from its own rng
+    // rather than consuming the simulation's
+    ui_rng: entity::GameRng,
+
+    // replay recording (when --record is set) and playback (when --replay is set)
+    replay_recorder: Option<Replay>,
+    replay_playback: Option<ReplayPlayback>,
+    // while recording or playing back a replay, the camera keeps the replay's viewport
+    fixed_viewport: Option<Viewport>,
+
+    // progress is saved here, except during replay playback
+    save_path: Option<std::path::PathBuf>,
+
+    // settings changed in game are saved to settings_path, if set, once settings_changed_time
+    // is SETTINGS_SAVE_DELAY in the past
+    settings: Settings,
+    settings_path: Option<std::path::PathBuf>,
+    settings_changed_time: Option<std::time::Instant>,
+
+    // palettes the player cycles through: the builtin palettes, and any custom palette the
+    // game started with
+    palettes: Vec<String>,
+
+    // watches res/ for edits when --hot-reload is set
+    resource_watcher: Option<FileWatcher>,
+    last_resource_poll_time: std::time::Instant,
 
     last_render_time: std::time::Instant,
+    simulation_time_accumulator: std::time::Duration,
     frame_index: u32,
 
     // gpu is last; which means it's last to be destructed. This prevents a crash during shutdown (sigh)
@@ -50,13 +139,43 @@ pub struct AppState {
 }
 
 impl AppState {
-    pub fn new(window: winit::window::Window, options: Options) -> Result<Self> {
+    pub fn new(
+        window: winit::window::Window,
+        options: Options,
+        settings: Settings,
+        settings_path: Option<std::path::PathBuf>,
+        replay: Option<Replay>,
+    ) -> Result<Self> {
         let mut entity_id_vendor = entity::IdVendor::default();
+        let mut tilesets = tileset::TileSetCache::default();
 
-        let audio = Audio::new(&options);
+        let campaign = Campaign::load(&options.campaign)?;
+        let level = campaign.level(options.level)?;
 
-        let game_controller =
-            GameController::new(options.lives, options.checkpoint.unwrap_or(0_u32));
+        let bindings = match (&options.input_bindings, &settings.bindings) {
+            (Some(path), _) => Bindings::load(path)?,
+            (None, Some(bindings)) => bindings.clone(),
+            (None, None) => Bindings::load(input_map::DEFAULT_BINDINGS_FILE)?,
+        };
+        let input_map = InputMap::new(bindings);
+
+        let session_settings = settings.session();
+        let mut audio = Audio::new(&options);
+        audio.set_music_volume(session_settings.music_volume);
+        audio.set_sfx_volume(session_settings.sfx_volume);
+        audio.set_level_tracks(level);
+
+        let game_controller = GameController::new(
+            options.lives_remaining.unwrap_or(options.lives),
+            options.vials,
+            options.checkpoint.unwrap_or(0_u32),
+            options.level,
+            campaign.num_levels(),
+        )
+        .with_continues(
+            options.continues_remaining.unwrap_or(options.continues),
+            options.lives,
+        );
 
         let mut gpu = pollster::block_on(gpu_state::GpuState::new(window));
 
@@ -64,20 +183,28 @@ impl AppState {
             &mut gpu,
             &options,
             &mut entity_id_vendor,
+            &mut tilesets,
+            level,
             game_controller.current_checkpoint(),
             game_controller.lives_remaining(),
-        );
-        let mut game_ui = GameUi::new(&mut gpu, &options, &mut entity_id_vendor);
+            game_controller.vials(),
+        )?;
+        let mut game_ui = GameUi::new(&mut gpu, &options, &mut entity_id_vendor, &mut tilesets)?;
         let debug_overlay = if options.debug_overlay {
             Some(DebugOverlay::new(gpu.window(), &gpu))
         } else {
             None
         };
 
-        let tonemap_file = format!("res/tonemaps/{}.png", options.palette);
-        let tonemap = texture::Texture::load(&gpu.device, &gpu.queue, &tonemap_file)
-            .with_context(|| format!("Failed to load palette \"{}\"", tonemap_file))?;
-        let lcd_filter = LcdFilter::new(&mut gpu, &options, tonemap);
+        let palette = Palette::load(&options.palette)
+            .with_context(|| format!("Failed to load palette \"{}\"", options.palette))?;
+        let mut lcd_filter = LcdFilter::new(&mut gpu, &options, &palette)?;
+        let mut palettes = palette::BUILTIN_PALETTES.map(String::from).to_vec();
+        if !Palette::is_builtin(&palette.name) {
+            palettes.push(palette.name.clone());
+        }
+        lcd_filter.apply_settings(&session_settings.lcd);
+        lcd_filter.set_lcd_hysteresis(session_settings.lcd.sludge);
 
         if options.checkpoint == Some(0) {
             // when game starts, palette is shifted to white, an Event::FirebrandCreated
@@ -87,20 +214,77 @@ impl AppState {
             game_ui.set_palette_shift(1.0);
         }
 
-        Ok(Self {
+        let rng = options
+            .seed
+            .map_or_else(entity::GameRng::from_entropy, entity::GameRng::new);
+        log::info!("Using rng seed {}", rng.seed());
+
+        let replay_recorder = options
+            .record
+            .as_ref()
+            .map(|_| Replay::new(&options, Some(rng.seed())));
+        let replay_playback = replay.map(ReplayPlayback::new);
+        let save_path = if replay_playback.is_none() {
+            options.save_path()
+        } else {
+            None
+        };
+        let resource_watcher = options
+            .hot_reload
+            .then(|| FileWatcher::new(&assets::locator().assets_dir(), &["tmx", "tsx", "png"]));
+
+        let mut app = Self {
             options,
+            campaign,
             audio,
             game_controller,
             game_state,
             game_ui,
             debug_overlay,
             lcd_filter,
+            input_map,
+            gamepads: Gamepads::new(),
+            quit_requested: false,
+            error: None,
             entity_id_vendor,
+            tilesets,
             message_dispatcher: event_dispatch::Dispatcher::default(),
+            rng,
+            ui_rng: entity::GameRng::from_entropy(),
+            replay_recorder,
+            replay_playback,
+            fixed_viewport: None,
+            save_path,
+            settings,
+            settings_path,
+            settings_changed_time: None,
+            palettes,
+            resource_watcher,
+            last_resource_poll_time: time::Instant::now(),
             last_render_time: time::Instant::now(),
+            simulation_time_accumulator: time::Duration::ZERO,
             frame_index: 0,
             gpu,
-        })
+        };
+        app.apply_zoom();
+        if let Some(recorder) = &mut app.replay_recorder {
+            let projection = &app.game_state.camera_controller.projection;
+            let viewport = Viewport {
+                width: projection.size().x as u32,
+                height: projection.size().y as u32,
+                scale: projection.scale(),
+            };
+            recorder.viewport = Some(viewport);
+            app.fixed_viewport = Some(viewport);
+        }
+        if let Some(playback) = &app.replay_playback {
+            app.fixed_viewport = playback.replay().viewport;
+            app.apply_zoom();
+        }
+        if app.replay_playback.is_none() {
+            app.game_ui.show_title();
+        }
+        Ok(app)
     }
 
     pub fn window(&self) -> &Window {
@@ -147,51 +331,110 @@ impl AppState {
                     }
                 }
 
-                WindowEvent::CloseRequested => event_loop.exit(),
-                WindowEvent::KeyboardInput {
-                    event:
-                        winit::event::KeyEvent {
-                            state: winit::event::ElementState::Pressed,
-                            physical_key:
-                                winit::keyboard::PhysicalKey::Code(winit::keyboard::KeyCode::Escape),
-                            ..
-                        },
-                    ..
-                } => event_loop.exit(),
-
+                WindowEvent::CloseRequested => {
+                    self.finish_replay_recording();
+                    self.save_settings();
+                    event_loop.exit();
+                }
                 WindowEvent::Resized(physical_size) => {
                     self.resize(*physical_size);
+                    self.window_resized(*physical_size);
                 }
                 _ => {}
             }
         }
+
+        if self.quit_requested {
+            self.finish_replay_recording();
+            self.save_settings();
+            event_loop.exit();
+        }
     }
 
     pub fn resize(&mut self, new_size: winit::dpi::PhysicalSize<u32>) {
         self.gpu.resize(new_size);
-        self.game_state
-            .resize(self.gpu.window(), new_size, &self.gpu);
+        if self.fixed_viewport.is_none() {
+            self.game_state
+                .resize(self.gpu.window(), new_size, &self.gpu);
+        }
         self.game_ui.resize(self.gpu.window(), new_size, &self.gpu);
         self.lcd_filter
             .resize(self.gpu.window(), new_size, &self.gpu, &self.game_state);
     }
 
     pub fn input(&mut self, event: &WindowEvent) -> bool {
-        if self
-            .game_state
-            .input(self.gpu.window(), event, self.game_ui.is_paused())
+        // keys bound to actions are handled as actions; others, e.g., debug keys, are passed on
+        if let WindowEvent::KeyboardInput {
+            event:
+                KeyEvent {
+                    physical_key: PhysicalKey::Code(key_code),
+                    state,
+                    ..
+                },
+            ..
+        } = event
         {
-            true
-        } else {
-            self.game_ui.input(self.gpu.window(), event)
+            if self.input_map.bindings().is_bound(Binding::Key(*key_code)) {
+                for (action, state) in self.input_map.process_keyboard(*key_code, *state) {
+                    self.process_action(action, state);
+                }
+                return true;
+            }
+        }
+
+        // the camera's zoom is fixed while recording or playing back a replay
+        if self.fixed_viewport.is_some() && matches!(event, WindowEvent::MouseWheel { .. }) {
+            return false;
         }
-    }
 
-    pub fn gamepad_input(&mut self, event: gilrs::Event) {
+        // during replay playback, entities are driven by the replay rather than user input
+        let block_entity_input = self.game_ui.is_paused() || self.replay_playback.is_some();
         self.game_state
-            .gamepad_input(event, self.game_ui.is_paused());
-        self.game_ui.gamepad_input(event);
-        self.game_controller.gamepad_input(event);
+            .input(self.gpu.window(), event, block_entity_input)
+    }
+
+    /// Processes input from the active gamepad, pausing the game if it was disconnected.
+    pub fn poll_gamepads(&mut self) {
+        for event in self.gamepads.poll() {
+            let actions = match event {
+                GamepadEvent::Input(event) => self.input_map.process_gamepad(event),
+                GamepadEvent::ActiveChanged => self.input_map.release_gamepad(),
+                GamepadEvent::ActiveDisconnected => {
+                    if self.replay_playback.is_none() {
+                        self.game_ui.pause();
+                    }
+                    self.input_map.release_gamepad()
+                }
+            };
+            for (action, state) in actions {
+                self.process_action(action, state);
+            }
+        }
+    }
+
+    /// Routes an action to entities, and then the game ui.
+    fn process_action(&mut self, action: Action, state: ElementState) {
+        match action {
+            Action::Quit => {
+                self.quit_requested |= state == ElementState::Pressed;
+                return;
+            }
+            Action::ToggleFullscreen => {
+                if state == ElementState::Pressed {
+                    self.toggle_fullscreen();
+                }
+                return;
+            }
+            _ => {}
+        }
+
+        let block_entity_input = self.game_ui.is_paused() || self.replay_playback.is_some();
+        if !self
+            .game_state
+            .process_action(action, state, block_entity_input)
+        {
+            self.game_ui.process_action(action, state);
+        }
     }
 
     pub fn update(
@@ -200,49 +443,409 @@ impl AppState {
         delta_time: std::time::Duration,
         frame_idx: u32,
     ) {
-        // Skip update if delta time is huge - this can happen after
-        // resuming from a pause.
-        if delta_time > std::time::Duration::from_millis(32) {
-            return;
-        }
+        // Clamp huge delta times - this can happen after resuming from a pause, or
+        // dragging the window - rather than trying to catch the simulation up.
+        let delta_time = if delta_time > MAX_FRAME_DELTA_TIME {
+            log::warn!(
+                "Frame delta time {:?} exceeds {:?}, clamping",
+                delta_time,
+                MAX_FRAME_DELTA_TIME
+            );
+            MAX_FRAME_DELTA_TIME
+        } else {
+            delta_time
+        };
 
         if let Some(ref mut debug_overlay) = self.debug_overlay {
             debug_overlay.update(self.gpu.window(), delta_time);
         }
 
-        let game_dt = if self.game_ui.is_paused() {
-            std::time::Duration::from_secs(0)
-        } else {
-            delta_time
-        };
+        self.poll_resources(time);
+
+        // Advance the simulation in fixed timesteps
+        let timestep = self.options.simulation_timestep();
+        self.simulation_time_accumulator += delta_time;
+        while self.simulation_time_accumulator >= timestep {
+            self.simulation_time_accumulator -= timestep;
+            self.step_simulation(time, timestep, frame_idx);
+        }
 
+        // Per-frame updates: audio, rendering state and presentation
         self.audio.update(delta_time);
 
+        let interpolation = self.simulation_time_accumulator.as_secs_f32() / timestep.as_secs_f32();
+        self.game_state.prepare_render(&mut self.gpu, interpolation);
+
         {
+            let game_dt = if self.game_ui.is_paused() {
+                std::time::Duration::from_secs(0)
+            } else {
+                delta_time
+            };
+
             let mut ctx = AppContext {
-                gpu: &mut self.gpu,
+                gpu: Some(&mut self.gpu),
                 audio: &mut self.audio,
                 message_dispatcher: &mut self.message_dispatcher,
                 entity_id_vendor: &mut self.entity_id_vendor,
+                rng: &mut self.ui_rng,
                 frame_idx,
                 time,
                 game_delta_time: game_dt,
                 real_delta_time: delta_time,
             };
 
-            self.game_state.update(&mut ctx);
-
             self.game_ui.update(&mut ctx, &self.game_state);
-
             self.lcd_filter.update(&mut ctx, &self.game_state);
+        }
+
+        event_dispatch::Dispatcher::dispatch(&self.message_dispatcher.drain(), self);
+
+        self.sync_settings(time);
+    }
+
+    /// Advances GameState and GameController by a single fixed timestep.
+    fn step_simulation(
+        &mut self,
+        time: std::time::Instant,
+        timestep: std::time::Duration,
+        frame_idx: u32,
+    ) {
+        let mut ui_commands = self.game_ui.take_commands();
+        let (real_dt, game_dt) = if let Some(frame) = self.next_replay_frame() {
+            // Replay playback drives the simulation with recorded timing, input and ui commands;
+            // the player may still quit
+            if let Some(input) = frame.firebrand_input {
+                self.game_state.override_firebrand_input(input);
+            }
+            ui_commands.retain(|command| *command == UiCommand::QuitGame);
+            ui_commands.extend(frame.ui_commands);
+            (frame.real_delta_time, frame.game_delta_time)
+        } else if self.game_ui.is_paused() {
+            (timestep, std::time::Duration::from_secs(0))
+        } else {
+            (timestep, timestep)
+        };
+        for command in &ui_commands {
+            self.message_dispatcher.broadcast((*command).into());
+        }
+
+        {
+            let mut ctx = AppContext {
+                gpu: Some(&mut self.gpu),
+                audio: &mut self.audio,
+                message_dispatcher: &mut self.message_dispatcher,
+                entity_id_vendor: &mut self.entity_id_vendor,
+                rng: &mut self.rng,
+                frame_idx,
+                time,
+                game_delta_time: game_dt,
+                real_delta_time: real_dt,
+            };
+
+            self.game_state.update(&mut ctx);
+
+            if let Some(ref mut recorder) = self.replay_recorder {
+                recorder.push_frame(ReplayFrame {
+                    real_delta_time: real_dt,
+                    game_delta_time: game_dt,
+                    firebrand_input: self.game_state.firebrand_input(),
+                    ui_commands,
+                });
+            }
 
             self.game_controller
-                .update(&mut ctx, &mut self.game_state, &mut self.game_ui);
+                .update(&mut ctx, &mut self.game_state, Some(&mut self.game_ui));
         }
 
         event_dispatch::Dispatcher::dispatch(&self.message_dispatcher.drain(), self);
     }
 
+    /// Returns the next frame of replay playback, if a replay is playing. When the replay
+    /// runs out of frames, control returns to the user.
+    fn next_replay_frame(&mut self) -> Option<ReplayFrame> {
+        let playback = self.replay_playback.as_mut()?;
+        let frame = playback.next_frame();
+        if frame.is_none() {
+            log::info!("Replay playback finished");
+            self.replay_playback = None;
+        }
+        frame
+    }
+
+    /// The error which ended the game, if a level failed to load
+    pub fn take_error(&mut self) -> Option<anyhow::Error> {
+        self.error.take()
+    }
+
+    /// Replaces the current GameState with one playing the campaign's level at `level_index`,
+    /// carrying firebrand's lives and vials forward from GameController. If the level can't be
+    /// loaded, the game quits with the error.
+    fn load_level(&mut self, level_index: u32) {
+        if let Err(e) = self.try_load_level(level_index) {
+            self.error = Some(e);
+            self.quit_requested = true;
+        }
+    }
+
+    fn try_load_level(&mut self, level_index: u32) -> Result<()> {
+        let level = self.campaign.level(level_index)?;
+        log::info!("Loading level {} \"{}\"", level_index, level.name);
+
+        self.game_state = GameState::new(
+            &mut self.gpu,
+            &self.options,
+            &mut self.entity_id_vendor,
+            &mut self.tilesets,
+            level,
+            self.game_controller.current_checkpoint(),
+            self.game_controller.lives_remaining(),
+            self.game_controller.vials(),
+        )
+        .with_context(|| format!("Unable to load level \"{}\"", level.name))?;
+        self.audio.set_level_tracks(level);
+
+        // the new level fades in from white, as at game start
+        self.game_state.set_palette_shift(1.0);
+        self.game_ui.set_palette_shift(1.0);
+        self.apply_zoom();
+        Ok(())
+    }
+
+    /// If hot reload is enabled, reloads the current level when files in res/ have changed.
+    fn poll_resources(&mut self, time: std::time::Instant) {
+        if time - self.last_resource_poll_time < HOT_RELOAD_POLL_INTERVAL {
+            return;
+        }
+        self.last_resource_poll_time = time;
+
+        if let Some(watcher) = self.resource_watcher.as_mut() {
+            let changed = watcher.poll();
+            if !changed.is_empty() {
+                log::info!("Resources changed: {:?}", changed);
+                self.reload_level();
+            }
+        }
+    }
+
+    /// Rebuilds the current level's stage from its map, tilesets and textures as they are on disk,
+    /// keeping firebrand where he stands. A map with problems is reported, and not loaded.
+    fn reload_level(&mut self) {
+        let level = match self.campaign.level(self.game_controller.current_level()) {
+            Result::Ok(level) => level,
+            Err(e) => {
+                log::error!("Unable to reload level: {:?}", e);
+                return;
+            }
+        };
+
+        // cached tilesets may have been edited
+        self.tilesets = tileset::TileSetCache::default();
+        let map = match GameState::load_map(level, &mut self.tilesets) {
+            Result::Ok(map) => map,
+            Err(e) => {
+                log::error!("Unable to reload level \"{}\": {:#}", level.name, e);
+                return;
+            }
+        };
+        let problems = validation::validate_loaded_map(&level.map, &map);
+        if !problems.is_empty() {
+            for problem in problems {
+                log::error!("{}", problem);
+            }
+            log::error!("Not reloading level \"{}\"", level.name);
+            return;
+        }
+
+        log::info!("Reloading level \"{}\"", level.name);
+        let reloaded = match GameState::new_from_map(
+            &mut self.gpu,
+            &self.options,
+            &mut self.entity_id_vendor,
+            &mut self.tilesets,
+            level,
+            map,
+            self.game_controller.current_checkpoint(),
+            self.game_controller.lives_remaining(),
+            self.game_controller.vials(),
+        ) {
+            Result::Ok(game_state) => game_state,
+            Err(e) => {
+                log::error!("Unable to reload level \"{}\": {:#}", level.name, e);
+                return;
+            }
+        };
+        self.game_state.reload_stage(
+            reloaded,
+            self.game_controller.current_checkpoint(),
+            &mut self.message_dispatcher,
+        );
+    }
+
+    /// Writes the player's progress to the save file. After a game over, the save resumes at the
+    /// start of the current level as a new game would, with the lives and continues the session
+    /// began with and no vials.
+    fn save_progress(&self, is_game_over: bool) {
+        let Some(save_path) = &self.save_path else {
+            return;
+        };
+
+        let mut save = SaveGame::new(&self.game_controller);
+        if is_game_over {
+            save.checkpoint = 0;
+            save.lives = self.options.lives;
+            save.continues = Some(self.options.continues);
+            save.vials = 0;
+        }
+        if let Err(e) = save.save(save_path) {
+            log::error!("Unable to save progress: {:?}", e);
+        }
+    }
+
+    /// Zooms the camera to the player's preferred scale; gameboy mode keeps the original scale.
+    /// While recording or playing back a replay, the camera keeps the replay's viewport instead.
+    fn apply_zoom(&mut self) {
+        let projection = &mut self.game_state.camera_controller.projection;
+        if let Some(viewport) = self.fixed_viewport {
+            projection.resize(viewport.width, viewport.height);
+            projection.set_scale(viewport.scale);
+        } else if let Some(zoom) = self.settings.session().zoom {
+            projection.set_scale(zoom);
+        }
+    }
+
+    /// Starts the current level over, from its first checkpoint with the lives the session began
+    /// with, e.g., after a game over.
+    fn new_game(&mut self) {
+        let level = self.game_controller.current_level();
+        self.game_controller =
+            GameController::new(self.options.lives, 0, 0, level, self.campaign.num_levels())
+                .with_continues(self.options.continues, self.options.lives);
+        self.load_level(level);
+    }
+
+    /// Switches to the palette following (or preceding) the current one, and makes it the
+    /// player's preferred palette. A custom palette the game started with is cycled through
+    /// after the builtin palettes.
+    fn cycle_palette(&mut self, forward: bool) {
+        let palettes = &self.palettes;
+        let index = palettes
+            .iter()
+            .position(|palette| *palette == self.options.palette)
+            .unwrap_or(0);
+        let index = if forward {
+            (index + 1) % palettes.len()
+        } else {
+            (index + palettes.len() - 1) % palettes.len()
+        };
+
+        let name = palettes[index].clone();
+        let result = Palette::load(&name)
+            .and_then(|palette| self.lcd_filter.set_palette(&self.gpu, &palette));
+        if let Err(e) = result {
+            log::error!("Unable to switch to palette \"{}\": {:?}", name, e);
+            return;
+        }
+        log::info!("Switched to palette \"{}\"", name);
+        self.settings.palette = Some(name.clone());
+        self.options.palette = name;
+        self.settings_changed_time = Some(time::Instant::now());
+    }
+
+    fn toggle_fullscreen(&mut self) {
+        self.settings.fullscreen = self.window().fullscreen().is_none();
+        self.window().set_fullscreen(
+            self.settings
+                .fullscreen
+                .then_some(winit::window::Fullscreen::Borderless(None)),
+        );
+        self.settings_changed_time = Some(time::Instant::now());
+    }
+
+    /// Records the window's size in the settings, unless it's sized by gameboy mode or fullscreen.
+    fn window_resized(&mut self, new_size: winit::dpi::PhysicalSize<u32>) {
+        if self.settings.overrides.window || self.window().fullscreen().is_some() {
+            return;
+        }
+        let size = new_size.to_logical::<u32>(self.window().scale_factor());
+        if size.width > 0 && size.height > 0 {
+            let window_size = Some((size.width, size.height));
+            if self.settings.window_size != window_size {
+                self.settings.window_size = window_size;
+                self.settings_changed_time = Some(time::Instant::now());
+            }
+        }
+    }
+
+    /// Records changes made in game, e.g., volumes set in the pause menu, zooming, or adjusting
+    /// the LCD's sludge in the debug overlay, to the settings, and saves the settings once changes have settled.
+    fn sync_settings(&mut self, time: std::time::Instant) {
+        let mut changed = false;
+
+        if !self.settings.overrides.window {
+            let zoom = self.game_state.camera_controller.projection.scale();
+            if self.settings.zoom.unwrap_or(DEFAULT_CAMERA_SCALE) != zoom {
+                self.settings.zoom = Some(zoom);
+                changed = true;
+            }
+        }
+
+        // settings the command line overrides are the session's, not the player's preference
+        if !self.settings.overrides.music_volume
+            && self.audio.music_volume() != self.settings.music_volume
+        {
+            self.settings.music_volume = self.audio.music_volume();
+            changed = true;
+        }
+        if self.audio.sfx_volume() != self.settings.sfx_volume {
+            self.settings.sfx_volume = self.audio.sfx_volume();
+            changed = true;
+        }
+
+        if !self.settings.overrides.sludge
+            && self.lcd_filter.lcd_hysteresis() != self.settings.lcd.sludge
+        {
+            self.settings.lcd.sludge = self.lcd_filter.lcd_hysteresis();
+            changed = true;
+        }
+
+        if changed {
+            self.settings_changed_time = Some(time);
+        }
+        if self
+            .settings_changed_time
+            .is_some_and(|changed_time| time - changed_time >= SETTINGS_SAVE_DELAY)
+        {
+            self.save_settings();
+        }
+    }
+
+    /// Saves the settings, if they've changed since they were last saved.
+    fn save_settings(&mut self) {
+        if self.settings_changed_time.take().is_none() {
+            return;
+        }
+        if let Some(settings_path) = &self.settings_path {
+            if let Err(e) = self.settings.save(settings_path) {
+                log::error!("Unable to save settings: {:?}", e);
+            }
+        }
+    }
+
+    /// If recording a replay, writes it to the file specified by --record
+    fn finish_replay_recording(&mut self) {
+        if let (Some(recorder), Some(path)) = (self.replay_recorder.take(), &self.options.record) {
+            match recorder.save(path) {
+                Result::Ok(_) => log::info!(
+                    "Recorded {} frames to replay {:?}",
+                    recorder.frames.len(),
+                    path
+                ),
+                Err(e) => log::error!("Unable to save replay: {:?}", e),
+            }
+        }
+    }
+
     pub fn render(
         &mut self,
         encoder: &mut wgpu::CommandEncoder,
@@ -268,12 +871,29 @@ impl AppState {
                 &mut self.game_state,
                 &mut self.lcd_filter,
             );
+            if let Some(forward) = debug_overlay.take_palette_cycle_request() {
+                self.message_dispatcher
+                    .broadcast(Event::CyclePalette { forward });
+            }
         }
     }
 }
 
 impl event_dispatch::MessageHandler for AppState {
     fn handle_message(&mut self, message: &event_dispatch::Message) {
+        match message.event {
+            Event::ResetState => self.rng.reset(),
+            Event::LoadLevel { level } => self.load_level(level),
+            Event::NewGame => self.new_game(),
+            Event::CyclePalette { forward } => self.cycle_palette(forward),
+            Event::QuitGame => self.quit_requested = true,
+            Event::FirebrandInjured => self.gamepads.rumble(FIREBRAND_INJURY_RUMBLE),
+            Event::BossInjured => self.gamepads.rumble(BOSS_INJURY_RUMBLE),
+            Event::StartCameraShake { .. } => self.gamepads.rumble(CAMERA_SHAKE_RUMBLE),
+            Event::EndCameraShake => self.gamepads.stop_rumble(),
+            _ => {}
+        }
+
         self.game_controller.handle_message(
             message,
             &mut self.message_dispatcher,
@@ -288,5 +908,18 @@ impl event_dispatch::MessageHandler for AppState {
             &mut self.audio,
         );
         self.game_ui.handle_message(message);
+
+        // a level which failed to load isn't saved as the player's progress
+        if self.error.is_some() {
+            return;
+        }
+        match message.event {
+            Event::FirebrandPassedCheckpoint
+            | Event::FirebrandDied
+            | Event::FirebrandPassedThroughExitDoor
+            | Event::LoadLevel { .. } => self.save_progress(false),
+            Event::GameOver { .. } => self.save_progress(true),
+            _ => {}
+        }
     }
 }
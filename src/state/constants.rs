@@ -25,11 +25,27 @@ pub mod layers {
         pub const ENTITIES: f32 = 70.0;
         pub const FIREBRAND: f32 = 60.0;
         pub const FOREGROUND: f32 = 50.0;
+
+        /// Looks up a stage depth by name, e.g., for a map layer's "z_depth" property
+        pub fn named(name: &str) -> Option<f32> {
+            match name {
+                "exit" => Some(EXIT),
+                "background" => Some(BACKGROUND),
+                "level" => Some(LEVEL),
+                "entities" => Some(ENTITIES),
+                "firebrand" => Some(FIREBRAND),
+                "foreground" => Some(FOREGROUND),
+                _ => None,
+            }
+        }
     }
 
     pub mod ui {
         pub const BACKGROUND: f32 = 40.0;
         pub const FOREGROUND: f32 = 10.0;
+        // the title screen and pause menu are drawn in front of the drawer
+        pub const MENU_BACKGROUND: f32 = 8.0;
+        pub const MENU_FOREGROUND: f32 = 6.0;
     }
 }
 
@@ -0,0 +1,78 @@
+use crate::input::Action;
+
+// ---------------------------------------------------------------------------------------------------------------------
+
+/// Where a continue restarts the level, top to bottom as laid out in game_ui.tmx's Continue layer
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ContinueChoice {
+    LastCheckpoint,
+    StartOfLevel,
+}
+
+impl ContinueChoice {
+    pub const ALL: [ContinueChoice; 2] =
+        [ContinueChoice::LastCheckpoint, ContinueChoice::StartOfLevel];
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+
+/// Selection state of the continue prompt shown after a game over. Up and down move the
+/// selection, wrapping around, and shoot or pause accept the selected choice.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ContinueMenu {
+    selected_index: usize,
+}
+
+impl ContinueMenu {
+    pub fn selected(&self) -> ContinueChoice {
+        ContinueChoice::ALL[self.selected_index]
+    }
+
+    /// Index of the selected choice, from the top of the prompt
+    pub fn selected_index(&self) -> usize {
+        self.selected_index
+    }
+
+    /// Handles a press of `action`, returning the player's choice if they accepted one.
+    pub fn process_action(&mut self, action: Action) -> Option<ContinueChoice> {
+        let count = ContinueChoice::ALL.len();
+        match action {
+            Action::MenuUp => {
+                self.selected_index = (self.selected_index + count - 1) % count;
+                None
+            }
+            Action::MenuDown => {
+                self.selected_index = (self.selected_index + 1) % count;
+                None
+            }
+            Action::Shoot | Action::Pause => Some(self.selected()),
+            _ => None,
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod continue_menu_tests {
+    use super::*;
+
+    #[test]
+    fn selection_wraps_and_is_accepted() {
+        let mut menu = ContinueMenu::default();
+        assert_eq!(menu.selected(), ContinueChoice::LastCheckpoint);
+        assert_eq!(menu.process_action(Action::MoveLeft), None);
+        assert_eq!(menu.process_action(Action::MenuUp), None);
+        assert_eq!(menu.selected(), ContinueChoice::StartOfLevel);
+        assert_eq!(
+            menu.process_action(Action::Shoot),
+            Some(ContinueChoice::StartOfLevel)
+        );
+        menu.process_action(Action::MenuDown);
+        assert_eq!(menu.selected_index(), 0);
+        assert_eq!(
+            menu.process_action(Action::Pause),
+            Some(ContinueChoice::LastCheckpoint)
+        );
+    }
+}
@@ -146,6 +146,7 @@ struct UiStateInput {
     character_cycle: String,
     draw_stage_collision_info: bool,
     lcd_hysteresis: Option<std::time::Duration>,
+    palette: String,
 }
 
 /// UiInteractionOutput represents the values from UiStateInput which changed
@@ -159,11 +160,14 @@ struct UiInteractionOutput {
     draw_stage_collision_info: Option<bool>,
     draw_entity_debug: Option<bool>,
     lcd_hysteresis: Option<Option<std::time::Duration>>,
+    // true to cycle to the next palette, false for the previous
+    cycle_palette: Option<bool>,
 }
 
 pub struct DebugOverlay {
     pub egui_renderer: EguiRenderer,
     pub scale_factor: f64,
+    palette_cycle_request: Option<bool>,
 }
 
 impl DebugOverlay {
@@ -174,6 +178,7 @@ impl DebugOverlay {
         Self {
             egui_renderer,
             scale_factor,
+            palette_cycle_request: None,
         }
     }
 
@@ -183,6 +188,13 @@ impl DebugOverlay {
 
     pub fn update(&mut self, _window: &Window, _dt: std::time::Duration) {}
 
+    /// Returns, and clears, the direction the palette was last cycled in the overlay, if it was
+    /// cycled since the previous call. Palette changes are applied by AppState, which tracks the
+    /// player's preferred palette.
+    pub fn take_palette_cycle_request(&mut self) -> Option<bool> {
+        self.palette_cycle_request.take()
+    }
+
     pub fn render(
         &mut self,
         gpu: &mut GpuState,
@@ -289,6 +301,17 @@ impl DebugOverlay {
                     };
                 }
                 ui.end_row();
+
+                ui.horizontal(|ui| {
+                    ui.label(format!("Palette: {}", input.palette));
+                    if ui.button("<").clicked() {
+                        output.cycle_palette = Some(false);
+                    }
+                    if ui.button(">").clicked() {
+                        output.cycle_palette = Some(true);
+                    }
+                });
+                ui.end_row();
             });
         output
     }
@@ -310,6 +333,7 @@ impl DebugOverlay {
                 draw_stage_collision_info: game_state.draw_stage_collision_info,
                 character_cycle: firebrand.entity.sprite_cycle().to_string(),
                 lcd_hysteresis: lcd_filter.lcd_hysteresis(),
+                palette: lcd_filter.palette().name.clone(),
             }
         } else {
             UiStateInput {
@@ -320,6 +344,7 @@ impl DebugOverlay {
                 draw_stage_collision_info: game_state.draw_stage_collision_info,
                 character_cycle: "<none>".to_owned(),
                 lcd_hysteresis: lcd_filter.lcd_hysteresis(),
+                palette: lcd_filter.palette().name.clone(),
             }
         }
     }
@@ -342,5 +367,8 @@ impl DebugOverlay {
         if let Some(lcd_hysteresis) = ui_input_state.lcd_hysteresis {
             lcd_filter.set_lcd_hysteresis(lcd_hysteresis);
         }
+        if let Some(forward) = ui_input_state.cycle_palette {
+            self.palette_cycle_request = Some(forward);
+        }
     }
 }
@@ -2,7 +2,7 @@ use cgmath::*;
 
 use crate::{
     entities::{self, util::HorizontalDir},
-    sprite, tileset,
+    property, sprite, tileset,
 };
 
 /// An Event payload for Message
@@ -21,6 +21,9 @@ pub enum Event {
     /// broadcast by Firebrand when they die
     FirebrandDied,
 
+    /// broadcast by Firebrand when injured, but not killed
+    FirebrandInjured,
+
     /// Sent by Firebrand to GameState to notify change of health, flight time, etc
     FirebrandStatusChanged {
         status: entities::firebrand::CharacterState,
@@ -61,6 +64,7 @@ pub enum Event {
         class_name: String,
         spawn_point_sprite: sprite::Sprite,
         spawn_point_tile: tileset::Tile,
+        spawn_point_properties: property::Properties,
     },
 
     /// Response from GameState to signal if requested entity was spawned.
@@ -96,8 +100,14 @@ pub enum Event {
     /// Sent by boss to game state to ask if it may rise and start fighting player
     QueryBossFightMayStart,
 
-    /// Sent by GameState to boss to signal that it may now raise
-    BossFightMayStart,
+    /// Sent by GameState to boss to signal that it may now raise. Bears the
+    /// level's boss hit points, if the campaign specifies them.
+    BossFightMayStart {
+        hit_points: Option<u32>,
+    },
+
+    /// broadcast by boss when hit by a fireball
+    BossInjured,
 
     /// Sent by boss to GameState when defeated
     BossDefeated,
@@ -127,8 +137,66 @@ pub enum Event {
     EndCameraShake,
 
     // Broadcast when firebrand has died with no remaining lives
-    GameOver,
+    GameOver {
+        // number of continues the player may still use
+        continues_remaining: u32,
+    },
 
     // Broadcast when GameState reset the level after player death
     ResetState,
+
+    // Broadcast by GameController when the current level is complete and the
+    // campaign's next level should be loaded
+    LoadLevel {
+        level: u32,
+    },
+
+    // Broadcast per UiCommand when the player starts a new game from the title screen after a
+    // game over
+    NewGame,
+
+    // Broadcast per UiCommand when the player chooses to restart from the last checkpoint
+    // reached
+    RestartFromCheckpoint,
+
+    // Broadcast per UiCommand when the player picks the next or previous palette
+    CyclePalette {
+        forward: bool,
+    },
+
+    // Broadcast per UiCommand when the player accepts a continue after a game over, restarting
+    // the level from its last reached checkpoint, or from its start
+    ContinueGame {
+        from_last_checkpoint: bool,
+    },
+
+    // Broadcast per UiCommand when the player chooses to quit
+    QuitGame,
+}
+
+/// A gameplay event the player requests through GameUi. GameUi updates at render rate, so
+/// AppState broadcasts these at the next simulation step, where replays record them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UiCommand {
+    NewGame,
+    RestartFromCheckpoint,
+    CyclePalette { forward: bool },
+    ContinueGame { from_last_checkpoint: bool },
+    QuitGame,
+}
+
+impl From<UiCommand> for Event {
+    fn from(command: UiCommand) -> Self {
+        match command {
+            UiCommand::NewGame => Event::NewGame,
+            UiCommand::RestartFromCheckpoint => Event::RestartFromCheckpoint,
+            UiCommand::CyclePalette { forward } => Event::CyclePalette { forward },
+            UiCommand::ContinueGame {
+                from_last_checkpoint,
+            } => Event::ContinueGame {
+                from_last_checkpoint,
+            },
+            UiCommand::QuitGame => Event::QuitGame,
+        }
+    }
 }
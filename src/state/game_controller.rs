@@ -10,43 +10,71 @@ const RESTART_GAME_DELAY: f32 = 4.0;
 const GAME_OVER_DELAY: f32 = 2.0;
 const FADE_IN_DURATION: f32 = 1.0;
 const FADE_OUT_DURATION: f32 = 1.0;
+const NEXT_LEVEL_DELAY: f32 = 4.0;
 
 //---------------------------------------------------------------------------------------------------------------------
 
 pub struct GameController {
+    current_level: u32,
+    num_levels: u32,
+    levels_completed: u32,
     current_checkpoint: u32,
     lives_remaining: u32,
+    vials: u32,
+    starting_lives: u32,
+    continues_remaining: u32,
+    game_over: bool,
+    // set when the fade out leads to a continue, rather than to the next level
+    continue_pending: bool,
     restart_game_countdown: Option<f32>,
     game_over_countdown: Option<f32>,
     fade_in_countdown: Option<f32>,
     fade_out_countdown: Option<f32>,
+    next_level_countdown: Option<f32>,
 }
 
 impl Default for GameController {
     fn default() -> Self {
-        Self::new(3, 0)
+        Self::new(3, 0, 0, 0, 1)
     }
 }
 
 impl GameController {
-    pub fn new(lives: u32, checkpoint: u32) -> Self {
+    /// Creates a GameController starting at `checkpoint` of `level`, in a campaign of `num_levels` levels.
+    /// The levels preceding `level` are considered completed. No continues are offered after a game
+    /// over, unless granted by `with_continues`.
+    pub fn new(lives: u32, vials: u32, checkpoint: u32, level: u32, num_levels: u32) -> Self {
         Self {
+            current_level: level,
+            num_levels,
+            levels_completed: level,
             current_checkpoint: checkpoint,
             lives_remaining: lives,
+            vials,
+            starting_lives: lives,
+            continues_remaining: 0,
+            game_over: false,
+            continue_pending: false,
             restart_game_countdown: None,
             game_over_countdown: None,
             fade_in_countdown: None,
             fade_out_countdown: None,
+            next_level_countdown: None,
         }
     }
 
-    pub fn gamepad_input(&mut self, _event: gilrs::Event) {}
+    /// Offers `continues` continues after a game over, each restarting with `starting_lives` lives.
+    pub fn with_continues(mut self, continues: u32, starting_lives: u32) -> Self {
+        self.continues_remaining = continues;
+        self.starting_lives = starting_lives;
+        self
+    }
 
     pub fn update(
         &mut self,
         ctx: &mut AppContext,
         game_state: &mut GameState,
-        game_ui: &mut GameUi,
+        mut game_ui: Option<&mut GameUi>,
     ) {
         let dt = ctx.game_delta_time.as_secs_f32();
 
@@ -54,10 +82,14 @@ impl GameController {
             let fade_in_countdown = fade_in_countdown - dt;
             let palette_shift = (fade_in_countdown / FADE_IN_DURATION).max(0.0);
             game_state.set_palette_shift(palette_shift);
-            game_ui.set_palette_shift(palette_shift);
+            if let Some(game_ui) = &mut game_ui {
+                game_ui.set_palette_shift(palette_shift);
+            }
             if fade_in_countdown < 0.0 {
                 self.fade_in_countdown = None;
-                game_ui.show_start_message();
+                if let Some(game_ui) = &mut game_ui {
+                    game_ui.show_start_message();
+                }
             } else {
                 self.fade_in_countdown = Some(fade_in_countdown);
             }
@@ -67,15 +99,50 @@ impl GameController {
             let fade_out_countdown = fade_out_countdown - dt;
             let palette_shift = (1.0 - (fade_out_countdown / FADE_OUT_DURATION)).max(0.0);
             game_state.set_palette_shift(-palette_shift);
-            game_ui.set_palette_shift(-palette_shift);
+            if let Some(game_ui) = &mut game_ui {
+                game_ui.set_palette_shift(-palette_shift);
+            }
             if fade_out_countdown < 0.0 {
                 self.fade_out_countdown = None;
-                game_ui.show_level_complete_message();
+                if self.continue_pending {
+                    // restart faded out, and fade back in as when the level loaded
+                    self.continue_pending = false;
+                    game_state.restart_game_at_checkpoint(
+                        self.current_checkpoint,
+                        self.lives_remaining,
+                        self.vials,
+                        ctx.message_dispatcher,
+                    );
+                    self.fade_in_countdown = Some(FADE_IN_DURATION);
+                } else {
+                    if let Some(game_ui) = &mut game_ui {
+                        game_ui.show_level_complete_message();
+                    }
+                    if self.current_level + 1 < self.num_levels {
+                        self.next_level_countdown = Some(NEXT_LEVEL_DELAY);
+                    } else {
+                        log::info!("GameController::update - Campaign complete");
+                    }
+                }
             } else {
                 self.fade_out_countdown = Some(fade_out_countdown);
             }
         }
 
+        if let Some(next_level_countdown) = self.next_level_countdown {
+            let next_level_countdown = next_level_countdown - dt;
+            if next_level_countdown < 0.0 {
+                self.next_level_countdown = None;
+                self.current_level += 1;
+                self.current_checkpoint = 0;
+                ctx.message_dispatcher.broadcast(Event::LoadLevel {
+                    level: self.current_level,
+                });
+            } else {
+                self.next_level_countdown = Some(next_level_countdown);
+            }
+        }
+
         if let Some(restart_game_countdown) = self.restart_game_countdown {
             let restart_game_countdown = restart_game_countdown - dt;
             if restart_game_countdown < 0.0 {
@@ -83,6 +150,7 @@ impl GameController {
                 game_state.restart_game_at_checkpoint(
                     self.current_checkpoint,
                     self.lives_remaining,
+                    self.vials,
                     ctx.message_dispatcher,
                 );
             } else {
@@ -94,7 +162,8 @@ impl GameController {
             let game_over_countdown = game_over_countdown - dt;
             if game_over_countdown < 0.0 {
                 self.game_over_countdown = None;
-                game_state.game_over(ctx.message_dispatcher);
+                self.game_over = true;
+                game_state.game_over(self.continues_remaining, ctx.message_dispatcher);
             } else {
                 self.game_over_countdown = Some(game_over_countdown);
             }
@@ -104,7 +173,7 @@ impl GameController {
     pub fn handle_message(
         &mut self,
         message: &event_dispatch::Message,
-        _message_dispatcher: &mut event_dispatch::Dispatcher,
+        message_dispatcher: &mut event_dispatch::Dispatcher,
         _entity_id_vendor: &mut entity::IdVendor,
         audio: &mut audio::Audio,
         game_state: &mut GameState,
@@ -146,14 +215,52 @@ impl GameController {
                 audio.start_track(audio::Tracks::MainTheme);
             }
 
+            Event::FirebrandStatusChanged { status } => {
+                self.vials = status.num_vials;
+            }
+
             Event::FirebrandPassedThroughExitDoor => {
+                self.levels_completed = self.levels_completed.max(self.current_level + 1);
+                self.fade_out_countdown = Some(FADE_OUT_DURATION);
+            }
+
+            Event::ContinueGame {
+                from_last_checkpoint,
+            } if self.game_over && self.continues_remaining > 0 => {
+                self.game_over = false;
+                self.continues_remaining -= 1;
+                self.lives_remaining = self.starting_lives;
+                if !from_last_checkpoint {
+                    self.current_checkpoint = 0;
+                }
+                self.continue_pending = true;
                 self.fade_out_countdown = Some(FADE_OUT_DURATION);
             }
 
+            // a game over, or leaving the level, can't be undone by restarting
+            Event::RestartFromCheckpoint
+                if !self.game_over
+                    && self.game_over_countdown.is_none()
+                    && self.fade_out_countdown.is_none()
+                    && self.next_level_countdown.is_none() =>
+            {
+                self.restart_game_countdown = None;
+                game_state.restart_game_at_checkpoint(
+                    self.current_checkpoint,
+                    self.lives_remaining,
+                    self.vials,
+                    message_dispatcher,
+                );
+            }
+
             _ => {}
         }
     }
 
+    pub fn current_level(&self) -> u32 {
+        self.current_level
+    }
+
     pub fn current_checkpoint(&self) -> u32 {
         self.current_checkpoint
     }
@@ -161,4 +268,24 @@ impl GameController {
     pub fn lives_remaining(&self) -> u32 {
         self.lives_remaining
     }
+
+    /// True from a game over until the player continues
+    pub fn is_game_over(&self) -> bool {
+        self.game_over
+    }
+
+    /// Continues the player may use after a game over
+    pub fn continues_remaining(&self) -> u32 {
+        self.continues_remaining
+    }
+
+    /// Vials collected by firebrand, carried from level to level
+    pub fn vials(&self) -> u32 {
+        self.vials
+    }
+
+    /// Number of campaign levels the player has completed
+    pub fn levels_completed(&self) -> u32 {
+        self.levels_completed
+    }
 }
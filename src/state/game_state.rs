@@ -1,6 +1,7 @@
+use anyhow::{Context, Result};
 use cgmath::*;
 use std::{
-    collections::{HashMap, HashSet},
+    collections::{BTreeMap, BTreeSet, HashMap},
     path::Path,
     rc::Rc,
     time::Duration,
@@ -8,20 +9,18 @@ use std::{
 use winit::{
     dpi::PhysicalPosition,
     event::{ElementState, KeyEvent, MouseButton, WindowEvent},
-    keyboard::PhysicalKey,
+    keyboard::{KeyCode, PhysicalKey},
     window::Window,
 };
 
 use crate::{
-    audio, camera, collision,
-    entities::{
-        self,
-        util::{CompassDir, HorizontalDir},
-        EntityClass,
-    },
+    audio, camera, campaign, collision,
+    entities::{self, firebrand::FirebrandInput, util::CompassDir, EntityClass},
     entity::{self, EntityComponents, GameStatePeek},
-    event_dispatch, map,
-    sprite::rendering,
+    event_dispatch,
+    input::Action,
+    map,
+    sprite::{self, rendering},
     texture, tileset,
     util::{self, hermite, lerp, Bounds},
     Options,
@@ -79,198 +78,73 @@ impl CameraShaker {
 
 const BOSS_FIGHT_START_TIME_ARENA_CONTRACTION_DURATION: f32 = 2.0;
 
-// ---------------------------------------------------------------------------------------------------------------------
+// Entities which move further than this in a single step are not interpolated when rendering
+const MAX_INTERPOLATION_DISTANCE_SQ: f32 = 2.0 * 2.0;
 
-pub struct GameState {
-    // Camera
-    pub camera_controller: camera::CameraController,
+// ---------------------------------------------------------------------------------------------------------------------
 
-    // Pipelines
+/// Gpu resources used to draw the stage and the entities upon it. GameState holds
+/// this as an Option so the simulation can run headless, without a wgpu device.
+struct StageRendering {
     sprite_render_pipeline: wgpu::RenderPipeline,
+    camera_uniforms: camera::Uniforms,
 
-    // Stage rendering
-    stage_material: Rc<rendering::Material>,
+    // Stage rendering; one material per map tileset
+    stage_materials: Vec<Rc<rendering::Material>>,
     stage_uniforms: rendering::Uniforms,
     stage_debug_draw_overlap_uniforms: rendering::Uniforms,
     stage_debug_draw_contact_uniforms: rendering::Uniforms,
     stage_sprite_drawable: rendering::Drawable,
 
-    // Collision detection and dispatch
-    map: map::Map,
-    collision_space: collision::Space,
-
     // Entity rendering
-    entity_tileset: tileset::TileSet,
+    entity_tileset: Rc<tileset::TileSet>,
     entity_material: Rc<rendering::Material>,
-    entities: HashMap<u32, entity::EntityComponents>,
-    firebrand_entity_id: Option<u32>,
-    firebrand_start_checkpoint: u32,
-    firebrand_start_lives_remaining: u32,
-    visible_entities: HashSet<u32>,
-    entities_to_add: Vec<EntityAdditionRequest>,
 
     // Flipbook animations
     flipbook_animations: Vec<rendering::FlipbookAnimationComponents>,
-
-    // Input state
-    last_mouse_pos: PhysicalPosition<f64>,
-    mouse_pressed: bool,
-
-    // Toggles
-    pub draw_stage_collision_info: bool,
-    pub camera_tracks_character: bool,
-
-    // General game state
-    time: f32,
-    boss_arena_entered_time: Option<f32>,
-    boss_arena_left_bounds: Option<f32>,
-    viewport_left_when_boss_arena_entered: Option<f32>,
-    camera_shaker: Option<CameraShaker>,
-    game_state_peek: GameStatePeek,
-    pub pixels_per_unit: Vector2<f32>,
-    palette_shift: f32,
-    num_restarts: u32,
 }
 
-impl GameState {
-    /// Creates new GameState
-    /// start_checkpoint: Index of the checkpoint to place character at
-    pub fn new(
+impl StageRendering {
+    fn new(
         gpu: &mut gpu_state::GpuState,
-        options: &Options,
-        entity_id_vendor: &mut entity::IdVendor,
-        start_checkpoint: u32,
-        lives_remaining: u32,
-    ) -> Self {
-        // Load the stage map
-        let map = map::Map::new_tmx(Path::new("res/level_1.tmx"));
-        let map = map.expect("Expected map to load");
-        let pixels_per_unit = map.tileset.get_sprite_size().cast().unwrap();
-
+        map: &map::Map,
+        entity_tileset_path: &Path,
+        tilesets: &mut tileset::TileSetCache,
+        stage_sprites: &[sprite::Sprite],
+        stage_animation_flipbooks: Vec<map::SpriteFlipbookAnimation>,
+        pixels_per_unit: Vector2<f32>,
+    ) -> Result<Self> {
         let material_bind_group_layout = rendering::Material::bind_group_layout(&gpu.device);
-        let (
-            stage_sprite_material,
-            stage_sprite_drawable,
-            collision_space,
-            entities,
-            stage_entities,
-            stage_animation_flipbooks,
-        ) = {
-            let stage_sprite_material = {
-                let spritesheet_path = Path::new("res").join(&map.tileset.image_path);
+
+        let stage_materials = map
+            .tilesets
+            .iter()
+            .map(|tileset| {
                 let spritesheet = Rc::new(
-                    texture::Texture::load(&gpu.device, &gpu.queue, spritesheet_path).unwrap(),
+                    texture::Texture::load(&gpu.device, &gpu.queue, &tileset.image_path)
+                        .with_context(|| {
+                            format!("Unable to load tileset image {:?}", tileset.image_path)
+                        })?,
                 );
-                Rc::new(rendering::Material::new(
+                Ok(Rc::new(rendering::Material::new(
                     &gpu.device,
                     "Sprite Material",
                     spritesheet,
                     &material_bind_group_layout,
-                ))
-            };
-
-            let get_layer = |name: &str| {
-                map.layer_named(name)
-                    .unwrap_or_else(|| panic!("Expect layer named \"{}\"", name))
-            };
-
-            let bg_layer = get_layer("Background");
-            let level_layer = get_layer("Level");
-            let exit_layer = get_layer("Exit");
-            let entity_layer = get_layer("Entities");
-            let rising_floor_layer = get_layer("RisingFloor");
-            let exit_door_left_layer = get_layer("ExitDoorLeft");
-            let exit_door_right_layer = get_layer("ExitDoorRight");
-
-            // generate level sprites
-            let bg_sprites = map.generate_sprites(bg_layer, |_, _| layers::stage::BACKGROUND);
-            let level_sprites = map.generate_sprites(level_layer, |_sprite, tile| {
-                if tile.get_property("foreground") == Some("true") {
-                    layers::stage::FOREGROUND
-                } else {
-                    layers::stage::LEVEL
-                }
-            });
-            let exit_sprites = map.generate_sprites(exit_layer, |_, _| layers::stage::EXIT);
-
-            // Collect sprites for RisingFloor and ExitDoor entities.
-            // The entities which draw these sprites will assign correct z depth at render time
-            let rising_floor_sprites = map.generate_sprites(rising_floor_layer, |_, _| 0.0);
-            let exit_door_left_sprites = map.generate_sprites(exit_door_left_layer, |_, _| 0.0);
-            let exit_door_right_sprites = map.generate_sprites(exit_door_right_layer, |_, _| 0.0);
-
-            let rising_floor_entity = Box::new(entities::rising_floor::RisingFloor::new(
-                rising_floor_sprites,
-            ));
-
-            let exit_door_left_entity = Box::new(entities::exit_door::ExitDoor::new(
-                exit_door_left_sprites,
-                HorizontalDir::West,
-            ));
-
-            let exit_door_right_entity = Box::new(entities::exit_door::ExitDoor::new(
-                exit_door_right_sprites,
-                HorizontalDir::East,
-            ));
-
-            // generate level entities
-            let level_colliders: Vec<collision::Collider> = level_sprites
-                .iter()
-                .map(collision::Collider::from_static_sprite)
-                .collect();
-            let mut collision_space = collision::Space::new(&level_colliders);
-            let entities = map.generate_entities(
-                entity_layer,
-                &mut collision_space,
-                entity_id_vendor,
-                |_, _| 0.0, // entities assign depth at render time
-            );
-
-            // generate animations
-            let stage_animation_flipbooks =
-                map.generate_animations(bg_layer, |_, _| layers::stage::BACKGROUND);
-
-            let mut stage_sprites = vec![];
-            stage_sprites.extend(bg_sprites);
-            stage_sprites.extend(level_sprites);
-            stage_sprites.extend(exit_sprites);
-
-            let stage_sprites_mesh =
-                rendering::Mesh::new(&stage_sprites, 0, &gpu.device, "Stage Sprite Mesh");
-
-            (
-                stage_sprite_material.clone(),
-                rendering::Drawable::with(stage_sprites_mesh, stage_sprite_material),
-                collision_space,
-                entities,
-                vec![
-                    rising_floor_entity as Box<dyn entity::Entity>,
-                    exit_door_left_entity as Box<dyn entity::Entity>,
-                    exit_door_right_entity as Box<dyn entity::Entity>,
-                ],
-                stage_animation_flipbooks,
-            )
-        };
+                )))
+            })
+            .collect::<Result<Vec<_>>>()?;
 
-        // Build camera, and camera uniform storage
-        let camera = camera::Camera::new((8.0, 8.0, -1.0), (0.0, 0.0, 1.0), Some(pixels_per_unit));
-        let viewport_scale = if options.gameboy {
-            MIN_CAMERA_SCALE
-        } else {
-            DEFAULT_CAMERA_SCALE
-        };
-        let projection = camera::Projection::new(
-            gpu.config.width,
-            gpu.config.height,
-            viewport_scale,
-            CAMERA_NEAR_PLANE,
-            CAMERA_FAR_PLANE,
+        let stage_sprite_drawable = rendering::Drawable::with_sprites(
+            stage_sprites,
+            stage_materials.clone(),
+            &gpu.device,
+            "Stage Sprite Mesh",
         );
-        let camera_uniforms: camera::Uniforms = util::UniformWrapper::new(&gpu.device);
-        let camera_controller = camera::CameraController::new(camera, projection, camera_uniforms);
 
         // Build the sprite render pipeline
 
+        let camera_uniforms: camera::Uniforms = util::UniformWrapper::new(&gpu.device);
         let mut stage_uniforms = util::UniformWrapper::<rendering::UniformData>::new(&gpu.device);
         let mut stage_debug_draw_overlap_uniforms =
             util::UniformWrapper::<rendering::UniformData>::new(&gpu.device);
@@ -282,7 +156,7 @@ impl GameState {
                 .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
                     bind_group_layouts: &[
                         &material_bind_group_layout,
-                        &camera_controller.uniforms.bind_group_layout,
+                        &camera_uniforms.bind_group_layout,
                         &stage_uniforms.bind_group_layout,
                     ],
                     label: Some("Stage Sprite Pipeline Layout"),
@@ -298,13 +172,20 @@ impl GameState {
 
         // Entities
 
-        let entity_tileset = tileset::TileSet::new_tsx("./res/entities.tsx")
-            .expect("Expected to load entities tileset");
+        let entity_tileset = tilesets
+            .load(entity_tileset_path)
+            .with_context(|| format!("Unable to load entity tileset {:?}", entity_tileset_path))?;
 
         let entity_material = Rc::new({
-            let spritesheet_path = Path::new("res").join(&entity_tileset.image_path);
-            let spritesheet =
-                Rc::new(texture::Texture::load(&gpu.device, &gpu.queue, spritesheet_path).unwrap());
+            let spritesheet_path = entity_tileset_path
+                .parent()
+                .unwrap_or_else(|| Path::new(""))
+                .join(&entity_tileset.image_path);
+            let spritesheet = Rc::new(
+                texture::Texture::load(&gpu.device, &gpu.queue, &spritesheet_path).with_context(
+                    || format!("Unable to load entity tileset image {:?}", spritesheet_path),
+                )?,
+            );
 
             rendering::Material::new(
                 &gpu.device,
@@ -314,23 +195,11 @@ impl GameState {
             )
         });
 
-        let mut entity_add_requests = vec![];
-        for e in entities.into_iter() {
-            entity_add_requests.push(EntityAdditionRequest {
-                entity_id: e.entity_id(),
-                entity: e,
-                needs_init: false,
-            });
-        }
-
         let flipbook_animations = stage_animation_flipbooks
             .into_iter()
             .map(|a| {
-                rendering::FlipbookAnimationDrawable::new(
-                    a,
-                    stage_sprite_material.clone(),
-                    &gpu.device,
-                )
+                let material = stage_materials[a.sprites[0].tileset].clone();
+                rendering::FlipbookAnimationDrawable::new(a, material, &gpu.device)
             })
             .map(|a| {
                 let uniforms = util::UniformWrapper::<rendering::UniformData>::new(&gpu.device);
@@ -363,26 +232,312 @@ impl GameState {
             .set_color(vec4(1.0, 0.0, 0.0, 0.75));
         stage_debug_draw_contact_uniforms.write(&mut gpu.queue);
 
-        let mut game_state = Self {
-            camera_controller,
+        Ok(Self {
             sprite_render_pipeline,
-            stage_material: stage_sprite_material,
+            camera_uniforms,
+            stage_materials,
             stage_uniforms,
             stage_debug_draw_overlap_uniforms,
             stage_debug_draw_contact_uniforms,
             stage_sprite_drawable,
+            entity_tileset,
+            entity_material,
+            flipbook_animations,
+        })
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+
+pub struct GameState {
+    // Camera
+    pub camera_controller: camera::CameraController,
+
+    // Gpu resources; None when running headless
+    rendering: Option<StageRendering>,
+
+    // Collision detection and dispatch
+    map: map::Map,
+    collision_space: collision::Space,
+
+    // Entities, ordered by id such that they update, and draw from the rng, in the same order
+    // on every run with the same seed
+    entities: BTreeMap<u32, entity::EntityComponents>,
+    firebrand_entity_id: Option<u32>,
+    firebrand_start_checkpoint: u32,
+    firebrand_start_lives_remaining: u32,
+    firebrand_start_vials: u32,
+    // when set, firebrand is next created here rather than at the start checkpoint
+    firebrand_start_position: Option<Point2<f32>>,
+    visible_entities: BTreeSet<u32>,
+    entities_to_add: Vec<EntityAdditionRequest>,
+
+    // Positions at the start of the most recent update, for render interpolation
+    previous_entity_positions: HashMap<u32, Point3<f32>>,
+    previous_camera_position: Point3<f32>,
+
+    // Replay support; the input firebrand acted on in the last update, and the input to apply next update
+    firebrand_input: Option<FirebrandInput>,
+    firebrand_input_override: Option<FirebrandInput>,
+
+    // Input state
+    last_mouse_pos: PhysicalPosition<f64>,
+    mouse_pressed: bool,
+
+    // Toggles
+    pub draw_stage_collision_info: bool,
+    pub camera_tracks_character: bool,
+
+    // General game state
+    boss_hit_points: Option<u32>,
+    time: f32,
+    boss_arena_entered_time: Option<f32>,
+    boss_arena_left_bounds: Option<f32>,
+    viewport_left_when_boss_arena_entered: Option<f32>,
+    camera_shaker: Option<CameraShaker>,
+    game_state_peek: GameStatePeek,
+    pub pixels_per_unit: Vector2<f32>,
+    palette_shift: f32,
+    num_restarts: u32,
+}
+
+impl GameState {
+    /// Creates new GameState playing `level`
+    /// start_checkpoint: Index of the checkpoint to place character at
+    /// lives_remaining, vials: Carried by the character from previous levels
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        gpu: &mut gpu_state::GpuState,
+        options: &Options,
+        entity_id_vendor: &mut entity::IdVendor,
+        tilesets: &mut tileset::TileSetCache,
+        level: &campaign::Level,
+        start_checkpoint: u32,
+        lives_remaining: u32,
+        vials: u32,
+    ) -> Result<Self> {
+        let map = Self::load_map(level, tilesets)?;
+        Self::new_from_map(
+            gpu,
+            options,
+            entity_id_vendor,
+            tilesets,
+            level,
+            map,
+            start_checkpoint,
+            lives_remaining,
+            vials,
+        )
+    }
+
+    /// Creates new GameState playing `level`, whose map has already been loaded as `map`
+    #[allow(clippy::too_many_arguments)]
+    pub fn new_from_map(
+        gpu: &mut gpu_state::GpuState,
+        options: &Options,
+        entity_id_vendor: &mut entity::IdVendor,
+        tilesets: &mut tileset::TileSetCache,
+        level: &campaign::Level,
+        map: map::Map,
+        start_checkpoint: u32,
+        lives_remaining: u32,
+        vials: u32,
+    ) -> Result<Self> {
+        let viewport_size = vec2(gpu.config.width, gpu.config.height);
+        Self::build(
+            Some(gpu),
+            viewport_size,
+            options,
+            entity_id_vendor,
+            tilesets,
+            level,
+            map,
+            start_checkpoint,
+            lives_remaining,
+            vials,
+        )
+    }
+
+    /// Creates a GameState which owns no gpu resources, suitable for running the simulation
+    /// without a window. The camera viewport is sized as if for a window of `viewport_size` pixels.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new_headless(
+        viewport_size: Vector2<u32>,
+        options: &Options,
+        entity_id_vendor: &mut entity::IdVendor,
+        tilesets: &mut tileset::TileSetCache,
+        level: &campaign::Level,
+        start_checkpoint: u32,
+        lives_remaining: u32,
+        vials: u32,
+    ) -> Result<Self> {
+        let map = Self::load_map(level, tilesets)?;
+        Self::build(
+            None,
+            viewport_size,
+            options,
+            entity_id_vendor,
+            tilesets,
+            level,
+            map,
+            start_checkpoint,
+            lives_remaining,
+            vials,
+        )
+    }
+
+    /// Loads the stage map of `level`
+    pub fn load_map(
+        level: &campaign::Level,
+        tilesets: &mut tileset::TileSetCache,
+    ) -> Result<map::Map> {
+        map::Map::new_tmx(&level.map, tilesets)
+            .with_context(|| format!("Unable to load map {:?}", level.map))
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn build(
+        gpu: Option<&mut gpu_state::GpuState>,
+        viewport_size: Vector2<u32>,
+        options: &Options,
+        entity_id_vendor: &mut entity::IdVendor,
+        tilesets: &mut tileset::TileSetCache,
+        level: &campaign::Level,
+        map: map::Map,
+        start_checkpoint: u32,
+        lives_remaining: u32,
+        vials: u32,
+    ) -> Result<Self> {
+        let pixels_per_unit = map.tile_size().cast().unwrap();
+
+        // Build the stage from the map's layers; each layer's properties determine its role,
+        // z depth, whether it participates in collision, and which entity (if any) owns it.
+        let (stage_sprites, collision_space, entities, stage_entities, stage_animation_flipbooks) = {
+            let layer_error = || format!("Invalid layer in map {:?}", level.map);
+
+            let mut stage_sprites = vec![];
+            let mut stage_animation_flipbooks = vec![];
+            let mut stage_entities = vec![];
+            let mut level_colliders: Vec<collision::Collider> = vec![];
+            let mut entity_layers = vec![];
+
+            for layer in &map.layers {
+                match layer.role().with_context(layer_error)? {
+                    map::LayerRole::Stage => {
+                        let z_depth = layer.z_depth().with_context(layer_error)?;
+                        let sprites = map.generate_sprites(layer, |_sprite, tile| {
+                            if tile.boolean_property("foreground") {
+                                layers::stage::FOREGROUND
+                            } else {
+                                z_depth
+                            }
+                        });
+                        if layer.collision() {
+                            level_colliders.extend(
+                                sprites.iter().map(collision::Collider::from_static_sprite),
+                            );
+                        }
+                        if layer.animated() {
+                            stage_animation_flipbooks.extend(
+                                map.generate_animations(layer, |_, _| z_depth)
+                                    .with_context(layer_error)?,
+                            );
+                        }
+                        stage_sprites.extend(sprites);
+                    }
+                    map::LayerRole::Entities => entity_layers.push(layer),
+                    map::LayerRole::Entity => {
+                        // The entities which draw these sprites will assign correct z depth at render time
+                        let sprites = map.generate_sprites(layer, |_, _| 0.0);
+                        stage_entities.push(
+                            entities::instantiate_layer_entity(layer, sprites)
+                                .with_context(layer_error)?,
+                        );
+                    }
+                }
+            }
+
+            // generate level entities
+            let mut collision_space = collision::Space::new(&level_colliders);
+            let mut entities = vec![];
+            for layer in entity_layers {
+                entities.extend(map.generate_entities(
+                    layer,
+                    &mut collision_space,
+                    entity_id_vendor,
+                    |_, _| 0.0, // entities assign depth at render time
+                )?);
+            }
+            entities.extend(map.generate_object_entities(&mut collision_space, entity_id_vendor)?);
+
+            (
+                stage_sprites,
+                collision_space,
+                entities,
+                stage_entities,
+                stage_animation_flipbooks,
+            )
+        };
+
+        // Build camera
+        let camera = camera::Camera::new((8.0, 8.0, -1.0), (0.0, 0.0, 1.0), Some(pixels_per_unit));
+        let viewport_scale = if options.gameboy {
+            MIN_CAMERA_SCALE
+        } else {
+            DEFAULT_CAMERA_SCALE
+        };
+        let projection = camera::Projection::new(
+            viewport_size.x,
+            viewport_size.y,
+            viewport_scale,
+            CAMERA_NEAR_PLANE,
+            CAMERA_FAR_PLANE,
+        );
+        let camera_controller = camera::CameraController::new(camera, projection);
+
+        let mut gpu = gpu;
+        let rendering = gpu
+            .as_deref_mut()
+            .map(|gpu| {
+                StageRendering::new(
+                    gpu,
+                    &map,
+                    &level.entity_tileset,
+                    tilesets,
+                    &stage_sprites,
+                    stage_animation_flipbooks,
+                    pixels_per_unit,
+                )
+            })
+            .transpose()?;
+
+        let mut entity_add_requests = vec![];
+        for e in entities.into_iter() {
+            entity_add_requests.push(EntityAdditionRequest {
+                entity_id: e.entity_id(),
+                entity: e,
+                needs_init: false,
+            });
+        }
+
+        let mut game_state = Self {
+            camera_controller,
+            rendering,
 
             map,
             collision_space,
-            entity_tileset,
-            entity_material,
-            entities: HashMap::new(),
+            entities: BTreeMap::new(),
             firebrand_entity_id: None,
             firebrand_start_checkpoint: start_checkpoint,
             firebrand_start_lives_remaining: lives_remaining,
-            visible_entities: HashSet::new(),
+            firebrand_start_vials: vials,
+            firebrand_start_position: None,
+            visible_entities: BTreeSet::new(),
             entities_to_add: Vec::new(),
-            flipbook_animations,
+            previous_entity_positions: HashMap::new(),
+            previous_camera_position: point3(0.0, 0.0, 0.0),
+            firebrand_input: None,
+            firebrand_input_override: None,
 
             last_mouse_pos: (0, 0).into(),
             mouse_pressed: false,
@@ -390,6 +545,7 @@ impl GameState {
             draw_stage_collision_info: false,
             camera_tracks_character: true,
 
+            boss_hit_points: level.boss_hit_points,
             time: 0.0,
             boss_arena_entered_time: None,
             boss_arena_left_bounds: None,
@@ -402,14 +558,14 @@ impl GameState {
         };
 
         for req in entity_add_requests {
-            game_state.add_entity(gpu, req);
+            game_state.add_entity(gpu.as_deref_mut(), req);
         }
 
         for se in stage_entities {
             game_state.request_add_entity(entity_id_vendor, se);
         }
 
-        game_state
+        Ok(game_state)
     }
 
     pub fn resize(
@@ -433,18 +589,7 @@ impl GameState {
                         ..
                     },
                 ..
-            } => {
-                let mut consumed = false;
-                if !is_paused {
-                    for e in self.entities.values_mut() {
-                        if e.entity.process_keyboard(*key_code, *state) {
-                            consumed = true;
-                            break;
-                        }
-                    }
-                }
-                consumed || self.camera_controller.process_keyboard(*key_code, *state)
-            }
+            } => self.process_keyboard(*key_code, *state, is_paused),
             WindowEvent::MouseWheel { delta, .. } => {
                 self.camera_controller.process_scroll(delta);
                 true
@@ -472,22 +617,58 @@ impl GameState {
         }
     }
 
-    pub fn gamepad_input(&mut self, event: gilrs::Event, is_paused: bool) {
+    /// Routes a key event to entities, and then the camera controller, returning true if consumed.
+    pub fn process_keyboard(
+        &mut self,
+        key_code: KeyCode,
+        state: ElementState,
+        is_paused: bool,
+    ) -> bool {
+        let mut consumed = false;
         if !is_paused {
             for e in self.entities.values_mut() {
-                e.entity.process_gamepad(event);
+                if e.entity.process_keyboard(key_code, state) {
+                    consumed = true;
+                    break;
+                }
             }
         }
+        consumed || self.camera_controller.process_keyboard(key_code, state)
     }
 
+    /// Routes an action to entities, returning true if consumed.
+    pub fn process_action(&mut self, action: Action, state: ElementState, is_paused: bool) -> bool {
+        if !is_paused {
+            for e in self.entities.values_mut() {
+                if e.entity.process_action(action, state) {
+                    return true;
+                }
+            }
+        }
+        false
+    }
+
+    /// Advances the simulation by `ctx.game_delta_time`. Gpu state is not touched here beyond
+    /// creating drawables for new entities; uniforms are written by `prepare_render`.
     pub fn update(&mut self, ctx: &mut AppContext) {
         self.collision_space.update();
 
+        //
+        //  Note positions before this step, for render interpolation
+        //
+
+        self.previous_entity_positions.clear();
+        for (id, e) in &self.entities {
+            self.previous_entity_positions
+                .insert(*id, e.entity.position());
+        }
+        self.previous_camera_position = self.camera_controller.camera.position();
+
         //
         //  Process pending entity additions
         //
 
-        self.process_entity_additions(ctx.gpu);
+        self.process_entity_additions(ctx.gpu.as_deref_mut());
 
         //
         // If firebrand hasn't been constructed yet, we need to instantiate him at the assigned checkpoint
@@ -501,18 +682,22 @@ impl GameState {
                 .collect::<Vec<_>>();
             let checkpoint_idx =
                 (self.firebrand_start_checkpoint as usize).min(positions.len() - 1);
-            let position = positions[checkpoint_idx];
+            let position = self
+                .firebrand_start_position
+                .take()
+                .unwrap_or_else(|| positions[checkpoint_idx].xy());
 
             // create firebrand and immediately process addition request since update()
             // depends on firebrand's location.
             self.firebrand_entity_id = Some(self.request_add_entity(
                 ctx.entity_id_vendor,
                 Box::new(entities::firebrand::Firebrand::new(
-                    position.xy(),
+                    position,
                     self.firebrand_start_lives_remaining,
+                    self.firebrand_start_vials,
                 )),
             ));
-            self.process_entity_additions(ctx.gpu);
+            self.process_entity_additions(ctx.gpu.as_deref_mut());
 
             ctx.message_dispatcher.broadcast(Event::FirebrandCreated {
                 checkpoint: self.firebrand_start_checkpoint,
@@ -526,7 +711,20 @@ impl GameState {
         self.game_state_peek.player_position = firebrand.position().xy();
         self.game_state_peek.current_map_bounds = current_map_bounds;
         self.game_state_peek.camera_position = self.camera_controller.camera.position().xy();
-        let palette_shift = self.palette_shift();
+
+        //
+        //  Apply any replayed input to firebrand, and note the input firebrand will act on
+        //
+
+        if let Some(firebrand) = self
+            .firebrand_entity_id
+            .and_then(|id| self.entities.get_mut(&id))
+        {
+            if let Some(input) = self.firebrand_input_override.take() {
+                firebrand.entity.override_user_input(input);
+            }
+            self.firebrand_input = firebrand.entity.user_input();
+        }
 
         //
         //  Update entities - if any are expired, remove them.
@@ -544,15 +742,8 @@ impl GameState {
                     ctx.audio,
                     ctx.message_dispatcher,
                     &game_state_peek,
+                    ctx.rng,
                 );
-                if let Some(ref mut uniforms) = e.uniforms {
-                    e.entity.update_uniforms(uniforms);
-                    uniforms
-                        .data
-                        .set_pixels_per_unit(self.pixels_per_unit)
-                        .set_palette_shift(palette_shift);
-                    uniforms.write(&mut ctx.gpu.queue);
-                }
 
                 if !e.entity.is_alive() {
                     e.entity.deactivate_collider(&mut self.collision_space);
@@ -565,22 +756,6 @@ impl GameState {
             }
         }
 
-        self.stage_uniforms.data.set_palette_shift(palette_shift);
-        self.stage_uniforms.write(&mut ctx.gpu.queue);
-
-        //
-        //  Update flipbook animations
-        //
-
-        for a in &mut self.flipbook_animations {
-            a.update(ctx.game_delta_time);
-            a.uniforms
-                .data
-                .set_pixels_per_unit(self.pixels_per_unit)
-                .set_palette_shift(palette_shift);
-            a.uniforms.write(&mut ctx.gpu.queue);
-        }
-
         //
         // Update camera state
         //
@@ -602,7 +777,16 @@ impl GameState {
             offset,
             Some(current_map_bounds),
         );
-        self.camera_controller.uniforms.write(&mut ctx.gpu.queue);
+
+        //
+        //  Update flipbook animations
+        //
+
+        if let Some(rendering) = &mut self.rendering {
+            for a in &mut rendering.flipbook_animations {
+                a.update(ctx.game_delta_time);
+            }
+        }
 
         //
         //  Notify entities of their visibility
@@ -611,12 +795,78 @@ impl GameState {
         self.update_entity_visibility();
     }
 
+    /// Writes entity, stage and camera uniforms for rendering. `interpolation` is the fraction
+    /// of a simulation step which has elapsed since the last update; entity and camera positions
+    /// are interpolated between their previous and current step positions by this amount.
+    pub fn prepare_render(&mut self, gpu: &mut gpu_state::GpuState, interpolation: f32) {
+        let palette_shift = self.palette_shift();
+        let Some(rendering) = &mut self.rendering else {
+            return;
+        };
+
+        let interpolated = |previous: Option<&Point3<f32>>, current: Point3<f32>| {
+            // don't interpolate across teleports, e.g., restarting at a checkpoint
+            match previous {
+                Some(previous) if previous.distance2(current) < MAX_INTERPOLATION_DISTANCE_SQ => {
+                    *previous + (current - *previous) * interpolation
+                }
+                _ => current,
+            }
+        };
+
+        for e in self.entities.values_mut() {
+            let id = e.id();
+            if let Some(ref mut uniforms) = e.uniforms {
+                e.entity.update_uniforms(uniforms);
+
+                let position = e.entity.position();
+                let render_position =
+                    interpolated(self.previous_entity_positions.get(&id), position);
+                uniforms
+                    .data
+                    .offset_model_position(render_position - position)
+                    .set_pixels_per_unit(self.pixels_per_unit)
+                    .set_palette_shift(palette_shift);
+                uniforms.write(&mut gpu.queue);
+            }
+        }
+
+        rendering
+            .stage_uniforms
+            .data
+            .set_palette_shift(palette_shift);
+        rendering.stage_uniforms.write(&mut gpu.queue);
+
+        for a in &mut rendering.flipbook_animations {
+            a.uniforms
+                .data
+                .set_pixels_per_unit(self.pixels_per_unit)
+                .set_palette_shift(palette_shift);
+            a.uniforms.write(&mut gpu.queue);
+        }
+
+        let mut camera = self.camera_controller.camera.clone();
+        camera.set_position(interpolated(
+            Some(&self.previous_camera_position),
+            camera.position(),
+        ));
+        rendering
+            .camera_uniforms
+            .data
+            .update_view_proj(&camera, &self.camera_controller.projection);
+        rendering.camera_uniforms.write(&mut gpu.queue);
+    }
+
     pub fn render(
         &mut self,
         gpu: &mut gpu_state::GpuState,
         encoder: &mut wgpu::CommandEncoder,
         frame_index: usize,
     ) {
+        let Some(rendering) = &self.rendering else {
+            return;
+        };
+
         //
         // Render Sprites and entities; this is first pass so we clear color/depth
         //
@@ -653,41 +903,38 @@ impl GameState {
             occlusion_query_set: None,
         });
 
-        render_pass.set_pipeline(&self.sprite_render_pipeline);
+        render_pass.set_pipeline(&rendering.sprite_render_pipeline);
 
         // Render stage
-        self.stage_sprite_drawable.draw(
+        rendering.stage_sprite_drawable.draw(
             &mut render_pass,
-            &self.camera_controller.uniforms,
-            &self.stage_uniforms,
+            &rendering.camera_uniforms,
+            &rendering.stage_uniforms,
         );
 
         // Render flipbook animations
-        for a in &self.flipbook_animations {
-            a.drawable.draw(
-                &mut render_pass,
-                &self.camera_controller.uniforms,
-                &a.uniforms,
-            );
+        for a in &rendering.flipbook_animations {
+            a.drawable
+                .draw(&mut render_pass, &rendering.camera_uniforms, &a.uniforms);
         }
 
         if self.draw_stage_collision_info {
             for e in self.entities.values() {
                 if let Some(overlapping) = e.entity.overlapping_sprites() {
-                    self.stage_sprite_drawable.draw_sprites(
+                    rendering.stage_sprite_drawable.draw_sprites(
                         overlapping,
                         &mut render_pass,
-                        &self.camera_controller.uniforms,
-                        &self.stage_debug_draw_overlap_uniforms,
+                        &rendering.camera_uniforms,
+                        &rendering.stage_debug_draw_overlap_uniforms,
                     );
                 }
 
                 if let Some(contacting) = e.entity.contacting_sprites() {
-                    self.stage_sprite_drawable.draw_sprites(
+                    rendering.stage_sprite_drawable.draw_sprites(
                         contacting,
                         &mut render_pass,
-                        &self.camera_controller.uniforms,
-                        &self.stage_debug_draw_contact_uniforms,
+                        &rendering.camera_uniforms,
+                        &rendering.stage_debug_draw_contact_uniforms,
                     );
                 }
             }
@@ -700,7 +947,7 @@ impl GameState {
                     if let Some(ref uniforms) = e.uniforms {
                         drawable.draw(
                             &mut render_pass,
-                            &self.camera_controller.uniforms,
+                            &rendering.camera_uniforms,
                             uniforms,
                             e.entity.sprite_cycle(),
                         );
@@ -708,7 +955,7 @@ impl GameState {
                 }
                 if let Some(ref drawable) = e.sprite_drawable {
                     if let Some(ref uniforms) = e.uniforms {
-                        drawable.draw(&mut render_pass, &self.camera_controller.uniforms, uniforms);
+                        drawable.draw(&mut render_pass, &rendering.camera_uniforms, uniforms);
                     }
                 }
             }
@@ -744,25 +991,23 @@ impl GameState {
                     direction,
                     velocity,
                     damage,
-                } => {
-                    if self.player_can_shoot_fireball() {
-                        self.request_add_entity(
-                            entity_id_vendor,
-                            Box::new(entities::fireball::Fireball::new_fireball(
-                                self.firebrand_entity_id.unwrap(),
-                                origin.xy(),
-                                *direction,
-                                *velocity,
-                                *damage,
-                            )),
-                        );
-
-                        // Reply to firebrand that a shot was fired
-                        message_dispatcher.global_to_entity(
+                } if self.player_can_shoot_fireball() => {
+                    self.request_add_entity(
+                        entity_id_vendor,
+                        Box::new(entities::fireball::Fireball::new_fireball(
                             self.firebrand_entity_id.unwrap(),
-                            Event::DidShootFireball,
-                        );
-                    }
+                            origin.xy(),
+                            *direction,
+                            *velocity,
+                            *damage,
+                        )),
+                    );
+
+                    // Reply to firebrand that a shot was fired
+                    message_dispatcher.global_to_entity(
+                        self.firebrand_entity_id.unwrap(),
+                        Event::DidShootFireball,
+                    );
                 }
 
                 Event::PlayEntityDeathAnimation {
@@ -781,11 +1026,13 @@ impl GameState {
                     class_name,
                     spawn_point_sprite,
                     spawn_point_tile,
+                    spawn_point_properties,
                 } => {
                     match entities::instantiate_map_sprite(
                         class_name,
                         spawn_point_sprite,
                         spawn_point_tile,
+                        spawn_point_properties,
                         &self.map,
                         &mut self.collision_space,
                         Some(entity_id_vendor),
@@ -859,13 +1106,16 @@ impl GameState {
                     self.on_level_complete();
                 }
 
-                Event::QueryBossFightMayStart => {
-                    if self.boss_arena_entered_time.is_some() {
-                        let sender = message
-                            .sender_entity_id
-                            .expect("TryBossRaise must be sent by Boss entity");
-                        message_dispatcher.global_to_entity(sender, Event::BossFightMayStart);
-                    }
+                Event::QueryBossFightMayStart if self.boss_arena_entered_time.is_some() => {
+                    let sender = message
+                        .sender_entity_id
+                        .expect("TryBossRaise must be sent by Boss entity");
+                    message_dispatcher.global_to_entity(
+                        sender,
+                        Event::BossFightMayStart {
+                            hit_points: self.boss_hit_points,
+                        },
+                    );
                 }
 
                 _ => {}
@@ -885,11 +1135,13 @@ impl GameState {
         &mut self,
         start_checkpoint: u32,
         lives_remaining: u32,
+        vials: u32,
         message_dispatcher: &mut event_dispatch::Dispatcher,
     ) {
         self.num_restarts += 1;
         self.firebrand_start_checkpoint = start_checkpoint;
         self.firebrand_start_lives_remaining = lives_remaining;
+        self.firebrand_start_vials = vials;
 
         self.firebrand_entity_id = None;
         self.visible_entities.clear();
@@ -912,14 +1164,82 @@ impl GameState {
         message_dispatcher.broadcast(Event::ResetState);
     }
 
-    pub fn game_over(&mut self, message_dispatcher: &mut event_dispatch::Dispatcher) {
-        message_dispatcher.broadcast(Event::GameOver);
+    /// Replaces this GameState's stage - map, colliders, entities and their gpu resources - with
+    /// those of `reloaded`, a GameState freshly built from the same level, e.g., after its map was
+    /// edited. The camera and palette are kept, and the stage restarts as by restart_game_at_checkpoint,
+    /// except that firebrand is recreated where he stands with his current lives and vials.
+    pub fn reload_stage(
+        &mut self,
+        mut reloaded: GameState,
+        start_checkpoint: u32,
+        message_dispatcher: &mut event_dispatch::Dispatcher,
+    ) {
+        let (firebrand_position, lives_remaining, vials) = match self.try_get_firebrand() {
+            Some(firebrand) => (
+                Some(firebrand.entity.position().xy()),
+                self.game_state_peek.player_lives,
+                self.game_state_peek.player_vials,
+            ),
+            None => (
+                self.firebrand_start_position,
+                self.firebrand_start_lives_remaining,
+                self.firebrand_start_vials,
+            ),
+        };
+
+        std::mem::swap(&mut reloaded.camera_controller, &mut self.camera_controller);
+        reloaded.draw_stage_collision_info = self.draw_stage_collision_info;
+        reloaded.camera_tracks_character = self.camera_tracks_character;
+        reloaded.palette_shift = self.palette_shift;
+        reloaded.num_restarts = self.num_restarts;
+        *self = reloaded;
+
+        self.restart_game_at_checkpoint(
+            start_checkpoint,
+            lives_remaining,
+            vials,
+            message_dispatcher,
+        );
+        self.firebrand_start_position = firebrand_position;
+    }
+
+    /// The input firebrand acted on during the most recent update, if firebrand exists.
+    pub fn firebrand_input(&self) -> Option<FirebrandInput> {
+        self.firebrand_input
+    }
+
+    /// Replaces the input firebrand will act on during the next update; used for replay playback.
+    pub fn override_firebrand_input(&mut self, input: FirebrandInput) {
+        self.firebrand_input_override = Some(input);
+    }
+
+    /// Returns true once the player has entered the boss arena, until the next restart.
+    pub fn boss_arena_entered(&self) -> bool {
+        self.boss_arena_entered_time.is_some()
+    }
+
+    pub fn game_over(
+        &mut self,
+        continues_remaining: u32,
+        message_dispatcher: &mut event_dispatch::Dispatcher,
+    ) {
+        message_dispatcher.broadcast(Event::GameOver {
+            continues_remaining,
+        });
     }
 
     pub fn game_state_peek(&self) -> GameStatePeek {
         self.game_state_peek
     }
 
+    /// Returns the id and position of every entity, in id order.
+    pub fn entity_positions(&self) -> Vec<(u32, Point3<f32>)> {
+        self.entities
+            .iter()
+            .map(|(id, e)| (*id, e.entity.position()))
+            .collect()
+    }
+
     pub fn entities_of_type(&self, entity_class: entities::EntityClass) -> Vec<&EntityComponents> {
         self.entities
             .values()
@@ -1075,38 +1395,56 @@ impl GameState {
         }
     }
 
-    /// Adds the entity specified in the request
-    fn add_entity(&mut self, gpu: &mut gpu_state::GpuState, mut req: EntityAdditionRequest) {
+    /// Adds the entity specified in the request. When running headless (no gpu) the entity
+    /// is added without drawables or uniforms.
+    fn add_entity(
+        &mut self,
+        gpu: Option<&mut gpu_state::GpuState>,
+        mut req: EntityAdditionRequest,
+    ) {
         if req.needs_init {
             req.entity
                 .init(req.entity_id, &self.map, &mut self.collision_space);
         }
 
+        let (Some(gpu), Some(rendering)) = (gpu, &self.rendering) else {
+            let components = EntityComponents::just_entity(req.entity);
+            self.entities.insert(components.id(), components);
+            return;
+        };
+
         let components = if !req.entity.sprite_name().is_empty() {
             let sprite_name = req.entity.sprite_name().to_string();
             // The Entity has specified a sprite name, which means it's using
-            // an EntityDrawable to render.
-            let uniforms = util::UniformWrapper::<rendering::UniformData>::new(&gpu.device);
-            EntityComponents::with_entity_drawable(
-                req.entity,
-                rendering::EntityDrawable::load(
-                    &self.entity_tileset,
-                    self.entity_material.clone(),
-                    &gpu.device,
-                    &sprite_name,
-                    0,
-                ),
-                uniforms,
-            )
+            // an EntityDrawable to render. If the sprite can't be loaded, the entity
+            // is added regardless, but isn't drawn.
+            match rendering::EntityDrawable::load(
+                &rendering.entity_tileset,
+                rendering.entity_material.clone(),
+                &gpu.device,
+                &sprite_name,
+                0,
+            ) {
+                Ok(drawable) => {
+                    let uniforms = util::UniformWrapper::<rendering::UniformData>::new(&gpu.device);
+                    EntityComponents::with_entity_drawable(req.entity, drawable, uniforms)
+                }
+                Err(e) => {
+                    log::error!("Unable to load sprite \"{}\": {:#}", sprite_name, e);
+                    EntityComponents::just_entity(req.entity)
+                }
+            }
         } else if let Some(sprites) = req.entity.stage_sprites() {
             // The Entity has specified sprites to render, which means its using a
             // sprite::Drawable using the stage material to render.
             let uniforms = util::UniformWrapper::<rendering::UniformData>::new(&gpu.device);
             EntityComponents::with_sprite_drawable(
                 req.entity,
-                rendering::Drawable::with(
-                    rendering::Mesh::new(&sprites, 0, &gpu.device, "Entity Stage Sprite Mesh"),
-                    self.stage_material.clone(),
+                rendering::Drawable::with_sprites(
+                    &sprites,
+                    rendering.stage_materials.clone(),
+                    &gpu.device,
+                    "Entity Stage Sprite Mesh",
                 ),
                 uniforms,
             )
@@ -1118,9 +1456,9 @@ impl GameState {
     }
 
     /// Adds all entities in the entities_to_add queue
-    fn process_entity_additions(&mut self, gpu: &mut gpu_state::GpuState) {
+    fn process_entity_additions(&mut self, mut gpu: Option<&mut gpu_state::GpuState>) {
         for addition in std::mem::take(&mut self.entities_to_add) {
-            self.add_entity(gpu, addition);
+            self.add_entity(gpu.as_deref_mut(), addition);
         }
     }
 
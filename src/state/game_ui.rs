@@ -1,17 +1,14 @@
+use anyhow::{Context, Result};
 use cgmath::*;
 use std::{collections::HashMap, path::Path, rc::Rc, time::Duration};
 
-use winit::{
-    event::{ElementState, KeyEvent, WindowEvent},
-    keyboard::{KeyCode, PhysicalKey},
-    window::Window,
-};
+use winit::{event::ElementState, window::Window};
 
-use crate::{audio, camera, sprite::rendering, state::gpu_state};
+use crate::{audio, camera, input::Action, sprite::rendering, state::gpu_state};
 use crate::{
     collision,
     entity::{self, EntityComponents},
-    texture,
+    texture, tileset,
 };
 use crate::{event_dispatch, Options};
 use crate::{
@@ -22,8 +19,10 @@ use crate::{
 use super::{
     app_state::AppContext,
     constants::{layers, CAMERA_FAR_PLANE, CAMERA_NEAR_PLANE, DEFAULT_CAMERA_SCALE},
-    events::Event,
+    continue_menu::{ContinueChoice, ContinueMenu},
+    events::{Event, UiCommand},
     game_state,
+    pause_menu::{MenuCommand, PauseMenu},
 };
 
 // ---------------------------------------------------------------------------------------------------------------------
@@ -31,6 +30,12 @@ use super::{
 const DRAWER_OPEN_VEL: f32 = 2.0;
 const START_MESSAGE_DURATION: f32 = 2.0;
 const START_MESSAGE_BLINK_PERIOD: f32 = 0.25;
+const TITLE_PROMPT_BLINK_PERIOD: f32 = 0.5;
+// how long the game over message shows before the continue prompt, or the title screen
+const GAME_OVER_MESSAGE_DURATION: f32 = 5.0;
+// how long the player has to accept a continue before returning to the title screen
+const CONTINUE_DURATION: f32 = 10.0;
+const VOLUME_STEP: f32 = 0.1;
 
 // ---------------------------------------------------------------------------------------------------------------------
 
@@ -55,6 +60,25 @@ pub struct GameUi {
     level_complete_uniforms: rendering::Uniforms,
     entities: HashMap<u32, entity::EntityComponents>,
 
+    // title screen and pause menu; the title prompt, and the pause menu's cursor and
+    // entities are positioned relative to the title and pause menu respectively
+    title_drawable: rendering::Drawable,
+    title_prompt_drawable: rendering::Drawable,
+    title_uniforms: rendering::Uniforms,
+    pause_menu_drawable: rendering::Drawable,
+    pause_menu_uniforms: rendering::Uniforms,
+    pause_menu_cursor_drawable: rendering::Drawable,
+    pause_menu_cursor_uniforms: rendering::Uniforms,
+    pause_menu_entities: HashMap<u32, entity::EntityComponents>,
+
+    // continue prompt shown after a game over; the cursor and entities are positioned
+    // relative to the prompt
+    continue_drawable: rendering::Drawable,
+    continue_uniforms: rendering::Uniforms,
+    continue_cursor_drawable: rendering::Drawable,
+    continue_cursor_uniforms: rendering::Uniforms,
+    continue_entities: HashMap<u32, entity::EntityComponents>,
+
     // state
     time: f32,
     drawer_open: bool,
@@ -65,6 +89,22 @@ pub struct GameUi {
     pixels_per_unit: Vector2<f32>,
     palette_shift: f32,
     toggle_drawer_needed: bool,
+    pause_menu: PauseMenu,
+    pending_menu_command: Option<MenuCommand>,
+    menu_selection_changed: bool,
+    title_visible: bool,
+    title_time: f32,
+    start_game_needed: bool,
+    // set when the title screen follows a game over, such that starting begins a new game
+    new_game_needed: bool,
+    game_over_countdown: Option<f32>,
+    continue_menu: ContinueMenu,
+    pending_continue: Option<ContinueChoice>,
+    continues_remaining: u32,
+    // counts down while the continue prompt shows
+    continue_countdown: Option<f32>,
+    // requested by the player, for AppState to broadcast at the next simulation step
+    commands: Vec<UiCommand>,
 }
 
 impl GameUi {
@@ -72,11 +112,14 @@ impl GameUi {
         gpu: &mut gpu_state::GpuState,
         _options: &Options,
         entity_id_vendor: &mut entity::IdVendor,
-    ) -> Self {
+        tilesets: &mut tileset::TileSetCache,
+    ) -> Result<Self> {
         // load game ui map and construct material/drawable etcs
-        let game_ui_map = map::Map::new_tmx(Path::new("res/game_ui.tmx"));
-        let game_ui_map = game_ui_map.expect("Expected 'res/game_ui.tmx' to load");
-        let pixels_per_unit = game_ui_map.tileset.get_sprite_size().cast().unwrap();
+        let game_ui_map = map::Map::new_tmx(Path::new("res/game_ui.tmx"), tilesets)
+            .context("Unable to load game ui map")?;
+        let pixels_per_unit = game_ui_map.tile_size().cast().unwrap();
+        // the ui is drawn from a single tileset
+        let ui_tileset = &game_ui_map.tilesets[0];
 
         // build camera
         let camera_view =
@@ -96,9 +139,17 @@ impl GameUi {
 
         let bind_group_layout = rendering::Material::bind_group_layout(&gpu.device);
         let sprite_material = {
-            let spritesheet_path = Path::new("res").join(&game_ui_map.tileset.image_path);
-            let spritesheet =
-                Rc::new(texture::Texture::load(&gpu.device, &gpu.queue, spritesheet_path).unwrap());
+            let spritesheet_path = &ui_tileset.image_path;
+            let spritesheet = Rc::new(
+                texture::Texture::load(&gpu.device, &gpu.queue, spritesheet_path).with_context(
+                    || {
+                        format!(
+                            "Unable to load game ui tileset image {:?}",
+                            spritesheet_path
+                        )
+                    },
+                )?,
+            );
             Rc::new(rendering::Material::new(
                 &gpu.device,
                 "UI Sprite Material",
@@ -135,59 +186,99 @@ impl GameUi {
         let get_layer = |name: &str| {
             game_ui_map
                 .layer_named(name)
-                .unwrap_or_else(|| panic!("Expect layer named \"{}\"", name))
+                .with_context(|| format!("Expected game ui map layer named \"{}\"", name))
         };
 
-        let create_drawable = |name: &str, z: f32| {
-            let layer = get_layer(name);
+        let create_drawable = |name: &str, z: f32| -> Result<rendering::Drawable> {
+            let layer = get_layer(name)?;
             let sprites = game_ui_map.generate_sprites(layer, |_, _| z);
             let mesh = rendering::Mesh::new(&sprites, 0, &gpu.device, name);
-            rendering::Drawable::with(mesh, sprite_material.clone())
+            Ok(rendering::Drawable::with(mesh, sprite_material.clone()))
         };
 
-        let drawer_drawable = create_drawable("Drawer", layers::ui::BACKGROUND);
-        let game_over_drawable = create_drawable("GameOver", layers::ui::FOREGROUND);
-        let game_start_drawable = create_drawable("GameStart", layers::ui::FOREGROUND);
-        let level_complete_drawable = create_drawable("LevelComplete", layers::ui::FOREGROUND);
+        let drawer_drawable = create_drawable("Drawer", layers::ui::BACKGROUND)?;
+        let game_over_drawable = create_drawable("GameOver", layers::ui::FOREGROUND)?;
+        let game_start_drawable = create_drawable("GameStart", layers::ui::FOREGROUND)?;
+        let level_complete_drawable = create_drawable("LevelComplete", layers::ui::FOREGROUND)?;
+        let title_drawable = create_drawable("Title", layers::ui::MENU_BACKGROUND)?;
+        let title_prompt_drawable = create_drawable("TitlePrompt", layers::ui::MENU_FOREGROUND)?;
+        let pause_menu_drawable = create_drawable("PauseMenu", layers::ui::MENU_BACKGROUND)?;
+        let pause_menu_cursor_drawable =
+            create_drawable("PauseMenuCursor", layers::ui::MENU_FOREGROUND)?;
+        let continue_drawable = create_drawable("Continue", layers::ui::MENU_BACKGROUND)?;
+        let continue_cursor_drawable =
+            create_drawable("ContinueCursor", layers::ui::MENU_FOREGROUND)?;
 
         //
         //  Load entities
         //
 
         let mut collision_space = collision::Space::new(&[]);
-        let entities_layer = get_layer("Entities");
+        let entities_layer = get_layer("Entities")?;
+        let get_object_group = |name: &str| {
+            game_ui_map
+                .object_group_named(name)
+                .with_context(|| format!("Expected game ui map object group named \"{}\"", name))
+        };
 
-        let entities = game_ui_map.generate_entities(
+        let mut entities = game_ui_map.generate_entities(
             entities_layer,
             &mut collision_space,
             entity_id_vendor,
             |_, _| 0.0,
-        );
+        )?;
+        entities.extend(game_ui_map.generate_object_group_entities(
+            get_object_group("EntityObjects")?,
+            &mut collision_space,
+            entity_id_vendor,
+        )?);
+        let pause_menu_entities = game_ui_map.generate_object_group_entities(
+            get_object_group("PauseMenuObjects")?,
+            &mut collision_space,
+            entity_id_vendor,
+        )?;
+        let continue_entities = game_ui_map.generate_object_group_entities(
+            get_object_group("ContinueObjects")?,
+            &mut collision_space,
+            entity_id_vendor,
+        )?;
 
         // convert entities to a mapping of id -> EntityComponents
-        let entities = entities
-            .into_iter()
-            .map(|e| {
-                let sprite_name = e.sprite_name().to_string();
-                let ec = EntityComponents::with_entity_drawable(
-                    e,
-                    rendering::EntityDrawable::load(
-                        &game_ui_map.tileset,
-                        sprite_material.clone(),
-                        &gpu.device,
-                        &sprite_name,
-                        0,
-                    ),
-                    util::UniformWrapper::<rendering::UniformData>::new(&gpu.device),
-                );
-                (ec.id(), ec)
-            })
-            .collect::<HashMap<_, _>>();
+        let to_entity_components = |entities: Vec<Box<dyn entity::Entity>>| {
+            entities
+                .into_iter()
+                .map(|e| {
+                    let sprite_name = e.sprite_name().to_string();
+                    let ec = EntityComponents::with_entity_drawable(
+                        e,
+                        rendering::EntityDrawable::load(
+                            &ui_tileset.tileset,
+                            sprite_material.clone(),
+                            &gpu.device,
+                            &sprite_name,
+                            0,
+                        )?,
+                        util::UniformWrapper::<rendering::UniformData>::new(&gpu.device),
+                    );
+                    Ok((ec.id(), ec))
+                })
+                .collect::<Result<HashMap<_, _>>>()
+        };
+        let entities = to_entity_components(entities)?;
+        let pause_menu_entities = to_entity_components(pause_menu_entities)?;
+        let continue_entities = to_entity_components(continue_entities)?;
 
         let game_over_uniforms = util::UniformWrapper::<rendering::UniformData>::new(&gpu.device);
         let game_start_uniforms = util::UniformWrapper::<rendering::UniformData>::new(&gpu.device);
         let level_complete_uniforms =
             util::UniformWrapper::<rendering::UniformData>::new(&gpu.device);
+        let title_uniforms = util::UniformWrapper::<rendering::UniformData>::new(&gpu.device);
+        let pause_menu_uniforms = util::UniformWrapper::<rendering::UniformData>::new(&gpu.device);
+        let pause_menu_cursor_uniforms =
+            util::UniformWrapper::<rendering::UniformData>::new(&gpu.device);
+        let continue_uniforms = util::UniformWrapper::<rendering::UniformData>::new(&gpu.device);
+        let continue_cursor_uniforms =
+            util::UniformWrapper::<rendering::UniformData>::new(&gpu.device);
 
         let mut game_ui = Self {
             pipeline,
@@ -208,6 +299,19 @@ impl GameUi {
             level_complete_drawable,
             level_complete_uniforms,
             entities,
+            title_drawable,
+            title_prompt_drawable,
+            title_uniforms,
+            pause_menu_drawable,
+            pause_menu_uniforms,
+            pause_menu_cursor_drawable,
+            pause_menu_cursor_uniforms,
+            pause_menu_entities,
+            continue_drawable,
+            continue_uniforms,
+            continue_cursor_drawable,
+            continue_cursor_uniforms,
+            continue_entities,
 
             time: 0.0,
             drawer_open: false,
@@ -218,11 +322,24 @@ impl GameUi {
             pixels_per_unit,
             palette_shift: 0.0,
             toggle_drawer_needed: false,
+            pause_menu: PauseMenu::default(),
+            pending_menu_command: None,
+            menu_selection_changed: false,
+            title_visible: false,
+            title_time: 0.0,
+            start_game_needed: false,
+            new_game_needed: false,
+            game_over_countdown: None,
+            continue_menu: ContinueMenu::default(),
+            pending_continue: None,
+            continues_remaining: 0,
+            continue_countdown: None,
+            commands: vec![],
         };
 
         game_ui.update_drawer_position(Duration::from_secs(0));
 
-        game_ui
+        Ok(game_ui)
     }
 
     pub fn resize(
@@ -235,46 +352,66 @@ impl GameUi {
             .resize(new_size.width, new_size.height);
     }
 
-    pub fn input(&mut self, _window: &Window, event: &WindowEvent) -> bool {
-        match event {
-            WindowEvent::KeyboardInput {
-                event:
-                    KeyEvent {
-                        physical_key: PhysicalKey::Code(key_code),
-                        state,
-                        ..
-                    },
-                ..
-            } => match (key_code, state) {
-                (KeyCode::F1, ElementState::Pressed) => {
-                    self.toggle_drawer_needed = true;
-                    true
-                }
-                _ => false,
-            },
-            _ => false,
+    /// Handles an action from keyboard or gamepad input, returning true if consumed. On the title
+    /// screen, pause or shoot start the game; while paused, actions navigate the pause menu, and
+    /// after a game over they navigate the continue prompt.
+    pub fn process_action(&mut self, action: Action, state: ElementState) -> bool {
+        if state != ElementState::Pressed {
+            return false;
         }
-    }
 
-    pub fn gamepad_input(&mut self, event: gilrs::Event) {
-        if let gilrs::EventType::ButtonPressed(button, ..) = event.event {
-            if matches!(button, gilrs::Button::Start) {
-                self.toggle_drawer_needed = true;
+        if self.title_visible {
+            if matches!(action, Action::Pause | Action::Shoot) {
+                self.start_game_needed = true;
             }
+            true
+        } else if self.drawer_open {
+            let selected_index = self.pause_menu.selected_index();
+            if let Some(command) = self.pause_menu.process_action(action) {
+                self.pending_menu_command = Some(command);
+            }
+            self.menu_selection_changed |= self.pause_menu.selected_index() != selected_index;
+            true
+        } else if self.continue_countdown.is_some() {
+            let selected_index = self.continue_menu.selected_index();
+            if let Some(choice) = self.continue_menu.process_action(action) {
+                self.pending_continue = Some(choice);
+            }
+            self.menu_selection_changed |= self.continue_menu.selected_index() != selected_index;
+            true
+        } else if action == Action::Pause && !self.game_over_message_visible {
+            self.toggle_drawer_needed = true;
+            true
+        } else {
+            false
         }
     }
 
     pub fn update(&mut self, ctx: &mut AppContext, game: &game_state::GameState) {
+        let gpu = ctx
+            .gpu
+            .as_deref_mut()
+            .expect("Expect GameUi to be updated with a GpuState");
         let pixels_per_unit = self.pixels_per_unit;
         let palette_shift = self.palette_shift();
         self.drawer_collision_space.update();
 
         self.time += ctx.game_delta_time.as_secs_f32();
+        self.title_time += ctx.real_delta_time.as_secs_f32();
+
+        if let Some(command) = self.pending_menu_command.take() {
+            self.perform_menu_command(command, ctx.audio);
+        }
+        if self.menu_selection_changed {
+            self.menu_selection_changed = false;
+            ctx.audio.play_sound(audio::Sounds::Bump);
+        }
 
         if self.toggle_drawer_needed {
             self.drawer_open = !self.drawer_open;
             self.toggle_drawer_needed = false;
             if self.drawer_open {
+                self.pause_menu = PauseMenu::default();
                 ctx.audio.play_sound(audio::Sounds::DrawerOpen);
                 ctx.audio.pause_current_track();
             } else {
@@ -282,6 +419,50 @@ impl GameUi {
             }
         }
 
+        if self.start_game_needed {
+            self.start_game_needed = false;
+            self.title_visible = false;
+            if self.new_game_needed {
+                self.new_game_needed = false;
+                self.commands.push(UiCommand::NewGame);
+            }
+        }
+
+        if let Some(game_over_countdown) = self.game_over_countdown {
+            let game_over_countdown = game_over_countdown - ctx.real_delta_time.as_secs_f32();
+            if game_over_countdown < 0.0 {
+                self.game_over_countdown = None;
+                self.game_over_message_visible = false;
+                if self.continues_remaining > 0 {
+                    self.continue_menu = ContinueMenu::default();
+                    self.continue_countdown = Some(CONTINUE_DURATION);
+                } else {
+                    self.new_game_needed = true;
+                    self.show_title();
+                }
+            } else {
+                self.game_over_countdown = Some(game_over_countdown);
+            }
+        }
+
+        if let Some(choice) = self.pending_continue.take() {
+            self.continue_countdown = None;
+            self.commands.push(UiCommand::ContinueGame {
+                from_last_checkpoint: choice == ContinueChoice::LastCheckpoint,
+            });
+        }
+
+        if let Some(continue_countdown) = self.continue_countdown {
+            let continue_countdown = continue_countdown - ctx.real_delta_time.as_secs_f32();
+            if continue_countdown < 0.0 {
+                self.continue_countdown = None;
+                self.new_game_needed = true;
+                self.show_title();
+            } else {
+                self.continue_countdown = Some(continue_countdown);
+            }
+        }
+
         // Canter camera on window, and set projection scale
         self.camera_view.set_position(point3(0.0, 0.0, 0.0));
         self.camera_projection
@@ -290,7 +471,7 @@ impl GameUi {
         self.camera_uniforms
             .data
             .update_view_proj(&self.camera_view, &self.camera_projection);
-        self.camera_uniforms.write(&mut ctx.gpu.queue);
+        self.camera_uniforms.write(&mut gpu.queue);
 
         // update drawer uniforms
         let bounds = self.game_ui_map.bounds();
@@ -306,7 +487,7 @@ impl GameUi {
             .set_color(vec4(1.0, 1.0, 1.0, 1.0))
             .set_palette_shift(palette_shift)
             .set_model_position(point3(drawer_offset.x, drawer_offset.y, drawer_offset.z));
-        self.drawer_uniforms.write(&mut ctx.gpu.queue);
+        self.drawer_uniforms.write(&mut gpu.queue);
 
         // update entity uniforms - note we have to apply drawer position offset
         let game_state_peek = game.game_state_peek();
@@ -318,6 +499,7 @@ impl GameUi {
                 ctx.audio,
                 ctx.message_dispatcher,
                 &game_state_peek,
+                ctx.rng,
             );
             if let Some(ref mut uniforms) = e.uniforms {
                 e.entity.update_uniforms(uniforms);
@@ -326,34 +508,113 @@ impl GameUi {
                     .set_pixels_per_unit(pixels_per_unit)
                     .set_palette_shift(palette_shift)
                     .offset_model_position(drawer_offset);
-                uniforms.write(&mut ctx.gpu.queue);
+                uniforms.write(&mut gpu.queue);
             }
         }
 
         // update game over and game start uniforms to center their test strings.
         // Note: We don't apply palette shift to text drawables
-        let mut center_text_drawable =
-            |drawable: &rendering::Drawable, uniforms: &mut rendering::Uniforms| {
-                let bounds = drawable
-                    .meshes
-                    .first()
-                    .expect("Expect drawable to have mesh at index 0")
-                    .bounds;
+        let mut position_text_drawable =
+            |uniforms: &mut rendering::Uniforms, position: Vector3<f32>| {
                 uniforms
                     .data
                     .set_pixels_per_unit(pixels_per_unit)
                     .set_color(vec4(1.0, 1.0, 1.0, 1.0))
-                    .set_model_position(point3(-bounds.width() / 2.0, -bounds.height() / 2.0, 0.0));
-                uniforms.write(&mut ctx.gpu.queue);
+                    .set_model_position(point3(position.x, position.y, position.z));
+                uniforms.write(&mut gpu.queue);
             };
 
-        center_text_drawable(&self.game_over_drawable, &mut self.game_over_uniforms);
-        center_text_drawable(&self.game_start_drawable, &mut self.game_start_uniforms);
-        center_text_drawable(
-            &self.level_complete_drawable,
+        position_text_drawable(
+            &mut self.game_over_uniforms,
+            Self::centering_offset(&self.game_over_drawable),
+        );
+        position_text_drawable(
+            &mut self.game_start_uniforms,
+            Self::centering_offset(&self.game_start_drawable),
+        );
+        position_text_drawable(
             &mut self.level_complete_uniforms,
+            Self::centering_offset(&self.level_complete_drawable),
+        );
+        position_text_drawable(
+            &mut self.title_uniforms,
+            Self::centering_offset(&self.title_drawable),
+        );
+
+        // the cursor moves down a row per menu item
+        let pause_menu_offset = Self::centering_offset(&self.pause_menu_drawable);
+        position_text_drawable(&mut self.pause_menu_uniforms, pause_menu_offset);
+        position_text_drawable(
+            &mut self.pause_menu_cursor_uniforms,
+            pause_menu_offset - vec3(0.0, self.pause_menu.selected_index() as f32, 0.0),
+        );
+
+        let continue_offset = Self::centering_offset(&self.continue_drawable);
+        position_text_drawable(&mut self.continue_uniforms, continue_offset);
+        position_text_drawable(
+            &mut self.continue_cursor_uniforms,
+            continue_offset - vec3(0.0, self.continue_menu.selected_index() as f32, 0.0),
         );
 
+        // pause menu entities are drawn in front of the pause menu, rather than the drawer
+        let pause_menu_entity_offset = pause_menu_offset
+            + vec3(
+                0.0,
+                0.0,
+                layers::ui::MENU_FOREGROUND - layers::ui::FOREGROUND,
+            );
+        for e in self.pause_menu_entities.values_mut() {
+            e.entity.update(
+                ctx.real_delta_time,
+                &self.game_ui_map,
+                &mut self.drawer_collision_space,
+                ctx.audio,
+                ctx.message_dispatcher,
+                &game_state_peek,
+                ctx.rng,
+            );
+            if let Some(ref mut uniforms) = e.uniforms {
+                e.entity.update_uniforms(uniforms);
+                uniforms
+                    .data
+                    .set_pixels_per_unit(pixels_per_unit)
+                    .offset_model_position(pause_menu_entity_offset);
+                uniforms.write(&mut gpu.queue);
+            }
+        }
+
+        // the continue prompt's entities show its countdown and the continues remaining
+        let continue_state_peek = entity::GameStatePeek {
+            continues_remaining: self.continues_remaining.min(9),
+            continue_countdown: (self.continue_countdown.unwrap_or(0.0) as u32).min(9),
+            ..game_state_peek
+        };
+        let continue_entity_offset = continue_offset
+            + vec3(
+                0.0,
+                0.0,
+                layers::ui::MENU_FOREGROUND - layers::ui::FOREGROUND,
+            );
+        for e in self.continue_entities.values_mut() {
+            e.entity.update(
+                ctx.real_delta_time,
+                &self.game_ui_map,
+                &mut self.drawer_collision_space,
+                ctx.audio,
+                ctx.message_dispatcher,
+                &continue_state_peek,
+                ctx.rng,
+            );
+            if let Some(ref mut uniforms) = e.uniforms {
+                e.entity.update_uniforms(uniforms);
+                uniforms
+                    .data
+                    .set_pixels_per_unit(pixels_per_unit)
+                    .offset_model_position(continue_entity_offset);
+                uniforms.write(&mut gpu.queue);
+            }
+        }
+
         // update countdowns
         self.start_message_blink_countdown =
             (self.start_message_blink_countdown - ctx.real_delta_time.as_secs_f32()).max(0.0);
@@ -400,23 +661,57 @@ impl GameUi {
             &self.drawer_uniforms,
         );
 
-        for e in self.entities.values() {
-            if e.entity.is_alive() && e.entity.should_draw() {
-                if let Some(ref drawable) = e.entity_drawable {
-                    if let Some(ref uniforms) = e.uniforms {
-                        drawable.draw(
-                            &mut render_pass,
-                            &self.camera_uniforms,
-                            uniforms,
-                            e.entity.sprite_cycle(),
-                        );
-                    }
-                }
-                if let Some(ref drawable) = e.sprite_drawable {
-                    if let Some(ref uniforms) = e.uniforms {
-                        drawable.draw(&mut render_pass, &self.camera_uniforms, uniforms);
-                    }
-                }
+        Self::draw_entities(&mut render_pass, &self.camera_uniforms, &self.entities);
+
+        if self.drawer_open {
+            self.pause_menu_drawable.draw(
+                &mut render_pass,
+                &self.camera_uniforms,
+                &self.pause_menu_uniforms,
+            );
+            self.pause_menu_cursor_drawable.draw(
+                &mut render_pass,
+                &self.camera_uniforms,
+                &self.pause_menu_cursor_uniforms,
+            );
+            Self::draw_entities(
+                &mut render_pass,
+                &self.camera_uniforms,
+                &self.pause_menu_entities,
+            );
+        }
+
+        if self.continue_countdown.is_some() {
+            self.continue_drawable.draw(
+                &mut render_pass,
+                &self.camera_uniforms,
+                &self.continue_uniforms,
+            );
+            self.continue_cursor_drawable.draw(
+                &mut render_pass,
+                &self.camera_uniforms,
+                &self.continue_cursor_uniforms,
+            );
+            Self::draw_entities(
+                &mut render_pass,
+                &self.camera_uniforms,
+                &self.continue_entities,
+            );
+        }
+
+        if self.title_visible {
+            self.title_drawable.draw(
+                &mut render_pass,
+                &self.camera_uniforms,
+                &self.title_uniforms,
+            );
+            let cycle = (self.title_time / TITLE_PROMPT_BLINK_PERIOD) as i32;
+            if cycle % 2 == 0 {
+                self.title_prompt_drawable.draw(
+                    &mut render_pass,
+                    &self.camera_uniforms,
+                    &self.title_uniforms,
+                );
             }
         }
 
@@ -462,8 +757,16 @@ impl GameUi {
                 }
             }
 
-            if matches!(message.event, Event::GameOver) {
-                self.show_game_over_message();
+            match message.event {
+                Event::GameOver {
+                    continues_remaining,
+                } => {
+                    self.continues_remaining = continues_remaining;
+                    self.show_game_over_message();
+                    self.game_over_countdown = Some(GAME_OVER_MESSAGE_DURATION);
+                }
+                Event::LoadLevel { .. } => self.level_complete_message_visible = false,
+                _ => {}
             }
         }
     }
@@ -477,14 +780,34 @@ impl GameUi {
         (self.palette_shift * 4.0).round() / 4.0
     }
 
+    /// True while the pause menu or the title screen is showing
     pub fn is_paused(&self) -> bool {
-        self.drawer_open
+        self.drawer_open || self.title_visible
+    }
+
+    /// Shows the title screen, pausing the game until the player starts.
+    pub fn show_title(&mut self) {
+        self.title_visible = true;
+        self.title_time = 0.0;
+        // the drawer closes behind the title screen
+        self.toggle_drawer_needed = self.drawer_open;
+    }
+
+    /// Pauses the game, if not already paused, e.g., when the player's gamepad disconnects.
+    pub fn pause(&mut self) {
+        self.toggle_drawer_needed =
+            !self.drawer_open && !self.title_visible && self.continue_countdown.is_none();
     }
 
     pub fn show_start_message(&mut self) {
         self.start_message_blink_countdown = START_MESSAGE_DURATION;
     }
 
+    /// Takes the gameplay commands the player requested since the previous call
+    pub fn take_commands(&mut self) -> Vec<UiCommand> {
+        std::mem::take(&mut self.commands)
+    }
+
     pub fn show_game_over_message(&mut self) {
         self.game_over_message_visible = true;
     }
@@ -495,6 +818,73 @@ impl GameUi {
 
     // MARK: Private
 
+    fn perform_menu_command(&mut self, command: MenuCommand, audio: &mut audio::Audio) {
+        match command {
+            MenuCommand::Resume => self.toggle_drawer_needed = self.drawer_open,
+            MenuCommand::Restart => {
+                self.toggle_drawer_needed = self.drawer_open;
+                self.commands.push(UiCommand::RestartFromCheckpoint);
+            }
+            MenuCommand::CyclePalette { forward } => {
+                self.commands.push(UiCommand::CyclePalette { forward });
+            }
+            MenuCommand::AdjustMusicVolume { steps } => {
+                audio.set_music_volume(Self::step_volume(audio.music_volume(), steps));
+            }
+            MenuCommand::AdjustSfxVolume { steps } => {
+                audio.set_sfx_volume(Self::step_volume(audio.sfx_volume(), steps));
+                // a sample of the new volume
+                audio.play_sound(audio::Sounds::Bump);
+            }
+            MenuCommand::Quit => self.commands.push(UiCommand::QuitGame),
+        }
+    }
+
+    /// Steps `volume` by `steps` VOLUME_STEPs, snapping to the nearest step
+    fn step_volume(volume: f32, steps: i32) -> f32 {
+        ((volume / VOLUME_STEP).round() + steps as f32) * VOLUME_STEP
+    }
+
+    fn draw_entities<'a>(
+        render_pass: &mut wgpu::RenderPass<'a>,
+        camera_uniforms: &'a camera::Uniforms,
+        entities: &'a HashMap<u32, EntityComponents>,
+    ) {
+        for e in entities.values() {
+            if e.entity.is_alive() && e.entity.should_draw() {
+                if let Some(ref drawable) = e.entity_drawable {
+                    if let Some(ref uniforms) = e.uniforms {
+                        drawable.draw(
+                            render_pass,
+                            camera_uniforms,
+                            uniforms,
+                            e.entity.sprite_cycle(),
+                        );
+                    }
+                }
+                if let Some(ref drawable) = e.sprite_drawable {
+                    if let Some(ref uniforms) = e.uniforms {
+                        drawable.draw(render_pass, camera_uniforms, uniforms);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Returns the model position offset which centers `drawable` in the ui camera's view
+    fn centering_offset(drawable: &rendering::Drawable) -> Vector3<f32> {
+        let bounds = drawable
+            .meshes
+            .first()
+            .expect("Expect drawable to have mesh at index 0")
+            .bounds;
+        vec3(
+            -bounds.left() - bounds.width() / 2.0,
+            -bounds.bottom() - bounds.height() / 2.0,
+            0.0,
+        )
+    }
+
     fn update_drawer_position(&mut self, dt: Duration) -> f32 {
         let bounds = self.game_ui_map.bounds();
         let vp_units_high = self.camera_projection.viewport_size().y;
@@ -0,0 +1,527 @@
+use std::time::{Duration, Instant};
+
+use anyhow::*;
+use cgmath::*;
+use winit::{event::ElementState, keyboard::KeyCode};
+
+use crate::{
+    audio::Audio,
+    campaign::Campaign,
+    entities::firebrand::FirebrandInput,
+    entity, event_dispatch,
+    input_map::{self, Binding, Bindings, InputMap},
+    tileset, Options,
+};
+
+use super::{
+    app_state::AppContext,
+    constants::{ORIGINAL_WINDOW_HEIGHT, ORIGINAL_WINDOW_WIDTH},
+    events::Event,
+    game_controller::GameController,
+    game_state::GameState,
+};
+
+// --------------------------------------------------------------------------------------------------------------------
+
+/// HeadlessState runs the game simulation - map, collision space, entities and message dispatch -
+/// without a window, gpu or audio device, advancing at the fixed `Options::simulation_timestep`.
+/// It mirrors AppState's update/dispatch loop, minus GameUi, LcdFilter and rendering.
+pub struct HeadlessState {
+    options: Options,
+    campaign: Campaign,
+    viewport_size: Vector2<u32>,
+    audio: Audio,
+    game_controller: GameController,
+    game_state: GameState,
+    input_map: InputMap,
+
+    entity_id_vendor: entity::IdVendor,
+    tilesets: tileset::TileSetCache,
+    message_dispatcher: event_dispatch::Dispatcher,
+    rng: entity::GameRng,
+
+    timestep: Duration,
+    start_time: Instant,
+    frame_index: u32,
+}
+
+impl HeadlessState {
+    pub fn new(options: Options) -> Result<Self> {
+        let mut entity_id_vendor = entity::IdVendor::default();
+        let mut tilesets = tileset::TileSetCache::default();
+        let campaign = Campaign::load(&options.campaign)?;
+        let level = campaign.level(options.level)?;
+        let input_map = InputMap::new(Bindings::load(
+            options
+                .input_bindings
+                .as_deref()
+                .unwrap_or(std::path::Path::new(input_map::DEFAULT_BINDINGS_FILE)),
+        )?);
+        let game_controller = GameController::new(
+            options.lives_remaining.unwrap_or(options.lives),
+            options.vials,
+            options.checkpoint.unwrap_or(0_u32),
+            options.level,
+            campaign.num_levels(),
+        )
+        .with_continues(
+            options.continues_remaining.unwrap_or(options.continues),
+            options.lives,
+        );
+
+        // Size the camera viewport as AppState's window would be; this matters since entity
+        // spawning is driven by viewport visibility.
+        let viewport_size = if options.gameboy {
+            vec2(ORIGINAL_WINDOW_WIDTH * 4, ORIGINAL_WINDOW_HEIGHT * 4)
+        } else {
+            vec2(ORIGINAL_WINDOW_WIDTH * 8, ORIGINAL_WINDOW_HEIGHT * 6)
+        };
+
+        let viewport_size = viewport_size.cast().unwrap();
+        let game_state = GameState::new_headless(
+            viewport_size,
+            &options,
+            &mut entity_id_vendor,
+            &mut tilesets,
+            level,
+            game_controller.current_checkpoint(),
+            game_controller.lives_remaining(),
+            game_controller.vials(),
+        )?;
+
+        // Headless runs are for reproducible tests, so default to a fixed seed
+        let rng = entity::GameRng::new(options.seed.unwrap_or_default());
+        let timestep = options.simulation_timestep();
+
+        Ok(Self {
+            audio: Audio::silent(),
+            game_controller,
+            game_state,
+            input_map,
+            options,
+            campaign,
+            viewport_size,
+            entity_id_vendor,
+            tilesets,
+            message_dispatcher: event_dispatch::Dispatcher::default(),
+            rng,
+            timestep,
+            start_time: Instant::now(),
+            frame_index: 0,
+        })
+    }
+
+    pub fn options(&self) -> &Options {
+        &self.options
+    }
+
+    pub fn game_state(&self) -> &GameState {
+        &self.game_state
+    }
+
+    pub fn game_controller(&self) -> &GameController {
+        &self.game_controller
+    }
+
+    pub fn frame_index(&self) -> u32 {
+        self.frame_index
+    }
+
+    /// Elapsed simulation time
+    pub fn time(&self) -> Duration {
+        self.timestep * self.frame_index
+    }
+
+    /// Forwards a key event to the game's entities, as if it came from the keyboard. Keys bound
+    /// to actions by the input bindings are forwarded as those actions.
+    pub fn process_keyboard(&mut self, key_code: KeyCode, state: ElementState) -> bool {
+        if self.input_map.bindings().is_bound(Binding::Key(key_code)) {
+            let mut consumed = false;
+            for (action, state) in self.input_map.process_keyboard(key_code, state) {
+                consumed |= self.game_state.process_action(action, state, false);
+            }
+            consumed
+        } else {
+            self.game_state.process_keyboard(key_code, state, false)
+        }
+    }
+
+    /// Replaces the input firebrand will act on during the next step, e.g., for replay playback.
+    pub fn override_firebrand_input(&mut self, input: FirebrandInput) {
+        self.game_state.override_firebrand_input(input);
+    }
+
+    /// Advances the simulation by a single fixed timestep.
+    pub fn step(&mut self) {
+        let time = self.start_time + self.time();
+        {
+            let mut ctx = AppContext {
+                gpu: None,
+                audio: &mut self.audio,
+                message_dispatcher: &mut self.message_dispatcher,
+                entity_id_vendor: &mut self.entity_id_vendor,
+                rng: &mut self.rng,
+                frame_idx: self.frame_index,
+                time,
+                game_delta_time: self.timestep,
+                real_delta_time: self.timestep,
+            };
+
+            self.game_state.update(&mut ctx);
+            self.game_controller
+                .update(&mut ctx, &mut self.game_state, None);
+        }
+
+        event_dispatch::Dispatcher::dispatch(&self.message_dispatcher.drain(), self);
+        self.frame_index = self.frame_index.wrapping_add(1);
+    }
+
+    /// Replaces the current GameState with one playing the campaign's level at `level_index`,
+    /// as AppState does when GameController requests the next level.
+    fn load_level(&mut self, level_index: u32) {
+        let level = match self.campaign.level(level_index) {
+            Result::Ok(level) => level,
+            Err(e) => {
+                log::error!("Unable to load level: {:?}", e);
+                return;
+            }
+        };
+
+        self.game_state = match GameState::new_headless(
+            self.viewport_size,
+            &self.options,
+            &mut self.entity_id_vendor,
+            &mut self.tilesets,
+            level,
+            self.game_controller.current_checkpoint(),
+            self.game_controller.lives_remaining(),
+            self.game_controller.vials(),
+        ) {
+            Result::Ok(game_state) => game_state,
+            Err(e) => {
+                log::error!("Unable to load level: {:?}", e);
+                return;
+            }
+        };
+    }
+
+    /// Rebuilds the current level's stage from its map and tilesets as they are on disk, keeping
+    /// firebrand where he stands, as AppState does when --hot-reload is set.
+    pub fn reload_level(&mut self) -> Result<()> {
+        let level = self.campaign.level(self.game_controller.current_level())?;
+        self.tilesets = tileset::TileSetCache::default();
+        let reloaded = GameState::new_headless(
+            self.viewport_size,
+            &self.options,
+            &mut self.entity_id_vendor,
+            &mut self.tilesets,
+            level,
+            self.game_controller.current_checkpoint(),
+            self.game_controller.lives_remaining(),
+            self.game_controller.vials(),
+        )?;
+        self.game_state.reload_stage(
+            reloaded,
+            self.game_controller.current_checkpoint(),
+            &mut self.message_dispatcher,
+        );
+        Ok(())
+    }
+
+    /// Steps the simulation until `predicate` returns true, or `timeout` of simulation time elapses.
+    /// Returns true iff the predicate was satisfied.
+    pub fn step_until<F>(&mut self, timeout: Duration, mut predicate: F) -> bool
+    where
+        F: FnMut(&HeadlessState) -> bool,
+    {
+        let end_time = self.time() + timeout;
+        while self.time() < end_time {
+            self.step();
+            if predicate(self) {
+                return true;
+            }
+        }
+        false
+    }
+}
+
+impl event_dispatch::MessageHandler for HeadlessState {
+    fn handle_message(&mut self, message: &event_dispatch::Message) {
+        match message.event {
+            Event::ResetState => self.rng.reset(),
+            Event::LoadLevel { level } => self.load_level(level),
+            _ => {}
+        }
+
+        self.game_controller.handle_message(
+            message,
+            &mut self.message_dispatcher,
+            &mut self.entity_id_vendor,
+            &mut self.audio,
+            &mut self.game_state,
+        );
+        self.game_state.handle_message(
+            message,
+            &mut self.message_dispatcher,
+            &mut self.entity_id_vendor,
+            &mut self.audio,
+        );
+    }
+}
+
+// --------------------------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod headless_tests {
+    use super::*;
+    use structopt::StructOpt;
+
+    fn options(args: &[&str]) -> Options {
+        Options::from_iter(std::iter::once("platformer").chain(args.iter().copied()))
+    }
+
+    #[test]
+    fn firebrand_is_created_at_start_checkpoint() {
+        let mut state = HeadlessState::new(options(&["--checkpoint", "1"])).unwrap();
+        state.step();
+
+        let firebrand = state
+            .game_state()
+            .try_get_firebrand()
+            .expect("Expect firebrand to be created on first step");
+        let checkpoint_position = state.game_state().ordered_checkpoints()[1]
+            .entity
+            .position();
+
+        assert_eq!(firebrand.entity.position().x, checkpoint_position.x);
+    }
+
+    #[test]
+    fn firebrand_walks_from_last_checkpoint_to_boss_arena() {
+        let mut state = HeadlessState::new(options(&["--checkpoint", "99"])).unwrap();
+        state.step();
+        let start_x = state.game_state().game_state_peek().player_position.x;
+
+        state.process_keyboard(KeyCode::KeyD, ElementState::Pressed);
+        let entered = state.step_until(Duration::from_secs(30), |state| {
+            state.game_state().boss_arena_entered()
+        });
+
+        assert!(entered, "Expect firebrand to reach the boss arena");
+        assert!(state.game_state().game_state_peek().player_position.x > start_x);
+    }
+
+    #[test]
+    fn reloading_level_keeps_firebrand_position() {
+        let mut state = HeadlessState::new(options(&["--checkpoint", "1"])).unwrap();
+        state.process_keyboard(KeyCode::KeyD, ElementState::Pressed);
+        for _ in 0..30 {
+            state.step();
+        }
+        state.process_keyboard(KeyCode::KeyD, ElementState::Released);
+        let firebrand_id = state.game_state().get_firebrand().id();
+        let position = state.game_state().get_firebrand().entity.position();
+
+        state.reload_level().unwrap();
+        state.step();
+
+        let firebrand = state.game_state().get_firebrand();
+        assert_ne!(firebrand.id(), firebrand_id);
+        assert_eq!(firebrand.entity.position().xy(), position.xy());
+        assert_eq!(state.game_controller().current_checkpoint(), 1);
+    }
+
+    #[test]
+    fn replayed_input_reproduces_session() {
+        let args = ["--checkpoint", "2"];
+        let mut recorded = HeadlessState::new(options(&args)).unwrap();
+        let mut inputs = vec![];
+
+        let script = [
+            (0, KeyCode::KeyD, ElementState::Pressed),
+            (30, KeyCode::KeyW, ElementState::Pressed),
+            (45, KeyCode::KeyW, ElementState::Released),
+            (60, KeyCode::Space, ElementState::Pressed),
+            (61, KeyCode::Space, ElementState::Released),
+            (90, KeyCode::KeyD, ElementState::Released),
+            (90, KeyCode::KeyA, ElementState::Pressed),
+        ];
+        for frame in 0..120 {
+            for (_, key, state) in script.iter().filter(|(f, _, _)| *f == frame) {
+                recorded.process_keyboard(*key, *state);
+            }
+            recorded.step();
+            inputs.push(recorded.game_state().firebrand_input());
+        }
+
+        let mut replayed = HeadlessState::new(options(&args)).unwrap();
+        for input in inputs {
+            if let Some(input) = input {
+                replayed.override_firebrand_input(input);
+            }
+            replayed.step();
+        }
+
+        assert_eq!(
+            recorded.game_state().game_state_peek().player_position,
+            replayed.game_state().game_state_peek().player_position
+        );
+    }
+
+    #[test]
+    fn runs_with_the_same_seed_are_reproducible() {
+        let run = || {
+            let mut state =
+                HeadlessState::new(options(&["--checkpoint", "2", "--seed", "7"])).unwrap();
+            state.process_keyboard(KeyCode::KeyD, ElementState::Pressed);
+            for frame in 0..600 {
+                if frame % 40 == 0 {
+                    state.process_keyboard(KeyCode::Space, ElementState::Pressed);
+                } else if frame % 40 == 1 {
+                    state.process_keyboard(KeyCode::Space, ElementState::Released);
+                }
+                state.step();
+            }
+            state.game_state().entity_positions()
+        };
+
+        let first = run();
+        assert!(first.len() > 1);
+        assert_eq!(first, run());
+    }
+
+    #[test]
+    fn missing_map_is_reported_rather_than_panicking() {
+        let manifest = std::env::temp_dir().join(format!(
+            "platformer-test-missing-map-{}.manifest",
+            std::process::id()
+        ));
+        std::fs::write(
+            &manifest,
+            "level Missing\nmap missing.tmx\nentity_tileset entities.tsx\n",
+        )
+        .unwrap();
+
+        let manifest_arg = manifest.to_string_lossy().to_string();
+        let result = HeadlessState::new(options(&["--campaign", &manifest_arg]));
+        std::fs::remove_file(&manifest).unwrap();
+
+        let error = format!("{:#}", result.err().expect("Expect missing map to fail"));
+        assert!(error.contains("missing.tmx"), "{}", error);
+    }
+
+    #[test]
+    fn completing_level_loads_next_level_with_lives_and_vials() {
+        // a two level campaign, playing level_1 twice
+        let res_dir = std::env::current_dir().unwrap().join("res");
+        let level = format!(
+            "map {}\nentity_tileset {}\n",
+            res_dir.join("level_1.tmx").display(),
+            res_dir.join("entities.tsx").display()
+        );
+        let manifest = std::env::temp_dir().join(format!(
+            "platformer-test-campaign-{}.manifest",
+            std::process::id()
+        ));
+        std::fs::write(&manifest, format!("level One\n{level}level Two\n{level}")).unwrap();
+
+        let manifest_arg = manifest.to_string_lossy().to_string();
+        let mut state = HeadlessState::new(options(&[
+            "--campaign",
+            &manifest_arg,
+            "--checkpoint",
+            "1",
+            "--lives",
+            "5",
+        ]))
+        .unwrap();
+        std::fs::remove_file(&manifest).unwrap();
+
+        state.step();
+        let firebrand_id = state.game_state().get_firebrand().id();
+        state.message_dispatcher.global_to_entity(
+            firebrand_id,
+            Event::FirebrandContactedPowerUp {
+                powerup_type: crate::entities::power_up::Type::Vial,
+            },
+        );
+        state.step();
+        state
+            .message_dispatcher
+            .broadcast(Event::FirebrandPassedThroughExitDoor);
+
+        let loaded = state.step_until(Duration::from_secs(10), |state| {
+            state.game_controller().current_level() == 1
+        });
+        assert!(loaded, "Expect next level to load after level completion");
+
+        state.step_until(Duration::from_secs(1), |state| {
+            state.game_state().try_get_firebrand().is_some()
+        });
+        let peek = state.game_state().game_state_peek();
+        assert_eq!(state.game_controller().current_checkpoint(), 0);
+        assert_eq!(peek.player_lives, 5);
+        assert_eq!(peek.player_vials, 1);
+    }
+
+    #[test]
+    fn continue_after_game_over_refills_lives() {
+        let mut state = HeadlessState::new(options(&[
+            "--checkpoint",
+            "2",
+            "--lives",
+            "1",
+            "--continues",
+            "1",
+        ]))
+        .unwrap();
+        state.step();
+
+        // lose the one life, then die again for a game over
+        let firebrand_id = state.game_state().get_firebrand().id();
+        state.message_dispatcher.broadcast(Event::FirebrandDied);
+        let restarted = state.step_until(Duration::from_secs(5), |state| {
+            state
+                .game_state()
+                .try_get_firebrand()
+                .is_some_and(|firebrand| firebrand.id() != firebrand_id)
+        });
+        assert!(restarted, "Expect firebrand to restart after losing a life");
+        assert_eq!(state.game_controller().lives_remaining(), 0);
+        state.message_dispatcher.broadcast(Event::FirebrandDied);
+
+        let game_over = state.step_until(Duration::from_secs(5), |state| {
+            state.game_controller().is_game_over()
+        });
+        assert!(game_over, "Expect game over after dying with no lives");
+
+        let firebrand_id = state.game_state().get_firebrand().id();
+        state.message_dispatcher.broadcast(Event::ContinueGame {
+            from_last_checkpoint: false,
+        });
+        state.step();
+        assert!(!state.game_controller().is_game_over());
+        assert_eq!(state.game_controller().continues_remaining(), 0);
+        assert_eq!(state.game_controller().current_checkpoint(), 0);
+
+        // the level restarts once faded out
+        let restarted = state.step_until(Duration::from_secs(5), |state| {
+            state
+                .game_state()
+                .try_get_firebrand()
+                .is_some_and(|firebrand| firebrand.id() != firebrand_id)
+        });
+        assert!(
+            restarted,
+            "Expect firebrand to be recreated after continuing"
+        );
+        let mut level_start = HeadlessState::new(options(&[])).unwrap();
+        level_start.step();
+        assert_eq!(
+            state.game_state().get_firebrand().entity.position(),
+            level_start.game_state().get_firebrand().entity.position()
+        );
+        assert_eq!(state.game_controller().lives_remaining(), 1);
+        assert_eq!(state.game_state().game_state_peek().player_lives, 1);
+    }
+}
@@ -1,7 +1,10 @@
+use anyhow::{Context, Result};
 use cgmath::*;
 use winit::window::Window;
 
 use crate::{
+    palette::Palette,
+    settings::LcdSettings,
     texture::{self, Texture},
     Options,
 };
@@ -179,6 +182,7 @@ pub struct LcdFilter {
     column_avg_color_attachment: Texture,
 
     uniforms: LcdUniforms,
+    palette: Palette,
     tonemap: Texture,
     noise: Texture,
 
@@ -190,7 +194,12 @@ impl LcdFilter {
     /// Default time it takes for an LCD pixel to change state
     pub const DEFAULT_HYSTERESIS: std::time::Duration = std::time::Duration::from_millis(65);
 
-    pub fn new(gpu: &mut gpu_state::GpuState, options: &Options, tonemap: Texture) -> Self {
+    pub fn new(
+        gpu: &mut gpu_state::GpuState,
+        options: &Options,
+        palette: &Palette,
+    ) -> Result<Self> {
+        let tonemap = Self::create_tonemap(gpu, palette)?;
         let uniforms = LcdUniforms::new(&gpu.device);
         let column_avg_color_attachment = texture::Texture::create_color_texture(
             &gpu.device,
@@ -202,7 +211,7 @@ impl LcdFilter {
 
         let noise_texture = "res/white_noise.png";
         let noise = texture::Texture::load(&gpu.device, &gpu.queue, noise_texture)
-            .unwrap_or_else(|_| panic!("Expected to load noise texture {}", noise_texture));
+            .with_context(|| format!("Unable to load noise texture {}", noise_texture))?;
 
         let display_pass = Self::create_display_pass(
             gpu,
@@ -217,7 +226,7 @@ impl LcdFilter {
 
         let lcd_hysteresis = (!options.no_sludgy).then_some(Self::DEFAULT_HYSTERESIS);
 
-        Self {
+        Ok(Self {
             display_pass_pipeline: display_pass.0,
             display_pass_textures_bind_group_layout: display_pass.1,
             display_pass_textures_bind_group: display_pass.2,
@@ -228,11 +237,30 @@ impl LcdFilter {
             column_avg_color_attachment,
 
             uniforms,
+            palette: palette.clone(),
             tonemap,
             noise,
             lcd_hysteresis,
             frames_available_for_hysteresis: 0,
-        }
+        })
+    }
+
+    /// Replaces the tonemap with one made from `palette`, taking effect from the next frame.
+    pub fn set_palette(&mut self, gpu: &gpu_state::GpuState, palette: &Palette) -> Result<()> {
+        self.tonemap = Self::create_tonemap(gpu, palette)?;
+        self.palette = palette.clone();
+        self.display_pass_textures_bind_group = Self::create_display_pass_textures_bind_group(
+            gpu,
+            &self.display_pass_textures_bind_group_layout,
+            &self.tonemap.view,
+            &self.noise.view,
+            &self.column_avg_color_attachment.view,
+        );
+        Ok(())
+    }
+
+    pub fn palette(&self) -> &Palette {
+        &self.palette
     }
 
     pub fn set_lcd_hysteresis(&mut self, hysteresis: Option<std::time::Duration>) {
@@ -243,6 +271,30 @@ impl LcdFilter {
         self.lcd_hysteresis
     }
 
+    /// Applies the tunables of `settings` which are set; sludge is applied separately, via
+    /// set_lcd_hysteresis, since --no-sludgy overrides it.
+    pub fn apply_settings(&mut self, settings: &LcdSettings) {
+        let data = &mut self.uniforms.data;
+        if let Some(alpha) = settings.lcd_shadow_alpha {
+            data.set_lcd_shadow_effect_alpha(alpha);
+        }
+        if let Some(alpha) = settings.frame_shadow_alpha {
+            data.set_frame_shadow_effect_alpha(alpha);
+        }
+        if let Some(power) = settings.column_bleed_power {
+            data.set_lcd_column_bleed_effect_power(power);
+        }
+        if let Some(alpha) = settings.column_bleed_alpha {
+            data.set_lcd_column_bleed_effect_alpha(alpha);
+        }
+        if let Some(noisiness) = settings.noisiness {
+            data.set_lcd_noisiness(noisiness);
+        }
+        if let Some(clarity) = settings.clarity {
+            data.set_lcd_clarity(clarity);
+        }
+    }
+
     pub fn resize(
         &mut self,
         _window: &Window,
@@ -280,12 +332,16 @@ impl LcdFilter {
     }
 
     pub fn update(&mut self, ctx: &mut AppContext, game: &game_state::GameState) {
+        let gpu = ctx
+            .gpu
+            .as_deref_mut()
+            .expect("Expect LcdFilter to be updated with a GpuState");
         // Determine an appropriate alpha for pixel effects - as window gets
         // smaller the effect needs to fade out, since it looks busy on small windows.
         // NOTE: min_high_freq and max_high_freq were determined via experimentation
         let pixel_effect_alpha = 0.25 * {
             let frequency = (game.camera_controller.projection.scale() * game.pixels_per_unit.x)
-                / ctx.gpu.config.width as f32;
+                / gpu.config.width as f32;
 
             let min_high_freq = 0.2;
             let max_high_freq = 0.5;
@@ -298,7 +354,7 @@ impl LcdFilter {
 
         // pixel effect hardness should go up as the LCD pixel size goes above 3 or so display pixels
         let pixel_effect_hardness = 1.0 * {
-            let lcd_pixel_size = ctx.gpu.config.width as f32
+            let lcd_pixel_size = gpu.config.width as f32
                 / (game.camera_controller.projection.scale() * game.pixels_per_unit.x);
             let min_hardness = 3.0_f32;
             let max_hardness = 7.0_f32;
@@ -311,7 +367,7 @@ impl LcdFilter {
             min_hardness + v * (max_hardness - min_hardness)
         };
 
-        let color_attachment_extent = ctx.gpu.color_attachment.extent;
+        let color_attachment_extent = gpu.color_attachment.extent;
         let layer_count = color_attachment_extent.depth_or_array_layers;
         let current_layer = ctx.frame_idx % layer_count;
         let history_count = self
@@ -325,8 +381,8 @@ impl LcdFilter {
             .min(self.frames_available_for_hysteresis as u32)
             .max(1_u32);
 
-        let ctx_width = ctx.gpu.config.width as f32;
-        let ctx_height = ctx.gpu.config.height as f32;
+        let ctx_width = gpu.config.width as f32;
+        let ctx_height = gpu.config.height as f32;
         let lcd_resolution_width =
             game.camera_controller.projection.scale() * game.pixels_per_unit.x;
         let lcd_pixel_size = ctx_width / lcd_resolution_width;
@@ -346,7 +402,7 @@ impl LcdFilter {
             .set_color_attachment_history_count(history_count)
             .set_lcd_resolution(lcd_resolution_width, lcd_resolution_height);
 
-        self.uniforms.write(&mut ctx.gpu.queue);
+        self.uniforms.write(&mut gpu.queue);
     }
 
     pub fn render(
@@ -398,6 +454,15 @@ impl LcdFilter {
             .min(gpu.color_attachment.layer_array_views.len());
     }
 
+    fn create_tonemap(gpu: &gpu_state::GpuState, palette: &Palette) -> Result<Texture> {
+        Texture::from_image(
+            &gpu.device,
+            &gpu.queue,
+            &palette.to_image(),
+            Some("LcdFilter Tonemap"),
+        )
+    }
+
     fn create_display_pass_textures_bind_group(
         gpu: &gpu_state::GpuState,
         layout: &wgpu::BindGroupLayout,
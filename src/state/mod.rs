@@ -1,9 +1,12 @@
 pub mod app_state;
 pub mod constants;
+pub mod continue_menu;
 pub mod debug_overlay;
 pub mod events;
 pub mod game_controller;
 pub mod game_state;
 pub mod game_ui;
 pub mod gpu_state;
+pub mod headless;
 pub mod lcd_filter;
+pub mod pause_menu;
@@ -0,0 +1,154 @@
+use crate::input::Action;
+
+// ---------------------------------------------------------------------------------------------------------------------
+
+/// The pause menu's options, top to bottom, as laid out in game_ui.tmx's PauseMenu layer
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MenuItem {
+    Resume,
+    Restart,
+    Palette,
+    MusicVolume,
+    SfxVolume,
+    Quit,
+}
+
+impl MenuItem {
+    pub const ALL: [MenuItem; 6] = [
+        MenuItem::Resume,
+        MenuItem::Restart,
+        MenuItem::Palette,
+        MenuItem::MusicVolume,
+        MenuItem::SfxVolume,
+        MenuItem::Quit,
+    ];
+}
+
+/// What the player chose to do from the pause menu
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MenuCommand {
+    Resume,
+    /// Restart from the last checkpoint reached
+    Restart,
+    CyclePalette {
+        forward: bool,
+    },
+    /// Raise or lower the music volume by `steps` tenths
+    AdjustMusicVolume {
+        steps: i32,
+    },
+    /// Raise or lower the sound effects volume by `steps` tenths
+    AdjustSfxVolume {
+        steps: i32,
+    },
+    Quit,
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+
+/// Selection state of the pause menu. Up and down move the selection, wrapping around; shoot
+/// chooses the selected item, left and right adjust the palette and volumes, and pause resumes.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct PauseMenu {
+    selected_index: usize,
+}
+
+impl PauseMenu {
+    pub fn selected(&self) -> MenuItem {
+        MenuItem::ALL[self.selected_index]
+    }
+
+    /// Index of the selected item, from the top of the menu
+    pub fn selected_index(&self) -> usize {
+        self.selected_index
+    }
+
+    /// Handles a press of `action`, returning the command the player chose, if any.
+    pub fn process_action(&mut self, action: Action) -> Option<MenuCommand> {
+        let count = MenuItem::ALL.len();
+        match action {
+            Action::MenuUp => {
+                self.selected_index = (self.selected_index + count - 1) % count;
+                None
+            }
+            Action::MenuDown => {
+                self.selected_index = (self.selected_index + 1) % count;
+                None
+            }
+            Action::MoveLeft => self.adjust(-1),
+            Action::MoveRight => self.adjust(1),
+            Action::Shoot => match self.selected() {
+                MenuItem::Resume => Some(MenuCommand::Resume),
+                MenuItem::Restart => Some(MenuCommand::Restart),
+                MenuItem::Quit => Some(MenuCommand::Quit),
+                _ => self.adjust(1),
+            },
+            Action::Pause => Some(MenuCommand::Resume),
+            _ => None,
+        }
+    }
+
+    fn adjust(&self, steps: i32) -> Option<MenuCommand> {
+        match self.selected() {
+            MenuItem::Palette => Some(MenuCommand::CyclePalette { forward: steps > 0 }),
+            MenuItem::MusicVolume => Some(MenuCommand::AdjustMusicVolume { steps }),
+            MenuItem::SfxVolume => Some(MenuCommand::AdjustSfxVolume { steps }),
+            _ => None,
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod pause_menu_tests {
+    use super::*;
+
+    #[test]
+    fn selection_wraps_around() {
+        let mut menu = PauseMenu::default();
+        assert_eq!(menu.selected(), MenuItem::Resume);
+        assert_eq!(menu.process_action(Action::MenuUp), None);
+        assert_eq!(menu.selected(), MenuItem::Quit);
+        menu.process_action(Action::MenuDown);
+        menu.process_action(Action::MenuDown);
+        assert_eq!(menu.selected(), MenuItem::Restart);
+        assert_eq!(menu.selected_index(), 1);
+    }
+
+    #[test]
+    fn items_produce_commands() {
+        let mut menu = PauseMenu::default();
+        assert_eq!(
+            menu.process_action(Action::Shoot),
+            Some(MenuCommand::Resume)
+        );
+        assert_eq!(
+            menu.process_action(Action::Pause),
+            Some(MenuCommand::Resume)
+        );
+        // left and right don't affect items which aren't adjustable
+        assert_eq!(menu.process_action(Action::MoveLeft), None);
+
+        menu.process_action(Action::MenuDown);
+        menu.process_action(Action::MenuDown);
+        assert_eq!(
+            menu.process_action(Action::MoveLeft),
+            Some(MenuCommand::CyclePalette { forward: false })
+        );
+
+        menu.process_action(Action::MenuDown);
+        assert_eq!(
+            menu.process_action(Action::MoveLeft),
+            Some(MenuCommand::AdjustMusicVolume { steps: -1 })
+        );
+        assert_eq!(
+            menu.process_action(Action::Shoot),
+            Some(MenuCommand::AdjustMusicVolume { steps: 1 })
+        );
+
+        menu.process_action(Action::MenuDown);
+        menu.process_action(Action::MenuDown);
+        assert_eq!(menu.process_action(Action::Shoot), Some(MenuCommand::Quit));
+    }
+}
@@ -2,6 +2,8 @@ use anyhow::*;
 use image::GenericImageView;
 use std::path::Path;
 
+use crate::assets;
+
 pub struct Texture {
     pub texture: wgpu::Texture,
     // the texture view for the texture object
@@ -25,7 +27,7 @@ impl Texture {
     ) -> Result<Self> {
         let path_copy = path.as_ref().to_path_buf();
         let label = path_copy.to_str();
-        let img = image::open(path)?;
+        let img = image::load_from_memory(&assets::read(path)?)?;
         Self::from_image(device, queue, &img, label)
     }
 
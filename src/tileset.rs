@@ -1,32 +1,43 @@
-use crate::{collision, util::Bounds};
+use crate::{assets, collision, property::Properties, util::Bounds};
 use anyhow::{Context, Result};
 use cgmath::*;
 use std::collections::HashMap;
-use std::fs::File;
-use std::io::BufReader;
-use std::path::Path;
-use xml::reader::{EventReader, XmlEvent};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use xml::{
+    attribute::OwnedAttribute,
+    reader::{EventReader, XmlEvent},
+};
 
 #[derive(Clone, Debug)]
 pub struct Tile {
     pub id: u32,
-    properties: HashMap<String, String>,
+    properties: Properties,
 }
 
 impl Tile {
-    fn new(id: u32) -> Self {
+    pub(crate) fn new(id: u32) -> Self {
         Tile {
             id,
-            properties: HashMap::new(),
+            properties: Properties::new(&Self::owner(id)),
         }
     }
 
+    /// Describes the tile in errors about its properties
+    fn owner(id: u32) -> String {
+        format!("<tile id=\"{}\">", id)
+    }
+
+    pub fn properties(&self) -> &Properties {
+        &self.properties
+    }
+
     pub fn shape(&self) -> collision::Shape {
         use collision::Shape;
 
-        let collision_shape = self.properties.get("collision_shape");
+        let collision_shape = self.properties.string("collision_shape");
         if let Some(collision_shape) = collision_shape {
-            match collision_shape.as_str() {
+            match collision_shape {
                 "square" => Shape::Square,
                 "triangle_ne" => Shape::NorthEast,
                 "triangle_se" => Shape::SouthEast,
@@ -40,29 +51,17 @@ impl Tile {
     }
 
     pub fn has_property(&self, name: &str) -> bool {
-        self.properties.contains_key(name)
+        self.properties.contains(name)
     }
 
+    /// True if the named property is set to true
     pub fn boolean_property(&self, name: &str) -> bool {
-        self.get_property(name) == Some("true")
-    }
-
-    pub fn float_property(&self, name: &str) -> f32 {
-        self.get_property(name)
-            .unwrap_or_else(|| panic!("Expected property\"{}\"", name))
-            .parse::<f32>()
-            .unwrap_or_else(|_| panic!("Expected property \"{}\" to parse to f32", name))
-    }
-
-    pub fn int_property(&self, name: &str) -> i32 {
-        self.get_property(name)
-            .unwrap_or_else(|| panic!("Expected property\"{}\"", name))
-            .parse::<i32>()
-            .unwrap_or_else(|_| panic!("Expected property \"{}\" to parse to i32", name))
+        self.properties.flag(name)
     }
 
+    /// Returns the named string property, or None if it's unset or not a string
     pub fn get_property(&self, name: &str) -> Option<&str> {
-        self.properties.get(name).map(|p| p.as_str())
+        self.properties.string(name)
     }
 }
 
@@ -76,6 +75,7 @@ pub struct TileSet {
     pub tile_height: u32,
     pub spacing: u32,
     pub columns: u32,
+    pub properties: Properties,
     tiles: HashMap<u32, Tile>,
 }
 
@@ -83,10 +83,30 @@ impl TileSet {
     pub fn new_tsx<P: AsRef<Path>>(tsx_file: P) -> Result<Self> {
         let path_copy = tsx_file.as_ref().to_path_buf();
         let file =
-            File::open(tsx_file).with_context(|| format!("Unable to open {:?}", path_copy))?;
-        let file = BufReader::new(file);
-        let parser = EventReader::new(file);
+            assets::open(tsx_file).with_context(|| format!("Unable to open {:?}", path_copy))?;
+        let mut events = EventReader::new(file).into_iter();
 
+        while let Some(e) = events.next() {
+            if let Ok(XmlEvent::StartElement {
+                name, attributes, ..
+            }) = e
+            {
+                if name.local_name == "tileset" {
+                    return Self::parse(attributes, &mut events)
+                        .with_context(|| format!("Unable to parse tileset {:?}", path_copy));
+                }
+            }
+        }
+
+        anyhow::bail!("Expected a <tileset> element in {:?}", path_copy)
+    }
+
+    /// Parses a <tileset> element with the provided attributes, consuming `events` through its closing
+    /// </tileset>. This is shared by tsx files and tilesets embedded in tmx maps.
+    pub fn parse<I>(attributes: Vec<OwnedAttribute>, events: &mut I) -> Result<Self>
+    where
+        I: Iterator<Item = xml::reader::Result<XmlEvent>>,
+    {
         let mut image_path: Option<String> = None;
         let mut image_width: Option<u32> = None;
         let mut image_height: Option<u32> = None;
@@ -95,63 +115,60 @@ impl TileSet {
         let mut tile_count: Option<u32> = None;
         let mut tiles: Vec<Tile> = vec![];
         let mut current_tile: Option<Tile> = None;
+        let mut properties = Properties::new("<tileset>");
         let mut spacing: Option<u32> = None;
         let mut columns: Option<u32> = None;
 
-        for e in parser {
+        // <tileset> attributes
+        for attr in attributes {
+            match attr.name.local_name.as_str() {
+                "spacing" => {
+                    spacing = Some(
+                        attr.value
+                            .parse()
+                            .context("Expected to parse 'spacing' to u32")?,
+                    )
+                }
+
+                "columns" => {
+                    columns = Some(
+                        attr.value
+                            .parse()
+                            .context("Expected to parse 'columns' to u32")?,
+                    )
+                }
+
+                "tilewidth" => {
+                    tile_width = Some(
+                        attr.value
+                            .parse()
+                            .context("Expected to parse 'tilewidth' to u32")?,
+                    )
+                }
+                "tileheight" => {
+                    tile_height = Some(
+                        attr.value
+                            .parse()
+                            .context("Expected to parse 'tileheight' to u32")?,
+                    )
+                }
+                "tilecount" => {
+                    tile_count = Some(
+                        attr.value
+                            .parse()
+                            .context("Expected to parse 'tilecount' to u32")?,
+                    )
+                }
+                _ => {}
+            }
+        }
+
+        while let Some(e) = events.next() {
             match e {
                 Ok(XmlEvent::StartElement {
                     name, attributes, ..
                 }) => {
                     match name.local_name.as_str() {
-                        //
-                        // Handle <tileset>
-                        //
-                        "tileset" => {
-                            for attr in attributes {
-                                match attr.name.local_name.as_str() {
-                                    "spacing" => {
-                                        spacing = Some(
-                                            attr.value
-                                                .parse()
-                                                .context("Expected to parse 'spacing' to u32")?,
-                                        )
-                                    }
-
-                                    "columns" => {
-                                        columns = Some(
-                                            attr.value
-                                                .parse()
-                                                .context("Expected to parse 'columns' to u32")?,
-                                        )
-                                    }
-
-                                    "tilewidth" => {
-                                        tile_width = Some(
-                                            attr.value
-                                                .parse()
-                                                .context("Expected to parse 'tilewidth' to u32")?,
-                                        )
-                                    }
-                                    "tileheight" => {
-                                        tile_height = Some(
-                                            attr.value
-                                                .parse()
-                                                .context("Expected to parse 'tileheight' to u32")?,
-                                        )
-                                    }
-                                    "tilecount" => {
-                                        tile_count = Some(
-                                            attr.value
-                                                .parse()
-                                                .context("Expected to parse 'tilecount' to u32")?,
-                                        )
-                                    }
-                                    _ => {}
-                                }
-                            }
-                        }
-
                         //
                         // Handle <image> block
                         //
@@ -175,7 +192,7 @@ impl TileSet {
                         }
 
                         //
-                        // Handle <tile> block - sets current_tile to be mutated by <property> block
+                        // Handle <tile> block - sets current_tile to be mutated by <properties> block
                         //
                         "tile" => {
                             let mut id: Option<u32> = None;
@@ -193,42 +210,29 @@ impl TileSet {
                         }
 
                         //
-                        // Handle <property> block - mutates the current_tile
+                        // Handle <properties> block - of the current_tile, or the tileset itself
                         //
-                        "property" => {
-                            let mut attr_name: Option<String> = None;
-                            let mut attr_value: Option<String> = None;
-                            for attr in attributes {
-                                match attr.name.local_name.as_str() {
-                                    "name" => attr_name = Some(attr.value),
-                                    "value" => attr_value = Some(attr.value),
-                                    _ => {}
-                                }
-                            }
-                            let attr_name = attr_name
-                                .context("Expected <property> to have a 'name' attribute")?;
-                            let attr_value = attr_value
-                                .context("Expected <property> to have a 'value' attribute")?;
-
+                        "properties" => {
                             if let Some(tile) = &mut current_tile {
-                                tile.properties.insert(attr_name, attr_value);
+                                tile.properties = Properties::parse(&Tile::owner(tile.id), events)?;
                             } else {
-                                anyhow::bail!("Expected current_tile to be Some when handling <property> block");
+                                properties = Properties::parse("<tileset>", events)?;
                             }
                         }
                         _ => {}
                     }
                 }
-                Ok(XmlEvent::EndElement { name }) => {
-                    if name.local_name.as_str() == "tile" {
-                        //
-                        //  Closes the <tile> block by assigning the current_tile
-                        //
-                        let tile = current_tile
-                            .take()
-                            .context("Expected to have a valid Tile when reaching </tile>")?;
-                        tiles.push(tile);
-                    }
+                Ok(XmlEvent::EndElement { name }) if name.local_name.as_str() == "tile" => {
+                    //
+                    //  Closes the <tile> block by assigning the current_tile
+                    //
+                    let tile = current_tile
+                        .take()
+                        .context("Expected to have a valid Tile when reaching </tile>")?;
+                    tiles.push(tile);
+                }
+                Ok(XmlEvent::EndElement { name }) if name.local_name.as_str() == "tileset" => {
+                    break;
                 }
                 Err(_) => {}
                 _ => {}
@@ -268,6 +272,7 @@ impl TileSet {
             tiles: tiles_map,
             spacing,
             columns,
+            properties,
         })
     }
 
@@ -328,3 +333,27 @@ impl TileSet {
         )
     }
 }
+
+// ---------------------------------------------------------------------------------------------------------------------
+
+/// Caches tilesets loaded from tsx files by path, so a tileset referenced by several maps is parsed once.
+#[derive(Default)]
+pub struct TileSetCache {
+    tilesets: HashMap<PathBuf, Rc<TileSet>>,
+}
+
+impl TileSetCache {
+    /// Returns the tileset loaded from `tsx_file`, loading it if it isn't already cached.
+    pub fn load<P: AsRef<Path>>(&mut self, tsx_file: P) -> Result<Rc<TileSet>> {
+        let tsx_file = tsx_file.as_ref();
+        // normalize so different relative paths to the same file share an entry
+        let key = assets::normalize(tsx_file);
+        if let Some(tileset) = self.tilesets.get(&key) {
+            return Ok(tileset.clone());
+        }
+
+        let tileset = Rc::new(TileSet::new_tsx(tsx_file)?);
+        self.tilesets.insert(key, tileset.clone());
+        Ok(tileset)
+    }
+}
@@ -1,8 +1,57 @@
-use std::hash::Hash;
+use std::{
+    fs,
+    hash::Hash,
+    path::{Path, PathBuf},
+};
 use wgpu::util::DeviceExt;
 
+use anyhow::{Context, Result};
 use cgmath::*;
 
+/// Name of the game's directory within the user's data and configuration directories
+pub const APP_DIR_NAME: &str = "platformer";
+
+/// The per-user data directory on this platform, per the XDG base directory spec on linux
+pub fn user_data_dir() -> Option<PathBuf> {
+    if cfg!(target_os = "windows") {
+        env_dir("APPDATA")
+    } else if cfg!(target_os = "macos") {
+        env_dir("HOME").map(|home| home.join("Library").join("Application Support"))
+    } else {
+        env_dir("XDG_DATA_HOME")
+            .or_else(|| env_dir("HOME").map(|home| home.join(".local").join("share")))
+    }
+}
+
+/// The per-user configuration directory on this platform, per the XDG base directory spec on linux
+pub fn user_config_dir() -> Option<PathBuf> {
+    if cfg!(target_os = "windows") {
+        env_dir("APPDATA")
+    } else if cfg!(target_os = "macos") {
+        env_dir("HOME").map(|home| home.join("Library").join("Application Support"))
+    } else {
+        env_dir("XDG_CONFIG_HOME").or_else(|| env_dir("HOME").map(|home| home.join(".config")))
+    }
+}
+
+/// Writes `contents` to `path`, creating its directory if needed. The contents are written to a
+/// temporary file first, so a failed write can't corrupt an existing file.
+pub fn write_file_atomically(path: &Path, contents: &str) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).with_context(|| format!("Unable to create directory {:?}", dir))?;
+    }
+    let temp_path = path.with_extension("tmp");
+    fs::write(&temp_path, contents).with_context(|| format!("Unable to write {:?}", temp_path))?;
+    fs::rename(&temp_path, path).with_context(|| format!("Unable to replace {:?}", path))
+}
+
+/// The absolute directory named by environment variable `name`, if set
+fn env_dir(name: &str) -> Option<PathBuf> {
+    std::env::var_os(name)
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_absolute())
+}
+
 pub fn rel_eq(a: f32, b: f32) -> bool {
     (a - b).abs() < f32::EPSILON
 }
@@ -0,0 +1,279 @@
+use std::{
+    collections::HashSet,
+    fmt,
+    path::{Path, PathBuf},
+};
+
+use cgmath::*;
+
+use crate::{
+    entities::{self, boss_fish, power_up, spawn_point, ui_digit},
+    map::{self, LayerRole},
+    property::Properties,
+    tileset,
+};
+
+// ---------------------------------------------------------------------------------------------------------------------
+
+/// A problem found in a map which would prevent the game from playing it, or playing it correctly.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Problem {
+    pub file: PathBuf,
+    /// The position of the problem in the map's tile coordinates, as displayed by Tiled
+    pub tile: Option<Point2<i32>>,
+    pub message: String,
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.tile {
+            Some(tile) => write!(
+                f,
+                "{}: tile ({}, {}): {}",
+                self.file.display(),
+                tile.x,
+                tile.y,
+                self.message
+            ),
+            None => write!(f, "{}: {}", self.file.display(), self.message),
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+
+/// Loads the level map at `tmx_file` and returns every problem found with it, rather than
+/// stopping at the first. Returns an empty vector for a valid map.
+pub fn validate_map(tmx_file: &Path, tilesets: &mut tileset::TileSetCache) -> Vec<Problem> {
+    match map::Map::new_tmx(tmx_file, tilesets) {
+        Ok(map) => validate_loaded_map(tmx_file, &map),
+        Err(e) => vec![Problem {
+            file: tmx_file.to_path_buf(),
+            tile: None,
+            message: format!("Unable to load map: {:#}", e),
+        }],
+    }
+}
+
+/// Returns every problem found with `map`, already loaded from `tmx_file`.
+pub fn validate_loaded_map(tmx_file: &Path, map: &map::Map) -> Vec<Problem> {
+    let mut validator = Validator {
+        file: tmx_file.to_path_buf(),
+        problems: vec![],
+    };
+    validator.validate(map);
+    validator.problems
+}
+
+struct Validator {
+    file: PathBuf,
+    problems: Vec<Problem>,
+}
+
+impl Validator {
+    fn report<S: Into<String>>(&mut self, tile: Option<Point2<i32>>, message: S) {
+        self.problems.push(Problem {
+            file: self.file.clone(),
+            tile,
+            message: message.into(),
+        });
+    }
+
+    fn validate(&mut self, map: &map::Map) {
+        let mut entity_layers = vec![];
+        let mut ground = HashSet::new();
+        let mut has_collision_layer = false;
+
+        for layer in &map.layers {
+            match layer.role() {
+                Ok(LayerRole::Stage) => {
+                    if let Err(e) = layer.z_depth() {
+                        self.report(None, format!("{:#}", e));
+                    }
+                }
+                Ok(LayerRole::Entities) => entity_layers.push(layer),
+                Ok(LayerRole::Entity) => {
+                    let sprites = map.generate_sprites(layer, |_, _| 0.0);
+                    if let Err(e) = entities::instantiate_layer_entity(layer, sprites) {
+                        self.report(None, format!("{:#}", e));
+                    }
+                }
+                Err(e) => self.report(None, format!("{:#}", e)),
+            }
+
+            if layer.collision() {
+                has_collision_layer = true;
+                ground.extend(
+                    map.tiles(layer)
+                        .filter(|(_, tile)| tile.boolean_property("ground"))
+                        .map(|(position, _)| position),
+                );
+            }
+        }
+
+        if entity_layers.is_empty() {
+            self.report(None, "Map has no layer with role \"entities\"");
+        }
+        if !has_collision_layer {
+            self.report(None, "Map has no layer with the \"collision\" property");
+        }
+
+        let mut checkpoints = vec![];
+        let no_properties = Properties::default();
+        for layer in entity_layers {
+            for (position, tile) in map.tiles(layer) {
+                if let Some(class_name) = tile.get_property("entity_class") {
+                    self.validate_entity(position, class_name, tile, &no_properties);
+                    if class_name == "CheckPoint" {
+                        checkpoints.push(position);
+                    }
+                }
+            }
+        }
+
+        // Objects whose class isn't an entity class are ignored by Map::generate_object_entities,
+        // as they are here; an entity_class tile property naming an unknown class is reported.
+        let tile_size = map.tile_size().cast::<i32>().unwrap();
+        let untiled = tileset::Tile::new(0);
+        for obj in map.object_groups.iter().flat_map(|g| g.objects.iter()) {
+            let tile = map.object_tile(obj);
+            let class_name = if !obj.class.is_empty() {
+                if entities::instantiate_entity_by_class_name(&obj.class).is_none() {
+                    continue;
+                }
+                obj.class.as_str()
+            } else {
+                match tile.and_then(|tile| tile.get_property("entity_class")) {
+                    Some(class_name) => class_name,
+                    None => continue,
+                }
+            };
+
+            // tile objects are positioned by their bottom-left corner, all others by their top-left
+            let row = obj.y.div_euclid(tile_size.y);
+            let position = point2(
+                obj.x.div_euclid(tile_size.x),
+                if tile.is_some() { row - 1 } else { row },
+            );
+            self.validate_entity(
+                position,
+                class_name,
+                tile.unwrap_or(&untiled),
+                &obj.properties,
+            );
+            if class_name == "CheckPoint" {
+                checkpoints.push(position);
+            }
+        }
+
+        if checkpoints.is_empty() {
+            self.report(None, "Map has no CheckPoint for Firebrand to start at");
+        }
+
+        // A checkpoint is reachable if it's not embedded in the ground, and Firebrand would
+        // land on ground beneath it rather than fall out of the stage.
+        for checkpoint in checkpoints {
+            if ground.contains(&checkpoint) {
+                self.report(Some(checkpoint), "CheckPoint is embedded in ground");
+            } else if !ground
+                .iter()
+                .any(|g| g.x == checkpoint.x && g.y > checkpoint.y)
+            {
+                self.report(Some(checkpoint), "CheckPoint has no ground beneath it");
+            }
+        }
+    }
+
+    fn validate_entity(
+        &mut self,
+        position: Point2<i32>,
+        class_name: &str,
+        tile: &tileset::Tile,
+        properties: &Properties,
+    ) {
+        let position = Some(position);
+        if entities::instantiate_entity_by_class_name(class_name).is_none() {
+            self.report(position, format!("Unknown entity_class \"{}\"", class_name));
+            return;
+        }
+
+        // the same checks the entities make when they're initialized from the map
+        let result = match class_name {
+            "SpawnPoint" => spawn_point::spawned_entity_class(tile, properties).map(|_| ()),
+            "BossFish" => boss_fish::Arena::from_tile(tile).map(|_| ()),
+            "PowerUp" => power_up::Type::from_tile(tile).map(|_| ()),
+            "UiDigit" => ui_digit::validate_properties(properties),
+            _ => Ok(()),
+        };
+        if let Err(e) = result {
+            self.report(position, format!("{:#}", e));
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod validation_tests {
+    use super::*;
+
+    #[test]
+    fn shipped_level_is_valid() {
+        let problems = validate_map(
+            Path::new("res/level_1.tmx"),
+            &mut tileset::TileSetCache::default(),
+        );
+        assert_eq!(problems, vec![]);
+    }
+
+    #[test]
+    fn every_problem_is_reported_with_its_tile() {
+        let problems = validate_map(
+            Path::new("test_fixtures/maps/invalid_level.tmx"),
+            &mut tileset::TileSetCache::default(),
+        );
+        let problems = problems
+            .iter()
+            .map(|p| (p.tile.map(|t| (t.x, t.y)), p.message.as_str()))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            problems,
+            vec![
+                (
+                    Some((2, 1)),
+                    "BossFish arena: <tile id=\"5\"> has no property \"arena_height\""
+                ),
+                (
+                    Some((1, 2)),
+                    "SpawnPoint has no \"spawned_entity_class\" property"
+                ),
+                (
+                    Some((2, 2)),
+                    "Unknown powerup_type \"sword\"; expected \"vial\" or \"heart\""
+                ),
+                (Some((3, 2)), "Unknown entity_class \"Dragon\""),
+                (
+                    Some((4, 0)),
+                    "SpawnPoint has no \"spawned_entity_class\" property"
+                ),
+                (
+                    Some((0, 0)),
+                    "Unknown UiDigit tracking \"score\"; expected \"vials\", \"lives\", \"music_volume\", \"sfx_volume\", \"continue_countdown\" or \"continues\""
+                ),
+                (Some((1, 0)), "UiDigit digit must be from 0 to 9, found -1"),
+                (Some((0, 2)), "CheckPoint has no ground beneath it"),
+            ]
+        );
+    }
+
+    #[test]
+    fn unloadable_map_is_reported() {
+        let problems = validate_map(
+            Path::new("test_fixtures/maps/missing.tmx"),
+            &mut tileset::TileSetCache::default(),
+        );
+        assert_eq!(problems.len(), 1);
+        assert!(problems[0].message.starts_with("Unable to load map"));
+    }
+}